@@ -0,0 +1,74 @@
+/// The status of the receiver of a *High Tension Message*, sent along with
+/// its acknowledgement when [`HiConfig::ack_status`] is set.
+///
+/// Senders get it with [`HiStream::last_ack`].
+///
+/// [`HiConfig::ack_status`]: struct.HiConfig.html#method.ack_status
+/// [`HiStream::last_ack`]: struct.HiStream.html#method.last_ack
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AckStatus {
+    /// Bytes of the wire frame received, trailer included.
+    pub bytes: u64,
+    /// Checksum of the wire frame received, trailer included.
+    pub checksum: u64,
+    /// Backlog of the receiver, as set with [`HiStream::set_queue_depth`].
+    ///
+    /// [`HiStream::set_queue_depth`]: struct.HiStream.html#method.set_queue_depth
+    pub queue_depth: u64,
+}
+
+impl AckStatus {
+    /// Size of the status frame following the acknowledgement.
+    pub(crate) const SIZE: usize = 24;
+
+    pub(crate) fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..8].copy_from_slice(&self.bytes.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.queue_depth.to_le_bytes());
+        bytes
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let word = |i: usize| {
+            let mut word = [0; 8];
+            word.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+            u64::from_le_bytes(word)
+        };
+        AckStatus {
+            bytes: word(0),
+            checksum: word(1),
+            queue_depth: word(2),
+        }
+    }
+}
+
+/// A Fletcher-like checksum of a wire frame, computed 8 bytes at a time so
+/// that it keeps up with the network.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Checksum {
+    sum: u64,
+    sum_of_sums: u64,
+    bytes: u64,
+}
+
+impl Checksum {
+    /// Account for `bytes`, made of whole words.
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks_exact(8) {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.sum = self.sum.wrapping_add(u64::from_le_bytes(word));
+            self.sum_of_sums = self.sum_of_sums.wrapping_add(self.sum);
+        }
+        self.bytes += bytes.len() as u64;
+    }
+
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        self.sum ^ self.sum_of_sums.rotate_left(32)
+    }
+}
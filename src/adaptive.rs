@@ -0,0 +1,87 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::pod::bytes_of;
+use crate::{write_some, Result};
+
+/// Chunk size the tuning starts from, in bytes, unless one is configured.
+const INITIAL_CHUNK: usize = 256 << 10;
+
+/// Bounds of the chunk size, in bytes.
+const MIN_CHUNK: usize = 16 << 10;
+const MAX_CHUNK: usize = 64 << 20;
+
+/// Growth of the chunk size after every write which went smoothly, in bytes.
+const STEP: usize = 64 << 10;
+
+/// A write stalls when its throughput falls below the usual one divided by
+/// `STALL_FACTOR`. Writes shorter than `STALL_MIN` are too short to be timed
+/// reliably, and never stall.
+const STALL_FACTOR: f64 = 4.0;
+const STALL_MIN: Duration = Duration::from_millis(1);
+
+/// The chunk size of the writes of a [`HiStream`] with
+/// [`HiConfig::adaptive_chunks`], tuned like TCP tunes its window: it grows
+/// by a fixed step after every write which went smoothly, and is halved
+/// after every write which stalled.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`HiConfig::adaptive_chunks`]: struct.HiConfig.html#method.adaptive_chunks
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkTuner {
+    size: usize,
+    /// Smoothed throughput of the writes, in bytes per second.
+    rate: Option<f64>,
+}
+
+impl ChunkTuner {
+    /// Start tuning from `initial` bytes, or a default size if `None`.
+    pub(crate) fn new(initial: Option<usize>) -> Self {
+        ChunkTuner {
+            size: initial.unwrap_or(INITIAL_CHUNK).clamp(MIN_CHUNK, MAX_CHUNK),
+            rate: None,
+        }
+    }
+
+    /// The current chunk size, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Send `data` as part of a *High Tension Message* like `hiwrite`, a
+    /// chunk at a time, timing every write to tune the chunk size.
+    pub(crate) fn write<W: Write + ?Sized>(&mut self, stream: &mut W, data: &[f64]) -> Result<()> {
+        let slice = bytes_of(data);
+        let mut i = 0;
+        while i < slice.len() {
+            let end = slice.len().min(i + self.size);
+            let start = Instant::now();
+            let n = write_some(stream, &slice[i..end])?;
+            self.observe(n, start.elapsed());
+            i += n;
+        }
+        Ok(())
+    }
+
+    /// Take a write of `bytes` which took `elapsed` into account.
+    fn observe(&mut self, bytes: usize, elapsed: Duration) {
+        let rate = bytes as f64 / elapsed.as_secs_f64().max(1e-9);
+        let stalled = match self.rate {
+            Some(usual) => elapsed >= STALL_MIN && rate * STALL_FACTOR < usual,
+            None => false,
+        };
+        self.size = if stalled {
+            (self.size / 2).max(MIN_CHUNK)
+        } else {
+            (self.size + STEP).min(MAX_CHUNK)
+        };
+        // Stalls do not count towards the usual throughput, so that a few of
+        // them in a row keep halving the chunk size
+        if !stalled {
+            self.rate = Some(match self.rate {
+                Some(usual) => usual + (rate - usual) / 8.0,
+                None => rate,
+            });
+        }
+    }
+}
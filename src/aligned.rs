@@ -0,0 +1,182 @@
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::io::{Read, Write};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::{read_into, RecvBuffer, Result, DEFAULT_SIZE, DELIMITER_NAN};
+
+/// An array of `f64` whose first value is aligned on a chosen boundary.
+///
+/// This is what [`hiread_aligned`] receives into, for SIMD kernels or GPU
+/// uploads which benefit from aligned sources. It dereferences to a slice.
+///
+/// [`hiread_aligned`]: fn.hiread_aligned.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::AlignedBuf;
+///
+/// let buf = AlignedBuf::new(64);
+/// assert!(buf.is_empty());
+/// assert_eq!(buf.as_ptr() as usize % 64, 0);
+/// ```
+pub struct AlignedBuf {
+    ptr: NonNull<f64>,
+    len: usize,
+    cap: usize,
+    align: usize,
+}
+
+// SAFETY: `AlignedBuf` owns its memory, like a `Vec<f64>`
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// Create an empty buffer aligned on `align` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two, or is smaller than the
+    /// alignment of `f64`.
+    pub fn new(align: usize) -> Self {
+        assert!(
+            align.is_power_of_two() && align >= std::mem::align_of::<f64>(),
+            "invalid alignment {}",
+            align
+        );
+        AlignedBuf {
+            // Dangling, but aligned, like an empty `Vec`
+            ptr: NonNull::new(align as *mut f64).unwrap(),
+            len: 0,
+            cap: 0,
+            align,
+        }
+    }
+
+    /// The alignment of this buffer, in bytes.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    fn layout(&self, cap: usize) -> Layout {
+        let size = cap.checked_mul(8).expect("capacity overflow");
+        Layout::from_size_align(size, self.align).expect("capacity overflow")
+    }
+}
+
+impl RecvBuffer for AlignedBuf {
+    fn words_mut(&mut self) -> &mut [f64] {
+        self
+    }
+
+    fn resize(&mut self, len: usize) {
+        // A first allocation is zeroed lazily by the allocator
+        let zeroed = self.cap == 0;
+        if len > self.cap {
+            let layout = self.layout(len);
+            // SAFETY: the layouts have a non-zero size, and the one of the
+            // current allocation is rebuilt from its capacity and alignment
+            let ptr = unsafe {
+                if self.cap == 0 {
+                    alloc::alloc_zeroed(layout)
+                } else {
+                    alloc::realloc(
+                        self.ptr.as_ptr() as *mut u8,
+                        self.layout(self.cap),
+                        layout.size(),
+                    )
+                }
+            };
+            self.ptr = match NonNull::new(ptr as *mut f64) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout),
+            };
+            self.cap = len;
+        }
+        if len > self.len && !zeroed {
+            // SAFETY: the range is within the allocation
+            unsafe {
+                self.ptr
+                    .as_ptr()
+                    .add(self.len)
+                    .write_bytes(0, len - self.len)
+            };
+        }
+        self.len = len;
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        // SAFETY: the first `len` values are initialized
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [f64] {
+        // SAFETY: the first `len` values are initialized
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        if self.cap > 0 {
+            // SAFETY: the memory was allocated with this layout
+            unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, self.layout(self.cap)) };
+        }
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("align", &self.align)
+            .field("data", &&self[..])
+            .finish()
+    }
+}
+
+/// Read a *High Tension Message* from the `stream`, into a buffer aligned on
+/// `align` bytes.
+///
+/// This function is blocking, and allocates like [`hiread`].
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Panics
+///
+/// Panics if `align` is not a power of two, or is smaller than the alignment
+/// of `f64`.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hiread_aligned;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// // Page aligned, ready for a DMA transfer
+/// let data = hiread_aligned(&mut stream, 4096)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_aligned<S: Read + Write>(stream: &mut S, align: usize) -> Result<AlignedBuf> {
+    let mut buf = AlignedBuf::new(align);
+    buf.resize(DEFAULT_SIZE);
+    read_into(stream, &mut buf, 0, &DELIMITER_NAN)?;
+    Ok(buf)
+}
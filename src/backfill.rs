@@ -0,0 +1,223 @@
+use std::io::{Read, Write};
+
+use crate::{Direction, Error, HiStream, Message, Result};
+
+/// Prefix of the text messages asking a [`JournaledSender`] to send a
+/// message again, before its sequence number.
+///
+/// [`JournaledSender`]: struct.JournaledSender.html
+pub const RESEND_PREFIX: &str = "RESEND ";
+
+/// A sender numbering its messages, and sending them again from its
+/// [`Journal`] when the receiver asks for it.
+///
+/// The sequence number of a message is its rank among the messages sent into
+/// the journal, from 0: numbers carry on across runs reopening the same
+/// journal. With [`HiConfig::user_headers`], each message is tagged with its
+/// sequence number, in decimal, as its user header.
+///
+/// The receiver, having found a gap in the sequence numbers after a
+/// reconnect for instance, asks for a message with a `RESEND <seq>` text
+/// message, sent by [`request_resend`]. The sender handles these requests
+/// while receiving with [`recv`], so both sides need
+/// [`HiConfig::typed_messages`].
+///
+/// [`Journal`]: struct.Journal.html
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+/// [`request_resend`]: fn.request_resend.html
+/// [`recv`]: #method.recv
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, request_resend, HiConfig, HiStream, Journal, JournaledSender};
+/// use std::{env, fs, thread};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let dir = env::temp_dir().join(format!("hi-tension-backfill-{}", std::process::id()));
+/// # let _ = fs::remove_dir_all(&dir);
+/// let config = HiConfig::new().typed_messages().user_headers();
+///
+/// let (client, server) = pipe();
+/// let server_config = config.clone();
+/// let receiver = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+///     let mut stream = HiStream::server(server, server_config)?;
+///     for _ in 0..3 {
+///         stream.read()?;
+///     }
+///     // Message 1 was lost on the way: ask for it again
+///     request_resend(&mut stream, 1)?;
+///     let data = stream.read()?;
+///     assert_eq!(stream.last_header(), Some(&b"1"[..]));
+///     stream.send_text("bye")?;
+///     Ok(data)
+/// });
+///
+/// let mut stream = HiStream::client(client, config)?;
+/// stream.set_journal(Journal::open(&dir)?);
+/// let mut sender = JournaledSender::new(stream);
+/// for i in 0..3 {
+///     assert_eq!(sender.send(&[i as f64; 4])?, i);
+/// }
+/// sender.recv()?;
+///
+/// assert_eq!(receiver.join().unwrap()?, [1.0; 4]);
+/// # fs::remove_dir_all(dir)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JournaledSender<S: Read + Write> {
+    stream: HiStream<S>,
+    sent: Vec<usize>,
+}
+
+impl<S: Read + Write> JournaledSender<S> {
+    /// Wrap `stream`, numbering its messages after the ones its journal
+    /// already holds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no journal is set on `stream` with
+    /// [`HiStream::set_journal`].
+    ///
+    /// [`HiStream::set_journal`]: struct.HiStream.html#method.set_journal
+    pub fn new(stream: HiStream<S>) -> Self {
+        let journal = stream
+            .journal()
+            .expect("a journaled sender needs a journal set on its stream");
+        let sent = journal
+            .entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.direction == Direction::Sent)
+            .map(|(i, _)| i)
+            .collect();
+        JournaledSender { stream, sent }
+    }
+
+    fn journal_len(&self) -> usize {
+        self.stream.journal().map_or(0, |journal| journal.len())
+    }
+
+    /// Send `data` as a *High Tension Message*, returning its sequence
+    /// number.
+    ///
+    /// This function is blocking, like [`HiStream::send`].
+    ///
+    /// [`HiStream::send`]: struct.HiStream.html#method.send
+    pub fn send(&mut self, data: &[f64]) -> Result<u64> {
+        let seq = self.sent.len() as u64;
+        self.stream.set_header(seq.to_string().as_bytes());
+        let len = self.journal_len();
+        self.stream.send(data)?;
+        if self.journal_len() > len {
+            self.sent.push(len);
+        }
+        Ok(seq)
+    }
+
+    /// Send the message `seq` again, read back from the journal, with the
+    /// same user header.
+    ///
+    /// This function is blocking. The message is not journaled again.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Invalid`] if no message `seq` was sent into the
+    /// journal.
+    ///
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    pub fn resend(&mut self, seq: u64) -> Result<()> {
+        let missing = || Error::Invalid(format!("no journaled message {}", seq));
+        let position = *self.sent.get(seq as usize).ok_or_else(missing)?;
+        let journal = self.stream.take_journal().ok_or_else(missing)?;
+        let result = journal.read(position).and_then(|data| {
+            let data = data.ok_or_else(missing)?;
+            self.stream.set_header(seq.to_string().as_bytes());
+            self.stream.send(&data)
+        });
+        self.stream.set_journal(journal);
+        result
+    }
+
+    /// Handle `text` if it is a `RESEND <seq>` request, sending the message
+    /// asked for, and return whether it was one.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`resend`].
+    ///
+    /// [`resend`]: #method.resend
+    pub fn handle(&mut self, text: &str) -> Result<bool> {
+        match text.strip_prefix(RESEND_PREFIX).map(str::parse) {
+            Some(Ok(seq)) => self.resend(seq).map(|()| true),
+            _ => Ok(false),
+        }
+    }
+
+    /// Get the next message from the peer which is not a `RESEND <seq>`
+    /// request, like [`HiStream::recv`], handling the requests received in
+    /// the meantime.
+    ///
+    /// This function is blocking.
+    ///
+    /// [`HiStream::recv`]: struct.HiStream.html#method.recv
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`resend`] when a request asks for an unknown message.
+    ///
+    /// [`resend`]: #method.resend
+    pub fn recv(&mut self) -> Result<Message> {
+        loop {
+            match self.stream.recv()? {
+                Message::Text(text) if self.handle(&text)? => {}
+                message => return Ok(message),
+            }
+        }
+    }
+
+    /// Number of messages sent into the journal, which is the sequence
+    /// number of the next one.
+    pub fn sent(&self) -> u64 {
+        self.sent.len() as u64
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &HiStream<S> {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut HiStream<S> {
+        &mut self.stream
+    }
+
+    /// Unwrap this `JournaledSender`, returning the underlying stream.
+    pub fn into_inner(self) -> HiStream<S> {
+        self.stream
+    }
+}
+
+/// Ask the [`JournaledSender`] at the other end of `stream` to send its
+/// message `seq` again.
+///
+/// This function is blocking, until the request is written. The message comes
+/// back as any other, tagged with `seq` as its user header with
+/// [`HiConfig::user_headers`].
+///
+/// [`JournaledSender`]: struct.JournaledSender.html
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+///
+/// # Panics
+///
+/// Panics like [`HiStream::send_text`].
+///
+/// [`HiStream::send_text`]: struct.HiStream.html#method.send_text
+pub fn request_resend<S: Read + Write>(stream: &mut HiStream<S>, seq: u64) -> Result<()> {
+    stream.send_text(&format!("{}{}", RESEND_PREFIX, seq))
+}
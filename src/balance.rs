@@ -0,0 +1,356 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::panic;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::{HiStream, Result};
+
+/// How a [`LoadBalancer`] picks the worker of each message.
+///
+/// [`LoadBalancer`]: struct.LoadBalancer.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Balance {
+    /// Each worker in turn, waiting for the next one to have room.
+    RoundRobin,
+    /// The worker with the fewest messages queued or being sent, so that
+    /// slow workers get less.
+    LeastInFlight,
+}
+
+/// A message waiting to be sent, with its sequence number.
+type Queued = (u64, Vec<f64>);
+
+struct Slot {
+    queue: VecDeque<Queued>,
+    sending: bool,
+    alive: bool,
+}
+
+impl Slot {
+    fn in_flight(&self) -> usize {
+        self.queue.len() + usize::from(self.sending)
+    }
+}
+
+struct State {
+    slots: Vec<Slot>,
+    closing: bool,
+    lost: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed.wait(state).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A sender spreading successive messages over a set of worker connections,
+/// for farms where any worker can process any message.
+///
+/// Each worker is served by a thread of its own, sending the messages queued
+/// for it as *High Tension Messages*. A message goes to the worker picked by
+/// the [`Balance`], as soon as that worker has fewer than
+/// [`max_in_flight`] messages queued or being sent: [`send`] blocks until
+/// then, so that a farm slower than its producer holds the producer back.
+///
+/// A worker whose connection fails is left out from then on, and the
+/// messages queued for it, the failed one included, are handed to the other
+/// workers. The failed message may then be processed twice, if only its
+/// acknowledgement was lost. Once every worker failed, sending fails with
+/// an IO error of kind [`NotConnected`].
+///
+/// Messages are numbered in the order they are sent, from 0. With
+/// [`HiConfig::user_headers`], each message carries its sequence number, in
+/// decimal, as its user header, for workers to tag their results with it:
+/// a [`ReorderPool`] then collects these results in order.
+///
+/// [`Balance`]: enum.Balance.html
+/// [`max_in_flight`]: #method.max_in_flight
+/// [`send`]: #method.send
+/// [`NotConnected`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotConnected
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+/// [`ReorderPool`]: struct.ReorderPool.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, Balance, HiConfig, HiStream, LoadBalancer};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut workers = Vec::new();
+/// let mut farm = Vec::new();
+/// for _ in 0..3 {
+///     let (client, server) = pipe();
+///     farm.push(thread::spawn(move || -> hi_tension::Result<usize> {
+///         let mut stream = HiStream::server(server, HiConfig::new())?;
+///         let mut processed = 0;
+///         while stream.read().is_ok() {
+///             processed += 1;
+///         }
+///         Ok(processed)
+///     }));
+///     workers.push(HiStream::client(client, HiConfig::new())?);
+/// }
+///
+/// let mut balancer = LoadBalancer::new(workers, Balance::RoundRobin);
+/// for frame in 0..30 {
+///     balancer.send(vec![f64::from(frame); 1024])?;
+/// }
+/// for stream in balancer.finish() {
+///     stream?.close()?;
+/// }
+///
+/// for worker in farm {
+///     assert_eq!(worker.join().unwrap()?, 10);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct LoadBalancer<S> {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<Result<HiStream<S>>>>,
+    balance: Balance,
+    max_in_flight: usize,
+    next: usize,
+    sequence: u64,
+}
+
+impl<S> LoadBalancer<S>
+where
+    S: Read + Write + Send + 'static,
+{
+    /// Spread messages over `workers`, picked according to `balance`, with
+    /// at most 2 messages in flight per worker.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is empty.
+    pub fn new(workers: Vec<HiStream<S>>, balance: Balance) -> Self {
+        assert!(!workers.is_empty(), "a load balancer needs workers");
+        let slots = workers
+            .iter()
+            .map(|_| Slot {
+                queue: VecDeque::new(),
+                sending: false,
+                alive: true,
+            })
+            .collect();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                slots,
+                closing: false,
+                lost: 0,
+            }),
+            changed: Condvar::new(),
+        });
+        let threads = workers
+            .into_iter()
+            .enumerate()
+            .map(|(i, stream)| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || serve_worker(&shared, i, stream))
+            })
+            .collect();
+        LoadBalancer {
+            shared,
+            threads,
+            balance,
+            max_in_flight: 2,
+            next: 0,
+            sequence: 0,
+        }
+    }
+
+    /// Let each worker have at most `max` messages queued or being sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        assert!(max > 0, "workers need room for at least one message");
+        self.max_in_flight = max;
+        self
+    }
+
+    /// Queue `data` for the next worker, returning the index of that worker
+    /// among the ones given to [`new`].
+    ///
+    /// This function is blocking, until that worker has room for it.
+    ///
+    /// [`new`]: #method.new
+    ///
+    /// # Errors
+    ///
+    /// Fails with an IO error of kind [`NotConnected`] if every worker
+    /// failed.
+    ///
+    /// [`NotConnected`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotConnected
+    pub fn send(&mut self, data: Vec<f64>) -> Result<usize> {
+        let mut state = self.shared.lock();
+        loop {
+            let alive = |i: &usize| state.slots[*i].alive;
+            let n = state.slots.len();
+            let pick = match self.balance {
+                Balance::RoundRobin => (0..n).map(|k| (self.next + k) % n).find(alive),
+                Balance::LeastInFlight => (0..n)
+                    .filter(alive)
+                    .min_by_key(|&i| state.slots[i].in_flight()),
+            };
+            let i = match pick {
+                Some(i) => i,
+                None => {
+                    let e = io::Error::new(io::ErrorKind::NotConnected, "every worker failed");
+                    return Err(e.into());
+                }
+            };
+            if state.slots[i].in_flight() < self.max_in_flight {
+                state.slots[i].queue.push_back((self.sequence, data));
+                self.sequence += 1;
+                self.next = i + 1;
+                self.shared.changed.notify_all();
+                return Ok(i);
+            }
+            state = self.shared.wait(state);
+        }
+    }
+
+    /// Number of messages sent so far, which is the sequence number of the
+    /// next one.
+    pub fn sent(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Number of messages queued or being sent for each worker.
+    pub fn in_flight(&self) -> Vec<usize> {
+        self.shared
+            .lock()
+            .slots
+            .iter()
+            .map(Slot::in_flight)
+            .collect()
+    }
+
+    /// Number of workers whose connection did not fail.
+    pub fn alive(&self) -> usize {
+        self.shared
+            .lock()
+            .slots
+            .iter()
+            .filter(|slot| slot.alive)
+            .count()
+    }
+
+    /// Number of messages dropped because every worker failed.
+    pub fn lost(&self) -> u64 {
+        self.shared.lock().lost
+    }
+
+    /// Wait for every message queued to be sent, and give back the stream of
+    /// each worker, in order, or the error which ended it.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of a worker thread, if any.
+    pub fn finish(mut self) -> Vec<Result<HiStream<S>>> {
+        self.close();
+        self.threads
+            .drain(..)
+            .map(|thread| thread.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    }
+}
+
+impl<S> LoadBalancer<S> {
+    fn close(&self) {
+        self.shared.lock().closing = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl<S> Drop for LoadBalancer<S> {
+    fn drop(&mut self) {
+        // The threads carry on until their queue is drained
+        self.close();
+    }
+}
+
+impl<S> fmt::Debug for LoadBalancer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.shared.lock();
+        let in_flight: Vec<_> = state.slots.iter().map(Slot::in_flight).collect();
+        f.debug_struct("LoadBalancer")
+            .field("balance", &self.balance)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("in_flight", &in_flight)
+            .field("lost", &state.lost)
+            .finish()
+    }
+}
+
+/// Send the messages queued for the worker `i` over `stream`, until the
+/// balancer is closed and the queue drained, or the connection fails.
+fn serve_worker<S: Read + Write>(
+    shared: &Shared,
+    i: usize,
+    mut stream: HiStream<S>,
+) -> Result<HiStream<S>> {
+    let headers = stream.config().user_headers;
+    loop {
+        let (sequence, data) = {
+            let mut state = shared.lock();
+            loop {
+                if let Some(queued) = state.slots[i].queue.pop_front() {
+                    state.slots[i].sending = true;
+                    break queued;
+                }
+                if state.closing {
+                    return Ok(stream);
+                }
+                state = shared.wait(state);
+            }
+        };
+        if headers {
+            stream.set_header(sequence.to_string().as_bytes());
+        }
+        let result = stream.send(&data);
+
+        let mut state = shared.lock();
+        state.slots[i].sending = false;
+        if let Err(e) = result {
+            state.slots[i].alive = false;
+            let mut orphans = std::mem::take(&mut state.slots[i].queue);
+            orphans.push_front((sequence, data));
+            redistribute(&mut state, orphans);
+            shared.changed.notify_all();
+            return Err(e);
+        }
+        shared.changed.notify_all();
+    }
+}
+
+/// Hand the messages of a failed worker to the least loaded workers left.
+fn redistribute(state: &mut State, orphans: VecDeque<Queued>) {
+    for queued in orphans {
+        let pick = (0..state.slots.len())
+            .filter(|&i| state.slots[i].alive)
+            .min_by_key(|&i| state.slots[i].in_flight());
+        match pick {
+            Some(i) => state.slots[i].queue.push_back(queued),
+            None => state.lost += 1,
+        }
+    }
+}
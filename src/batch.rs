@@ -0,0 +1,191 @@
+use std::io::{Read, Write};
+
+use crate::pod::bytes_of;
+use crate::{hidelimiter, hiread, hiwrite, pack_bytes, Error, HiStream, Result};
+
+/// Several named columns of the same length, received as one *High Tension
+/// Message*.
+///
+/// Typical tabular sensor data (time, x, y, z, intensity...) travels this way
+/// in a single framed unit. Batches are sent with [`hiwrite_batch`], and
+/// received with [`hiread_batch`].
+///
+/// The columns are stored one after the other in the received buffer, so no
+/// copy happens after reception.
+///
+/// [`hiwrite_batch`]: fn.hiwrite_batch.html
+/// [`hiread_batch`]: fn.hiread_batch.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordBatch {
+    names: Vec<String>,
+    rows: usize,
+    data: Vec<f64>,
+    start: usize,
+}
+
+impl RecordBatch {
+    /// Number of rows, which is the length of every column.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Names of the columns, in order.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Get the column named `name`.
+    pub fn column(&self, name: &str) -> Option<&[f64]> {
+        let i = self.names.iter().position(|n| n == name)?;
+        self.column_at(i)
+    }
+
+    /// Get the `i`-th column.
+    pub fn column_at(&self, i: usize) -> Option<&[f64]> {
+        if i >= self.names.len() {
+            return None;
+        }
+        let start = self.start + i * self.rows;
+        Some(&self.data[start..start + self.rows])
+    }
+
+    /// Iterate over the columns and their names.
+    pub fn columns(&self) -> impl Iterator<Item = (&str, &[f64])> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(move |(i, name)| (name.as_str(), self.column_at(i).unwrap()))
+    }
+
+    fn decode(data: Vec<f64>) -> Result<Self> {
+        let malformed = || Error::Framing("malformed record batch".into());
+        let word = |i: usize| {
+            data.get(i)
+                .map(|x| x.to_bits() as usize)
+                .ok_or_else(malformed)
+        };
+
+        let columns = word(0)?;
+        let rows = word(1)?;
+        let mut names = Vec::new();
+        let mut pos = 2;
+        for _ in 0..columns {
+            let len = word(pos)?;
+            let words = len.div_ceil(8);
+            let bytes = data
+                .get(pos + 1..pos + 1 + words)
+                .map(|w| &bytes_of(w)[..len])
+                .ok_or_else(malformed)?;
+            names.push(String::from_utf8(bytes.to_vec()).map_err(|_| malformed())?);
+            pos += 1 + words;
+        }
+
+        if columns.checked_mul(rows) != Some(data.len() - pos) {
+            return Err(malformed());
+        }
+        Ok(RecordBatch {
+            names,
+            rows,
+            data,
+            start: pos,
+        })
+    }
+}
+
+/// Encode the header of a batch: the number of columns and rows, then every
+/// name as its length in bytes followed by its bytes padded to whole words.
+fn header(columns: &[(&str, &[f64])]) -> Vec<f64> {
+    let rows = columns.first().map_or(0, |(_, c)| c.len());
+    assert!(
+        columns.iter().all(|(_, c)| c.len() == rows),
+        "columns of a record batch must have the same length"
+    );
+
+    let mut header = vec![
+        f64::from_bits(columns.len() as u64),
+        f64::from_bits(rows as u64),
+    ];
+    for (name, _) in columns {
+        header.push(f64::from_bits(name.len() as u64));
+        header.extend(pack_bytes(name.as_bytes()));
+    }
+    header
+}
+
+/// Send named `columns` as a single *High Tension Message*.
+///
+/// This function is blocking. Unlike [`hiwrite`], the message is complete: no
+/// call to [`hidelimiter`] is needed.
+///
+/// The columns are written one after the other, straight from their slices.
+/// The message must be received with [`hiread_batch`].
+///
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+/// [`hiread_batch`]: fn.hiread_batch.html
+///
+/// # Panics
+///
+/// Panics if the columns do not all have the same length.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiread_batch, hiwrite_batch};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let time = vec![0.0, 0.1, 0.2];
+/// let intensity = vec![3.0, 2.5, 2.7];
+///
+/// hiwrite_batch(&mut stream, &[("time", &time), ("intensity", &intensity)])?;
+///
+/// let batch = hiread_batch(&mut stream)?;
+/// let intensity = batch.column("intensity").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_batch<S: Read + Write>(stream: &mut S, columns: &[(&str, &[f64])]) -> Result<()> {
+    hiwrite(stream, &header(columns))?;
+    for (_, column) in columns {
+        hiwrite(stream, column)?;
+    }
+    hidelimiter(stream)
+}
+
+/// Read a record batch sent by [`hiwrite_batch`].
+///
+/// This function is blocking.
+///
+/// [`hiwrite_batch`]: fn.hiwrite_batch.html
+pub fn hiread_batch<S: Read + Write>(stream: &mut S) -> Result<RecordBatch> {
+    RecordBatch::decode(hiread(stream)?)
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Send named `columns` as a single *High Tension Message*, like
+    /// [`hiwrite_batch`].
+    ///
+    /// [`hiwrite_batch`]: fn.hiwrite_batch.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the columns do not all have the same length.
+    pub fn send_batch(&mut self, columns: &[(&str, &[f64])]) -> Result<()> {
+        self.write(&header(columns))?;
+        for (_, column) in columns {
+            self.write(column)?;
+        }
+        self.finish()
+    }
+
+    /// Read a record batch sent by [`send_batch`].
+    ///
+    /// [`send_batch`]: #method.send_batch
+    pub fn read_batch(&mut self) -> Result<RecordBatch> {
+        RecordBatch::decode(self.read()?)
+    }
+}
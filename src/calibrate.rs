@@ -0,0 +1,84 @@
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::{hidelimiter, hiwrite_chunked, Result};
+
+/// The last word of a probe message: a NaN spelling `probe`, like the
+/// delimiter is one. `HiStream` receivers discard such messages.
+pub(crate) const PROBE_NAN: [u8; 8] = *b"probe\x00\xf8\x7f";
+
+/// Chunk sizes tried by the calibration, in bytes. The last one writes whole
+/// probes at once, like `hiwrite`.
+const CANDIDATES: [usize; 6] = [64 << 10, 256 << 10, 1 << 20, 4 << 20, 16 << 20, 64 << 20];
+
+/// Size of the probe messages, in floats: 32 MB.
+const PROBE_LEN: usize = 4 << 20;
+
+/// Find the fastest of the `CANDIDATES` chunk sizes, timing every probe sent
+/// with `send`. A first probe warms the connection up, and is not timed.
+pub(crate) fn calibrate_with<F>(mut send: F) -> Result<usize>
+where
+    F: FnMut(&[f64], usize) -> Result<()>,
+{
+    let probe = probe_message();
+    send(&probe, usize::MAX)?;
+
+    let mut best = (Duration::MAX, 0);
+    for &chunk_size in &CANDIDATES {
+        let start = Instant::now();
+        send(&probe, chunk_size)?;
+        best = best.min((start.elapsed(), chunk_size));
+    }
+    Ok(best.1)
+}
+
+fn probe_message() -> Vec<f64> {
+    let mut probe = vec![0.0; PROBE_LEN];
+    probe[PROBE_LEN - 1] = f64::from_le_bytes(PROBE_NAN);
+    probe
+}
+
+/// Whether a message received is a calibration probe.
+pub(crate) fn is_probe(data: &[f64]) -> bool {
+    data.len() == PROBE_LEN && data[PROBE_LEN - 1].to_le_bytes() == PROBE_NAN
+}
+
+/// Measure which chunk size makes [`hiwrite_chunked`] the fastest on the
+/// `stream`, and return it in bytes.
+///
+/// This function is blocking, and sends 7 probe messages of 32 MB to the
+/// other end of the `stream`. A [`HiStream`] peer discards them, while a peer
+/// using [`hiread`] receives them as ordinary messages and has to discard them
+/// itself.
+///
+/// The best chunk size varies widely between loopback, LAN and WAN links, so
+/// this is best done on the live connection, right after it is opened.
+///
+/// [`hiwrite_chunked`]: fn.hiwrite_chunked.html
+/// [`HiStream`]: struct.HiStream.html
+/// [`hiread`]: fn.hiread.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hicalibrate, hidelimiter, hiwrite_chunked};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let chunk_size = hicalibrate(&mut stream)?;
+/// loop {
+///     hiwrite_chunked(&mut stream, &vec![0.0; 1_000_000], chunk_size)?;
+///     hidelimiter(&mut stream)?;
+/// }
+/// # }
+/// ```
+pub fn hicalibrate<S: Read + Write>(stream: &mut S) -> Result<usize> {
+    calibrate_with(|probe, chunk_size| {
+        hiwrite_chunked(stream, probe, chunk_size)?;
+        hidelimiter(stream)
+    })
+}
@@ -0,0 +1,101 @@
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::{Error, HiStream, Result};
+
+/// Receive the messages of `stream` on a dedicated thread, and hand them out
+/// through a channel.
+///
+/// At most `bound` received messages wait in the channel: past that, the
+/// thread stops reading until the application catches up.
+///
+/// The thread ends when the peer closes the connection with
+/// [`HiStream::close`], when the channel is dropped, or on the first error.
+/// Joining it gives the stream back, or the error.
+///
+/// [`HiStream::close`]: struct.HiStream.html#method.close
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{spawn_receiver, HiConfig, HiStream};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+/// let (messages, thread) = spawn_receiver(HiStream::client(tcp, HiConfig::new())?, 4);
+///
+/// for data in messages {
+///     println!("received {} floats", data.len());
+/// }
+/// thread.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_receiver<S>(
+    mut stream: HiStream<S>,
+    bound: usize,
+) -> (Receiver<Vec<f64>>, JoinHandle<Result<HiStream<S>>>)
+where
+    S: Read + Write + Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel(bound);
+    let thread = thread::spawn(move || loop {
+        match stream.read() {
+            Ok(data) => {
+                if sender.send(data).is_err() {
+                    return Ok(stream);
+                }
+            }
+            Err(Error::Closed) => return Ok(stream),
+            Err(e) => return Err(e),
+        }
+    });
+    (receiver, thread)
+}
+
+/// Send the messages given to a channel on a dedicated thread, each as a
+/// *High Tension Message* over `stream`.
+///
+/// At most `bound` messages wait in the channel: past that, sending to the
+/// channel blocks until the thread catches up.
+///
+/// The thread ends once every sender is dropped and the channel is drained,
+/// or on the first error, after which sending to the channel fails. Joining
+/// it gives the stream back, or the error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{spawn_sender, HiConfig, HiStream};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+/// let (messages, thread) = spawn_sender(HiStream::client(tcp, HiConfig::new())?, 4);
+///
+/// for i in 0..100 {
+///     messages.send(vec![i as f64; 1_000_000]).unwrap();
+/// }
+/// drop(messages);
+/// thread.join().unwrap()?.close()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_sender<S>(
+    mut stream: HiStream<S>,
+    bound: usize,
+) -> (SyncSender<Vec<f64>>, JoinHandle<Result<HiStream<S>>>)
+where
+    S: Read + Write + Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel::<Vec<f64>>(bound);
+    let thread = thread::spawn(move || {
+        for data in receiver {
+            stream.send(&data)?;
+        }
+        Ok(stream)
+    });
+    (sender, thread)
+}
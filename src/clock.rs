@@ -0,0 +1,174 @@
+use std::io::{Read, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{Error, HiStream, Result};
+
+/// When a *High Tension Message* was produced, according to its sender.
+///
+/// Timestamps are attached to messages when [`HiConfig::timestamps`] is set,
+/// and read back with [`HiStream::last_timestamp`]. They are taken when the
+/// sender starts writing the message.
+///
+/// [`HiConfig::timestamps`]: struct.HiConfig.html#method.timestamps
+/// [`HiStream::last_timestamp`]: struct.HiStream.html#method.last_timestamp
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Time elapsed on the monotonic clock of the sender, since an arbitrary
+    /// origin fixed for the lifetime of its process.
+    ///
+    /// Only differences between timestamps of the same sender are meaningful,
+    /// but they are immune to wall clock adjustments.
+    pub monotonic: Duration,
+    /// Wall clock time of the sender.
+    pub wall: SystemTime,
+}
+
+impl Timestamp {
+    /// Take a timestamp now.
+    pub fn now() -> Self {
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        Timestamp {
+            monotonic: ORIGIN.get_or_init(Instant::now).elapsed(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    pub(crate) fn to_words(self) -> [f64; 2] {
+        [
+            f64::from_bits(self.monotonic.as_nanos() as u64),
+            f64::from_bits(wall_nanos(self.wall) as u64),
+        ]
+    }
+
+    pub(crate) fn from_words(words: &[f64]) -> Self {
+        Timestamp {
+            monotonic: Duration::from_nanos(words[0].to_bits()),
+            wall: UNIX_EPOCH + Duration::from_nanos(words[1].to_bits()),
+        }
+    }
+}
+
+/// The estimated offset between the wall clocks of two peers.
+///
+/// This is the result of [`HiStream::sync_clock`].
+///
+/// [`HiStream::sync_clock`]: struct.HiStream.html#method.sync_clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockOffset {
+    /// Nanoseconds to add to a local wall clock time to get the corresponding
+    /// wall clock time of the peer.
+    pub offset_nanos: i64,
+    /// The round trip time of the exchange the offset was estimated from. The
+    /// error on the offset is at most half of it.
+    pub round_trip: Duration,
+}
+
+impl ClockOffset {
+    /// Convert the wall clock time `time` of the peer to local time.
+    pub fn to_local(&self, time: SystemTime) -> SystemTime {
+        if self.offset_nanos >= 0 {
+            time - Duration::from_nanos(self.offset_nanos as u64)
+        } else {
+            time + Duration::from_nanos(self.offset_nanos.unsigned_abs())
+        }
+    }
+}
+
+fn wall_nanos(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i64,
+        Err(e) => -(e.duration().as_nanos() as i64),
+    }
+}
+
+fn nanos_word(time: SystemTime) -> f64 {
+    f64::from_bits(wall_nanos(time) as u64)
+}
+
+fn word_nanos(word: f64) -> i64 {
+    word.to_bits() as i64
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Estimate the offset between the local wall clock and the one of the
+    /// peer, which must be calling [`answer_clock`] with the same number of
+    /// `rounds`.
+    ///
+    /// This function is blocking.
+    ///
+    /// Each round is an NTP-like exchange of two *High Tension Messages*. The
+    /// estimate of the round with the shortest round trip is returned, since it
+    /// is the least affected by network jitter.
+    ///
+    /// [`answer_clock`]: #method.answer_clock
+    ///
+    /// # Examples
+    ///
+    /// Aligning the timestamps of a remote source with local ones:
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, HiConfig::new().timestamps())?;
+    /// let offset = stream.sync_clock(8)?;
+    ///
+    /// let data = stream.read()?;
+    /// let produced = offset.to_local(stream.last_timestamp().unwrap().wall);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sync_clock(&mut self, rounds: usize) -> Result<ClockOffset> {
+        let mut best: Option<ClockOffset> = None;
+        for _ in 0..rounds.max(1) {
+            let t0 = SystemTime::now();
+            self.send(&[nanos_word(t0)])?;
+            let reply = self.read()?;
+            let t3 = SystemTime::now();
+
+            let invalid = || Error::Framing("invalid clock synchronization reply".into());
+            if reply.len() != 2 {
+                return Err(invalid());
+            }
+            let (t0, t3) = (wall_nanos(t0), wall_nanos(t3));
+            let (t1, t2) = (word_nanos(reply[0]), word_nanos(reply[1]));
+
+            // The times of the peer are whatever it sent
+            let offset_nanos = (t1.checked_sub(t0))
+                .zip(t2.checked_sub(t3))
+                .and_then(|(a, b)| a.checked_add(b))
+                .ok_or_else(invalid)?
+                / 2;
+            let round_trip = (t3.checked_sub(t0))
+                .zip(t2.checked_sub(t1))
+                .and_then(|(a, b)| a.checked_sub(b))
+                .ok_or_else(invalid)?;
+            let offset = ClockOffset {
+                offset_nanos,
+                round_trip: Duration::from_nanos(round_trip.max(0) as u64),
+            };
+            if best.is_none_or(|best| offset.round_trip < best.round_trip) {
+                best = Some(offset);
+            }
+        }
+        Ok(best.expect("at least one round"))
+    }
+
+    /// Answer the [`sync_clock`] call of the peer, for the same number of
+    /// `rounds`.
+    ///
+    /// This function is blocking.
+    ///
+    /// [`sync_clock`]: #method.sync_clock
+    pub fn answer_clock(&mut self, rounds: usize) -> Result<()> {
+        for _ in 0..rounds.max(1) {
+            self.read()?;
+            let t1 = SystemTime::now();
+            self.send(&[nanos_word(t1), nanos_word(SystemTime::now())])?;
+        }
+        Ok(())
+    }
+}
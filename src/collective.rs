@@ -0,0 +1,203 @@
+use std::io::{Read, Write};
+
+use crate::{hiempty, hiread_exact, read_chunks, Error, Result};
+
+/// An elementwise operation combining the arrays of several peers, for
+/// [`hireduce`].
+///
+/// [`hireduce`]: fn.hireduce.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReduceOp {
+    /// Sum of the values.
+    Sum,
+    /// Smallest value, ignoring NaN values.
+    Min,
+    /// Largest value, ignoring NaN values.
+    Max,
+}
+
+impl ReduceOp {
+    fn apply(self, acc: f64, x: f64) -> f64 {
+        match self {
+            ReduceOp::Sum => acc + x,
+            ReduceOp::Min => acc.min(x),
+            ReduceOp::Max => acc.max(x),
+        }
+    }
+}
+
+/// Read a *High Tension Message* from every one of the `streams`, and combine
+/// them elementwise with `op`.
+///
+/// This function is blocking. The messages are received in turn, each one
+/// chunk at a time, and folded into the first one: besides the result, only a
+/// single chunk is ever held in memory. Peers sending later simply wait for
+/// their turn.
+///
+/// This makes a cheap all-reduce at a master node, when followed by sending
+/// the result back to every peer.
+///
+/// # Errors
+///
+/// Fails with [`Error::Invalid`] if the messages are not all of the same
+/// length. Every message is still received, so that the streams stay usable
+/// for the next messages.
+///
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hireduce, ReduceOp};
+/// use std::net::TcpListener;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let listener = TcpListener::bind("0.0.0.0:34567")?;
+/// let mut workers = (0..4)
+///     .map(|_| Ok(listener.accept()?.0))
+///     .collect::<std::io::Result<Vec<_>>>()?;
+///
+/// let total = hireduce(&mut workers, ReduceOp::Sum)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hireduce<S: Read + Write>(streams: &mut [S], op: ReduceOp) -> Result<Vec<f64>> {
+    let (first, rest) = match streams.split_first_mut() {
+        Some(split) => split,
+        None => return Ok(Vec::new()),
+    };
+    let mut acc = Vec::new();
+    read_chunks(first, |values| {
+        acc.extend_from_slice(values);
+        Ok(())
+    })?;
+
+    let mut mismatch = None;
+    for (i, stream) in rest.iter_mut().enumerate() {
+        let mut index = 0;
+        let len = read_chunks(stream, |values| {
+            if let Some(acc) = acc.get_mut(index..index + values.len()) {
+                for (acc, &x) in acc.iter_mut().zip(values) {
+                    *acc = op.apply(*acc, x);
+                }
+            }
+            index += values.len();
+            Ok(())
+        })?;
+        if len != acc.len() && mismatch.is_none() {
+            mismatch = Some(format!(
+                "{} floats from stream {} instead of {}",
+                len,
+                i + 1,
+                acc.len()
+            ));
+        }
+    }
+
+    match mismatch {
+        Some(reason) => Err(Error::Invalid(reason)),
+        None => Ok(acc),
+    }
+}
+
+/// Synchronize the peers at the other end of the `streams`, from a
+/// coordinator.
+///
+/// This function is blocking. It waits for an empty message from every peer,
+/// as sent by [`hibarrier_wait`], then answers each with an empty message of
+/// its own. Once it returns, every peer reached the barrier, and every peer is
+/// released from it.
+///
+/// This is enough to align the steps of loosely coupled simulations, without
+/// pulling in MPI. On TCP, disable Nagle's algorithm with `set_nodelay` on
+/// both sides, or every barrier waits for delayed acknowledgements, tens of
+/// milliseconds long.
+///
+/// [`hibarrier_wait`]: fn.hibarrier_wait.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if a peer sends a message which is not empty,
+/// because it is not at the barrier. The peers already released stay so.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hibarrier;
+/// use std::net::TcpListener;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let listener = TcpListener::bind("0.0.0.0:34567")?;
+/// let mut workers = (0..4)
+///     .map(|_| {
+///         let (tcp, _) = listener.accept()?;
+///         tcp.set_nodelay(true)?;
+///         Ok(tcp)
+///     })
+///     .collect::<std::io::Result<Vec<_>>>()?;
+///
+/// loop {
+///     // Every worker completed its step
+///     hibarrier(&mut workers)?;
+/// }
+/// # }
+/// ```
+pub fn hibarrier<S: Read + Write>(streams: &mut [S]) -> Result<()> {
+    for stream in streams.iter_mut() {
+        expect_empty(stream)?;
+    }
+    for stream in streams.iter_mut() {
+        hiempty(stream)?;
+    }
+    Ok(())
+}
+
+/// Reach the barrier of the coordinator at the other end of the `stream`, and
+/// wait for every other peer to reach it too.
+///
+/// This function is blocking, until the coordinator returns from
+/// [`hibarrier`].
+///
+/// [`hibarrier`]: fn.hibarrier.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the coordinator answers with a message
+/// which is not empty.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hibarrier_wait;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// stream.set_nodelay(true)?;
+///
+/// for step in 0.. {
+///     // Compute the step, then wait for the other workers
+///     hibarrier_wait(&mut stream)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn hibarrier_wait<S: Read + Write>(stream: &mut S) -> Result<()> {
+    hiempty(stream)?;
+    expect_empty(stream)
+}
+
+/// Receive a message, which must be empty.
+fn expect_empty<S: Read + Write>(stream: &mut S) -> Result<()> {
+    hiread_exact::<_, 0>(stream).map(|[]| ())
+}
@@ -0,0 +1,595 @@
+use std::fmt;
+
+#[cfg(feature = "proxy")]
+use crate::proxy::Proxy;
+use crate::{Identity, Quota, RetryPolicy, Schema, SessionStore, DELIMITER_NAN};
+
+/// Configuration of a [`HiStream`].
+///
+/// Both ends of a connection must use compatible configurations: options that
+/// change the framing of *High Tension Messages* (such as [`hmac_key`]) have
+/// to be set on both sides.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`hmac_key`]: #method.hmac_key
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::HiConfig;
+///
+/// let config = HiConfig::new().hmac_key("shared secret");
+/// ```
+#[derive(Clone)]
+pub struct HiConfig {
+    pub(crate) hmac_key: Option<Vec<u8>>,
+    pub(crate) token: Option<String>,
+    pub(crate) identity: Option<Identity>,
+    pub(crate) sessions: Option<SessionStore>,
+    pub(crate) timestamps: bool,
+    pub(crate) user_headers: bool,
+    pub(crate) batching: Option<usize>,
+    pub(crate) column_major: bool,
+    pub(crate) delimiter: u64,
+    pub(crate) schemas: Vec<Schema>,
+    pub(crate) typed_messages: bool,
+    pub(crate) chunk_size: Option<usize>,
+    pub(crate) adaptive_chunks: bool,
+    pub(crate) small_messages: Option<usize>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) prefault: bool,
+    pub(crate) max_message: Option<usize>,
+    pub(crate) float_report: bool,
+    pub(crate) send_quota: Option<Quota>,
+    pub(crate) receive_quota: Option<Quota>,
+    pub(crate) ack_status: bool,
+    pub(crate) strict_framing: bool,
+    pub(crate) auto_resync: bool,
+    #[cfg(feature = "proxy")]
+    pub(crate) proxy: Option<Proxy>,
+}
+
+impl Default for HiConfig {
+    fn default() -> Self {
+        HiConfig {
+            hmac_key: None,
+            token: None,
+            identity: None,
+            sessions: None,
+            timestamps: false,
+            user_headers: false,
+            batching: None,
+            column_major: false,
+            delimiter: u64::from_le_bytes(DELIMITER_NAN),
+            schemas: Vec::new(),
+            typed_messages: false,
+            chunk_size: None,
+            adaptive_chunks: false,
+            small_messages: None,
+            prefault: false,
+            max_message: None,
+            float_report: false,
+            send_quota: None,
+            receive_quota: None,
+            ack_status: false,
+            strict_framing: false,
+            auto_resync: false,
+            retry: None,
+            #[cfg(feature = "proxy")]
+            proxy: None,
+        }
+    }
+}
+
+impl HiConfig {
+    /// Create a configuration with every option disabled, which speaks the
+    /// plain `hi-tension` protocol.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sign every *High Tension Message* with an HMAC-SHA256 computed from
+    /// `key`.
+    ///
+    /// The 32 bytes tag is appended to the message, right before the
+    /// delimiter. On receipt, messages whose tag does not match are rejected
+    /// with [`Error::AuthFailed`].
+    ///
+    /// This protects against tampering, not against eavesdropping: the data
+    /// itself still travels in clear.
+    ///
+    /// [`Error::AuthFailed`]: enum.Error.html#variant.AuthFailed
+    pub fn hmac_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.hmac_key = Some(key.into());
+        self
+    }
+
+    /// Authenticate connections with an access `token` during the handshake.
+    ///
+    /// A client sends its token to the server in [`HiStream::client`]. A server
+    /// with a token set refuses, in [`HiStream::server`], every client which
+    /// does not present the same one; both sides then fail with
+    /// [`Error::AuthFailed`]. Tokens are compared in constant time.
+    ///
+    /// The token travels in clear, so this only keeps away peers which can
+    /// reach the port but cannot eavesdrop on the network.
+    ///
+    /// [`HiStream::client`]: struct.HiStream.html#method.client
+    /// [`HiStream::server`]: struct.HiStream.html#method.server
+    /// [`Error::AuthFailed`]: enum.Error.html#variant.AuthFailed
+    ///
+    /// # Panics
+    ///
+    /// Panics if `token` contains a newline.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        let token = token.into();
+        assert!(
+            !token.contains('\n'),
+            "access tokens cannot contain newlines"
+        );
+        self.token = Some(token);
+        self
+    }
+
+    /// Announce `identity` to the peer during the handshake, which gets it
+    /// back with [`HiStream::peer_identity`].
+    ///
+    /// Servers use the identity of their clients to pick how to serve them,
+    /// with [`HiServer::serve_routed`].
+    ///
+    /// [`HiStream::peer_identity`]: struct.HiStream.html#method.peer_identity
+    /// [`HiServer::serve_routed`]: struct.HiServer.html#method.serve_routed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{HiConfig, Identity};
+    ///
+    /// let config = HiConfig::new().identity(Identity::new("archiver-2", "archiver"));
+    /// ```
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Assign a [`Session`] to every client accepted by [`HiStream::server`],
+    /// keeping it in `store` so that the client can reattach to it later.
+    ///
+    /// This option only matters on the server side. Clients get their session
+    /// from the handshake, and reattach with [`HiStream::resume`].
+    ///
+    /// [`Session`]: struct.Session.html
+    /// [`HiStream::server`]: struct.HiStream.html#method.server
+    /// [`HiStream::resume`]: struct.HiStream.html#method.resume
+    pub fn sessions(mut self, store: SessionStore) -> Self {
+        self.sessions = Some(store);
+        self
+    }
+
+    /// Attach a [`Timestamp`] to every *High Tension Message*, taken by the
+    /// sender when it starts writing the message.
+    ///
+    /// The timestamp is sent as a trailer of two words, right before the
+    /// delimiter (and the HMAC, if any). Receivers get it back with
+    /// [`HiStream::last_timestamp`].
+    ///
+    /// [`Timestamp`]: struct.Timestamp.html
+    /// [`HiStream::last_timestamp`]: struct.HiStream.html#method.last_timestamp
+    pub fn timestamps(mut self) -> Self {
+        self.timestamps = true;
+        self
+    }
+
+    /// Attach a user header, made of opaque bytes, to every *High Tension
+    /// Message*.
+    ///
+    /// Applications can tag their messages (frame number, detector ID...)
+    /// without a separate round trip. Senders set the header of the next
+    /// message with [`HiStream::set_header`], and receivers get it back with
+    /// [`HiStream::last_header`]. A message sent without header carries an
+    /// empty one.
+    ///
+    /// The header is sent in the trailer of the message, padded to whole words
+    /// and followed by its length in bytes.
+    ///
+    /// [`HiStream::set_header`]: struct.HiStream.html#method.set_header
+    /// [`HiStream::last_header`]: struct.HiStream.html#method.last_header
+    pub fn user_headers(mut self) -> Self {
+        self.user_headers = true;
+        self
+    }
+
+    /// Coalesce up to `messages` *High Tension Messages* into each wire frame.
+    ///
+    /// For workloads made of many tiny arrays, this amortizes the delimiter
+    /// and acknowledge round trip over a whole batch. Receivers still get the
+    /// messages one by one, with their boundaries preserved.
+    ///
+    /// A batch is sent once full, when [`HiStream::flush`] is called, or
+    /// before reading, so that requests waiting for an answer are not held
+    /// back. Since a wire frame is then signed and acknowledged as a whole,
+    /// the other options (HMAC, timestamps, user headers) apply per frame
+    /// rather than per message.
+    ///
+    /// The frame holds the messages, followed by their lengths and their
+    /// count.
+    ///
+    /// [`HiStream::flush`]: struct.HiStream.html#method.flush
+    ///
+    /// # Panics
+    ///
+    /// Panics if `messages` is zero.
+    pub fn batching(mut self, messages: usize) -> Self {
+        assert!(messages > 0, "a batch holds at least one message");
+        self.batching = Some(messages);
+        self
+    }
+
+    /// Ask the peer to send matrices in column-major order.
+    ///
+    /// The request is made during the handshake. Matrices sent afterwards by
+    /// the peer with [`HiStream::send_matrix`] are then transposed on the fly,
+    /// so consumers needing that layout avoid a full pass over every matrix
+    /// after receipt.
+    ///
+    /// [`HiStream::send_matrix`]: struct.HiStream.html#method.send_matrix
+    pub fn column_major(mut self) -> Self {
+        self.column_major = true;
+        self
+    }
+
+    /// End the *High Tension Messages* sent to this side with the 64 bits
+    /// pattern `delimiter`, instead of the magic NaN value
+    /// `0x7ff800100400a05b`.
+    ///
+    /// This is meant for datasets which legitimately contain the magic NaN
+    /// value. The delimiter is announced during the handshake, and the peer
+    /// uses it for the messages it sends: each side may pick its own. Another
+    /// NaN payload unlikely to appear in the data is a good choice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::HiConfig;
+    ///
+    /// let config = HiConfig::new().delimiter(0x7ff8_dead_beef_0001);
+    /// ```
+    pub fn delimiter(mut self, delimiter: u64) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Register `schema`, a named type of the *High Tension Messages* sent or
+    /// received on this side.
+    ///
+    /// Schemas are announced during the handshake, which fails with
+    /// [`Error::Handshake`] if both sides know a schema of the same name
+    /// defined differently. Senders then tag messages with
+    /// [`HiStream::set_schema`], and receivers check them with
+    /// [`HiStream::last_schema`].
+    ///
+    /// When schemas are registered, every message carries the ID of its
+    /// schema as one more word of trailer, right before the user header (and
+    /// the timestamp, and the HMAC, if any).
+    ///
+    /// [`Error::Handshake`]: enum.Error.html#variant.Handshake
+    /// [`HiStream::set_schema`]: struct.HiStream.html#method.set_schema
+    /// [`HiStream::last_schema`]: struct.HiStream.html#method.last_schema
+    ///
+    /// # Panics
+    ///
+    /// Panics if a schema with the same name is already registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{HiConfig, Schema};
+    ///
+    /// let config = HiConfig::new()
+    ///     .schema(Schema::new("spectrum", "f64").shape(&[Some(4096)]).units("dBm"))
+    ///     .schema(Schema::new("waveform", "f64").units("V"));
+    /// ```
+    pub fn schema(mut self, schema: Schema) -> Self {
+        assert!(
+            self.schemas.iter().all(|s| s.name() != schema.name()),
+            "schema {} registered twice",
+            schema.name()
+        );
+        self.schemas.push(schema);
+        self
+    }
+
+    /// Prefix every message with a byte telling its kind, so that *Simple
+    /// Text Messages* and *High Tension Messages* can be mixed on the same
+    /// connection.
+    ///
+    /// Text messages are sent with [`HiStream::send_text`], and messages of
+    /// either kind are received with [`HiStream::recv`]. The prefix is `T` for
+    /// text messages and `A` for arrays.
+    ///
+    /// [`HiStream::send_text`]: struct.HiStream.html#method.send_text
+    /// [`HiStream::recv`]: struct.HiStream.html#method.recv
+    pub fn typed_messages(mut self) -> Self {
+        self.typed_messages = true;
+        self
+    }
+
+    /// Write *High Tension Messages* at most `bytes` at a time, like
+    /// [`hiwrite_chunked`], instead of whole.
+    ///
+    /// [`HiStream::calibrate`] measures the best size on a live connection.
+    ///
+    /// [`hiwrite_chunked`]: fn.hiwrite_chunked.html
+    /// [`HiStream::calibrate`]: struct.HiStream.html#method.calibrate
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is zero.
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        assert!(bytes > 0, "chunks hold at least one byte");
+        self.chunk_size = Some(bytes);
+        self
+    }
+
+    /// Tune the size of the writes of *High Tension Messages* while sending,
+    /// so that the same program performs well on loopback, on a LAN and on a
+    /// high-latency WAN.
+    ///
+    /// Every write is timed: the size grows a little after every write which
+    /// went at the usual throughput, and is halved after every write which
+    /// stalled, like TCP tunes its window. The size set with [`chunk_size`],
+    /// or found by [`HiStream::calibrate`], is where the tuning starts from.
+    ///
+    /// [`chunk_size`]: #method.chunk_size
+    /// [`HiStream::calibrate`]: struct.HiStream.html#method.calibrate
+    pub fn adaptive_chunks(mut self) -> Self {
+        self.adaptive_chunks = true;
+        self
+    }
+
+    /// Take a fast path for messages of at most `max` values, for workloads
+    /// where the setup of each message costs more than its transfer.
+    ///
+    /// Such messages are sent by [`HiStream::send`] with a single write,
+    /// trailer and delimiter included. On this side, every message is received
+    /// into a buffer sized for `max` values, instead of the large one
+    /// [`hiread`] reserves, and only grows for larger messages.
+    ///
+    /// [`HiStream::send`]: struct.HiStream.html#method.send
+    /// [`hiread`]: fn.hiread.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::HiConfig;
+    ///
+    /// let config = HiConfig::new().small_messages(512);
+    /// ```
+    pub fn small_messages(mut self, max: usize) -> Self {
+        self.small_messages = Some(max);
+        self
+    }
+
+    /// Retry the reads and writes of the underlying stream failing with a
+    /// transient error, according to `policy`, once the handshake is done.
+    ///
+    /// Callers then get an error only once the policy gave up.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Touch every memory page of the data written by a [`HiStream`] before
+    /// its first byte is sent, with [`prefault`].
+    ///
+    /// The first transfer of a freshly allocated array then streams at full
+    /// speed, instead of stalling on page faults midway.
+    ///
+    /// [`HiStream`]: struct.HiStream.html
+    /// [`prefault`]: fn.prefault.html
+    pub fn prefault(mut self) -> Self {
+        self.prefault = true;
+        self
+    }
+
+    /// Reject the received wire frames of more than `len` values, so that a
+    /// confused or malicious peer cannot make the receiver allocate without
+    /// bounds to receive them. Text messages are limited to `len * 8` bytes.
+    /// The numbers of values [`HiStream::read_quantized`] and
+    /// [`HiStream::read_sparse`] decode from a frame are checked against the
+    /// limit too, but not the ones of the free functions such as
+    /// [`hiread_quantized`], which know no configuration.
+    ///
+    /// Reception stops with [`Error::TooLarge`] as soon as the limit is
+    /// exceeded, leaving the connection unusable. The limit is announced
+    /// during the handshake, so that a peer using [`HiStream`] fails to send
+    /// such messages beforehand, with the same error and without writing
+    /// anything. With [`batching`], the limit bounds whole batches.
+    ///
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    /// [`HiStream::read_quantized`]: struct.HiStream.html#method.read_quantized
+    /// [`HiStream::read_sparse`]: struct.HiStream.html#method.read_sparse
+    /// [`hiread_quantized`]: fn.hiread_quantized.html
+    /// [`HiStream`]: struct.HiStream.html
+    /// [`batching`]: #method.batching
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::HiConfig;
+    ///
+    /// let config = HiConfig::new().max_message(1 << 27); // 1 GB
+    /// ```
+    pub fn max_message(mut self, len: usize) -> Self {
+        self.max_message = Some(len);
+        self
+    }
+
+    /// Count the NaN, infinite, subnormal and zero values of every message
+    /// received, reported by [`HiStream::last_report`].
+    ///
+    /// [`HiStream::last_report`]: struct.HiStream.html#method.last_report
+    pub fn float_report(mut self) -> Self {
+        self.float_report = true;
+        self
+    }
+
+    /// Bound the bytes sent by each [`HiStream`], so that a single runaway
+    /// producer cannot saturate a shared uplink.
+    ///
+    /// A message exceeding the quota of the current period is refused with
+    /// [`Error::QuotaExceeded`], before any of its values are written, and
+    /// the stream stays usable. A message written in several parts, such as
+    /// with [`HiStream::write`] or [`HiStream::send_batch`], is only checked
+    /// against the quota with its first part: the next parts count towards
+    /// the quota, but never refuse the message halfway.
+    ///
+    /// [`HiStream`]: struct.HiStream.html
+    /// [`Error::QuotaExceeded`]: enum.Error.html#variant.QuotaExceeded
+    /// [`HiStream::write`]: struct.HiStream.html#method.write
+    /// [`HiStream::send_batch`]: struct.HiStream.html#method.send_batch
+    pub fn send_quota(mut self, quota: Quota) -> Self {
+        self.send_quota = Some(quota);
+        self
+    }
+
+    /// Bound the bytes received by each [`HiStream`].
+    ///
+    /// Once the quota of the current period is exhausted, receiving fails
+    /// with [`Error::QuotaExceeded`] without reading anything, leaving the
+    /// peer blocked until the next period. The message crossing the quota is
+    /// still received.
+    ///
+    /// [`HiStream`]: struct.HiStream.html
+    /// [`Error::QuotaExceeded`]: enum.Error.html#variant.QuotaExceeded
+    pub fn receive_quota(mut self, quota: Quota) -> Self {
+        self.receive_quota = Some(quota);
+        self
+    }
+
+    /// Have receivers follow their acknowledgements with their status: the
+    /// size and checksum of the wire frame they received, and their queue
+    /// depth, given with [`HiStream::set_queue_depth`].
+    ///
+    /// Senders check the size and checksum against what they sent, failing
+    /// with [`Error::Invalid`] if they differ, and get the status with
+    /// [`HiStream::last_ack`] to spot slow consumers. Both ends of a
+    /// handshake must set this option for it to be used, and the status
+    /// costs 24 bytes per wire frame.
+    ///
+    /// [`HiStream::set_queue_depth`]: struct.HiStream.html#method.set_queue_depth
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    /// [`HiStream::last_ack`]: struct.HiStream.html#method.last_ack
+    pub fn ack_status(mut self) -> Self {
+        self.ack_status = true;
+        self
+    }
+
+    /// Check that the delimiter of every wire frame received is the last
+    /// word read with it, failing with [`Error::Framing`] otherwise.
+    ///
+    /// A sender out of step, such as one pipelining messages or writing
+    /// bytes which are not whole words, makes the receiver otherwise take the
+    /// stray bytes for the start of the next message, and every array after
+    /// them comes out garbled. In strict mode, the receiver instead spots a
+    /// delimiter followed by anything else, or out of step with the words,
+    /// right away, and refuses the frame, so that the sender fails with
+    /// [`Error::RemoteError`]. The connection is left unusable until
+    /// [`HiStream::resync`] is called. Checking every word costs a pass over
+    /// the data, as it is received.
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    /// [`Error::RemoteError`]: enum.Error.html#variant.RemoteError
+    /// [`HiStream::resync`]: struct.HiStream.html#method.resync
+    pub fn strict_framing(mut self) -> Self {
+        self.strict_framing = true;
+        self
+    }
+
+    /// Call [`HiStream::resync`] whenever a reception fails with
+    /// [`Error::Framing`] or [`Error::TooLarge`], so that one corrupted
+    /// message does not leave a long-lived connection unusable.
+    ///
+    /// The reception still fails, and the next one gets the next message
+    /// which arrives whole.
+    ///
+    /// [`HiStream::resync`]: struct.HiStream.html#method.resync
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    pub fn auto_resync(mut self) -> Self {
+        self.auto_resync = true;
+        self
+    }
+
+    /// Tunnel the connections opened by [`HiStream::connect`],
+    /// [`HiStream::connect_any`] and [`HiPool`] through a proxy, to cross
+    /// bastion hosts and institutional firewalls.
+    ///
+    /// `url` is either `socks5://[user:password@]host[:port]`, port 1080 by
+    /// default, or `http://[user:password@]host[:port]`, using the `CONNECT`
+    /// method, port 8080 by default. The addresses of the servers are
+    /// resolved locally, and the proxy is given their IP addresses.
+    ///
+    /// Credentials travel in clear to the proxy.
+    ///
+    /// [`HiStream::connect`]: struct.HiStream.html#method.connect
+    /// [`HiStream::connect_any`]: struct.HiStream.html#method.connect_any
+    /// [`HiPool`]: struct.HiPool.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `url` is not a SOCKS5 or HTTP proxy URL.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let config = HiConfig::new().proxy("socks5://bastion.example.org:1080");
+    /// let mut stream = HiStream::connect("10.0.3.7:34567", config)?;
+    /// stream.send(&[1.0, 2.0, 3.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "proxy")]
+    pub fn proxy(mut self, url: &str) -> Self {
+        let proxy = Proxy::parse(url);
+        assert!(proxy.is_some(), "invalid proxy URL: {}", url);
+        self.proxy = proxy;
+        self
+    }
+}
+
+impl fmt::Debug for HiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("HiConfig");
+        debug
+            .field("hmac_key", &self.hmac_key.as_ref().map(|_| "<redacted>"))
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("identity", &self.identity)
+            .field("sessions", &self.sessions)
+            .field("timestamps", &self.timestamps)
+            .field("user_headers", &self.user_headers)
+            .field("batching", &self.batching)
+            .field("column_major", &self.column_major)
+            .field("delimiter", &format_args!("{:#018x}", self.delimiter))
+            .field("schemas", &self.schemas)
+            .field("typed_messages", &self.typed_messages)
+            .field("chunk_size", &self.chunk_size)
+            .field("adaptive_chunks", &self.adaptive_chunks)
+            .field("small_messages", &self.small_messages)
+            .field("retry", &self.retry)
+            .field("prefault", &self.prefault)
+            .field("max_message", &self.max_message)
+            .field("float_report", &self.float_report)
+            .field("send_quota", &self.send_quota)
+            .field("receive_quota", &self.receive_quota)
+            .field("ack_status", &self.ack_status)
+            .field("strict_framing", &self.strict_framing)
+            .field("auto_resync", &self.auto_resync);
+        #[cfg(feature = "proxy")]
+        debug.field("proxy", &self.proxy);
+        debug.finish()
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::mpsc::SendError;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::{HiStream, Result};
+
+#[derive(Default)]
+struct Queue {
+    order: VecDeque<String>,
+    frames: HashMap<String, Vec<f64>>,
+    senders: usize,
+    stopped: bool,
+    conflated: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The sending end of [`spawn_conflating_sender`], queuing at most one frame
+/// per topic.
+///
+/// `ConflatingSender` is a cheap handle: clones feed the same queue, and the
+/// thread ends once they are all dropped and the queue is drained.
+///
+/// [`spawn_conflating_sender`]: fn.spawn_conflating_sender.html
+pub struct ConflatingSender {
+    shared: Arc<Shared>,
+}
+
+impl ConflatingSender {
+    /// Queue `frame` as the next one of `topic`, replacing the frame of
+    /// `topic` still waiting to be sent, if any.
+    ///
+    /// This function never blocks on the network. A replaced frame keeps its
+    /// place in the queue, so that the topics are sent in turn.
+    ///
+    /// # Errors
+    ///
+    /// Gives `frame` back if the thread stopped, after an error.
+    pub fn publish(
+        &self,
+        topic: &str,
+        frame: Vec<f64>,
+    ) -> std::result::Result<(), SendError<Vec<f64>>> {
+        let mut queue = self.shared.lock();
+        if queue.stopped {
+            return Err(SendError(frame));
+        }
+        if queue.frames.insert(topic.to_owned(), frame).is_some() {
+            queue.conflated += 1;
+        } else {
+            queue.order.push_back(topic.to_owned());
+        }
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Number of frames replaced by a newer one before being sent.
+    pub fn conflated(&self) -> u64 {
+        self.shared.lock().conflated
+    }
+
+    /// Number of frames waiting to be sent, at most one per topic.
+    pub fn queued(&self) -> usize {
+        self.shared.lock().order.len()
+    }
+}
+
+impl Clone for ConflatingSender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        ConflatingSender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for ConflatingSender {
+    fn drop(&mut self) {
+        self.shared.lock().senders -= 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl fmt::Debug for ConflatingSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let queue = self.shared.lock();
+        f.debug_struct("ConflatingSender")
+            .field("queued", &queue.order.len())
+            .field("conflated", &queue.conflated)
+            .finish()
+    }
+}
+
+/// Send the frames published to a [`ConflatingSender`] on a dedicated thread,
+/// each as a *High Tension Message* over `stream`, keeping only the latest
+/// frame of each topic while the network is behind.
+///
+/// Unlike [`spawn_sender`], publishing never blocks: a new frame replaces the
+/// one of its topic still waiting to be sent, which bounds both the memory
+/// used and the latency of monitoring streams. With
+/// [`HiConfig::user_headers`], each message carries the name of its topic as
+/// its user header.
+///
+/// The thread ends once every sender is dropped and the queue is drained, or
+/// on the first error, after which publishing fails. Joining it gives the
+/// stream back, or the error.
+///
+/// [`ConflatingSender`]: struct.ConflatingSender.html
+/// [`spawn_sender`]: fn.spawn_sender.html
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, spawn_conflating_sender, HiConfig, HiStream};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<(String, Vec<f64>)>> {
+///     let mut stream = HiStream::server(server, HiConfig::new().user_headers())?;
+///     let mut frames = Vec::new();
+///     while let Ok(frame) = stream.read() {
+///         let topic = String::from_utf8_lossy(stream.last_header().unwrap()).into_owned();
+///         frames.push((topic, frame));
+///     }
+///     Ok(frames)
+/// });
+///
+/// let stream = HiStream::client(client, HiConfig::new().user_headers())?;
+/// let (frames, thread) = spawn_conflating_sender(stream);
+/// for step in 0..1000 {
+///     frames.publish("temperature", vec![f64::from(step); 1000]).unwrap();
+///     frames.publish("pressure", vec![-f64::from(step); 1000]).unwrap();
+/// }
+/// drop(frames);
+/// thread.join().unwrap()?.close()?;
+///
+/// // Some frames were skipped, but the latest ones got through
+/// let frames = consumer.join().unwrap()?;
+/// assert!(frames.contains(&("pressure".into(), vec![-999.0; 1000])));
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_conflating_sender<S>(
+    mut stream: HiStream<S>,
+) -> (ConflatingSender, JoinHandle<Result<HiStream<S>>>)
+where
+    S: Read + Write + Send + 'static,
+{
+    let shared = Arc::new(Shared::default());
+    shared.lock().senders = 1;
+    let sender = ConflatingSender {
+        shared: Arc::clone(&shared),
+    };
+    let thread = thread::spawn(move || {
+        let headers = stream.config().user_headers;
+        while let Some((topic, frame)) = next_frame(&shared) {
+            if headers {
+                stream.set_header(topic.as_bytes());
+            }
+            if let Err(e) = stream.send(&frame) {
+                let mut queue = shared.lock();
+                queue.stopped = true;
+                queue.order.clear();
+                queue.frames.clear();
+                return Err(e);
+            }
+        }
+        Ok(stream)
+    });
+    (sender, thread)
+}
+
+/// Wait for the next frame to send, or `None` once every sender is dropped
+/// and the queue is drained.
+fn next_frame(shared: &Shared) -> Option<(String, Vec<f64>)> {
+    let mut queue = shared.lock();
+    loop {
+        if let Some(topic) = queue.order.pop_front() {
+            let frame = queue.frames.remove(&topic)?;
+            return Some((topic, frame));
+        }
+        if queue.senders == 0 {
+            return None;
+        }
+        queue = shared
+            .changed
+            .wait(queue)
+            .unwrap_or_else(|e| e.into_inner());
+    }
+}
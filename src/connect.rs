@@ -0,0 +1,144 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::{HiConfig, HiStream, Result};
+
+/// Delay before racing the next address against the pending attempts, as
+/// recommended by RFC 8305.
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Open a TCP connection to `addr`, racing its IPv6 and IPv4 addresses.
+///
+/// This function is blocking.
+///
+/// Hostnames often resolve to several records, of both families, some of
+/// them unreachable. Rather than trying them one after the other, and
+/// waiting for each broken one to time out, the addresses are alternated
+/// between families, starting with IPv6, and a new attempt starts every
+/// 250 ms while the previous ones are pending, as in the "Happy Eyeballs"
+/// algorithm of RFC 8305. The first connection established wins, and the
+/// others are dropped.
+///
+/// # Errors
+///
+/// Fails with the error of the last attempt if no address is reachable.
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::connect_dual_stack;
+/// use std::net::TcpListener;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let listener = TcpListener::bind("127.0.0.1:0")?;
+/// let tcp = connect_dual_stack(listener.local_addr()?)?;
+/// assert_eq!(tcp.peer_addr()?, listener.local_addr()?);
+/// # Ok(())
+/// # }
+/// ```
+pub fn connect_dual_stack<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+    race(addr.to_socket_addrs()?.collect(), None)
+}
+
+/// Connect to the first of `addrs` to answer, starting an attempt every
+/// [`ATTEMPT_DELAY`] while the previous ones are pending, each giving up
+/// after `timeout`, if any.
+pub(crate) fn race(addrs: Vec<SocketAddr>, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no address to connect to",
+        ));
+    }
+    let (sender, results) = mpsc::channel();
+    let mut addrs = interleave(addrs).into_iter().peekable();
+    let mut pending = 0;
+    loop {
+        if let Some(addr) = addrs.next() {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                sender.send(match timeout {
+                    Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                    None => TcpStream::connect(addr),
+                })
+            });
+            pending += 1;
+        }
+        let result = if addrs.peek().is_some() {
+            match results.recv_timeout(ATTEMPT_DELAY) {
+                Ok(result) => result,
+                Err(_) => continue,
+            }
+        } else {
+            results.recv().expect("attempts are pending")
+        };
+        pending -= 1;
+        match result {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) if pending == 0 && addrs.peek().is_none() => return Err(e),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Connect to the first of `addrs` to answer, through the proxy of `config`,
+/// if any.
+pub(crate) fn open(
+    addrs: Vec<SocketAddr>,
+    timeout: Option<Duration>,
+    config: &HiConfig,
+) -> io::Result<TcpStream> {
+    #[cfg(feature = "proxy")]
+    {
+        if let Some(proxy) = &config.proxy {
+            return proxy.connect(&addrs, timeout);
+        }
+    }
+    #[cfg(not(feature = "proxy"))]
+    let _ = config;
+    race(addrs, timeout)
+}
+
+/// Alternate IPv6 and IPv4 addresses, keeping their order within families.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv6);
+    let mut v6 = v6.into_iter();
+    let mut v4 = v4.into_iter();
+    let mut interleaved = Vec::with_capacity(v6.len() + v4.len());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+impl HiStream<TcpStream> {
+    /// Connect to the server at `addr`, with [`connect_dual_stack`] or
+    /// through [`HiConfig::proxy`], and perform the client side of the
+    /// handshake.
+    ///
+    /// This function is blocking.
+    ///
+    /// [`connect_dual_stack`]: fn.connect_dual_stack.html
+    /// [`HiConfig::proxy`]: struct.HiConfig.html#method.proxy
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let mut stream = HiStream::connect("node17.cluster:34567", HiConfig::new())?;
+    /// stream.send(&[1.0, 2.0, 3.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect<A: ToSocketAddrs>(addr: A, config: HiConfig) -> Result<Self> {
+        let tcp = open(addr.to_socket_addrs()?.collect(), None, &config)?;
+        HiStream::client(tcp, config)
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+
+use crate::{hiread, is_end, Error, Result};
+
+/// A sink writing the arrays received as record batches to a CSV file, row
+/// by row, as they arrive.
+///
+/// Each batch is a row-major array of whole rows, such as a message read
+/// with [`hiread`] or [`HiStream::read`]. Values are written in their
+/// shortest representation reading back to the same float, and not quoted.
+///
+/// [`hiread`]: fn.hiread.html
+/// [`HiStream::read`]: struct.HiStream.html#method.read
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::CsvSink;
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let file = BufWriter::new(File::create("events.csv")?);
+/// let mut sink = CsvSink::with_header(file, &["time", "energy", "angle"])?;
+///
+/// while sink.receive(&mut stream)? > 0 {}
+/// sink.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CsvSink<W: Write> {
+    writer: W,
+    columns: usize,
+    rows: u64,
+    line: String,
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Create a sink writing rows of `columns` values into `writer`, without
+    /// a header line.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `columns` is zero.
+    pub fn new(writer: W, columns: usize) -> Self {
+        assert!(columns > 0, "CSV rows hold at least one column");
+        CsvSink {
+            writer,
+            columns,
+            rows: 0,
+            line: String::new(),
+        }
+    }
+
+    /// Create a sink writing rows with one value per name of `names` into
+    /// `writer`, after a header line holding the names.
+    ///
+    /// Names holding commas, quotes or newlines are quoted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `names` is empty.
+    pub fn with_header(writer: W, names: &[&str]) -> Result<Self> {
+        let mut sink = Self::new(writer, names.len());
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                sink.line.push(',');
+            }
+            if name.contains(&[',', '"', '\n', '\r'][..]) {
+                let _ = write!(sink.line, "\"{}\"", name.replace('"', "\"\""));
+            } else {
+                sink.line.push_str(name);
+            }
+        }
+        sink.line.push('\n');
+        sink.writer.write_all(sink.line.as_bytes())?;
+        sink.line.clear();
+        Ok(sink)
+    }
+
+    /// Write the rows of the row-major record batch `data`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Invalid`] if `data` does not hold whole rows, and
+    /// then writes nothing.
+    ///
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    pub fn write_batch(&mut self, data: &[f64]) -> Result<()> {
+        if !data.len().is_multiple_of(self.columns) {
+            return Err(Error::Invalid(format!(
+                "batch of {} values, not rows of {} columns",
+                data.len(),
+                self.columns
+            )));
+        }
+        for row in data.chunks_exact(self.columns) {
+            for (i, value) in row.iter().enumerate() {
+                if i > 0 {
+                    self.line.push(',');
+                }
+                let _ = write!(self.line, "{:?}", value);
+            }
+            self.line.push('\n');
+        }
+        let result = self.writer.write_all(self.line.as_bytes());
+        self.line.clear();
+        result?;
+        self.rows += (data.len() / self.columns) as u64;
+        Ok(())
+    }
+
+    /// Read a *High Tension Message* from the `stream`, and write it as a
+    /// record batch.
+    ///
+    /// This function is blocking, and returns the number of rows written. An
+    /// empty message, sent by [`hiempty`], or the end of a dataset, sent by
+    /// [`hiend`], writes none: either signals the end of the data.
+    ///
+    /// [`hiempty`]: fn.hiempty.html
+    /// [`hiend`]: fn.hiend.html
+    pub fn receive<S: Read + Write>(&mut self, stream: &mut S) -> Result<usize> {
+        let data = hiread(stream)?;
+        if is_end(&data) {
+            return Ok(0);
+        }
+        self.write_batch(&data)?;
+        Ok(data.len() / self.columns)
+    }
+
+    /// Number of rows written so far, header excluded.
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Flush the rows written, and give the writer back.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,185 @@
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::pod::{bytes_of, bytes_of_mut};
+use crate::{hidelimiter, hiend, hiread, hiwrite, is_end, pack_bytes, Error, Result};
+
+/// Floats read from a file at a time.
+const FILE_CHUNK: usize = 131_072;
+
+/// Send each of the files at `paths` as a *High Tension Message* of its own
+/// into the `stream`, followed by the end of the dataset, to be written back
+/// by [`hiread_dataset`].
+///
+/// This function is blocking, and returns the number of files sent.
+///
+/// Files travel byte for byte, whatever their content: raw `.f64` arrays as
+/// well as `.npy` files, for instance. Each one is read by chunks while
+/// being sent, and its message ends with its name and length.
+///
+/// [`hiread_dataset`]: fn.hiread_dataset.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Invalid`] if the name of a file is not valid UTF-8.
+///
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread_dataset, hiwrite_dataset, pipe};
+/// use std::{env, fs, thread};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let source = env::temp_dir().join(format!("hi-tension-source-{}", std::process::id()));
+/// let target = env::temp_dir().join(format!("hi-tension-target-{}", std::process::id()));
+/// fs::create_dir_all(&source)?;
+/// fs::write(source.join("run-1.f64"), 1.5f64.to_le_bytes())?;
+/// fs::write(source.join("notes.txt"), "calibrated")?;
+///
+/// let (mut client, mut server) = pipe();
+/// let copy = target.clone();
+/// let consumer = thread::spawn(move || hiread_dataset(&mut server, copy));
+///
+/// let mut paths: Vec<_> = fs::read_dir(&source)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+/// paths.sort();
+/// assert_eq!(hiwrite_dataset(&mut client, &paths)?, 2);
+///
+/// assert_eq!(consumer.join().unwrap()?.len(), 2);
+/// assert_eq!(fs::read(target.join("notes.txt"))?, b"calibrated");
+/// # fs::remove_dir_all(source)?;
+/// # fs::remove_dir_all(target)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_dataset<S, P>(stream: &mut S, paths: &[P]) -> Result<usize>
+where
+    S: Read + Write,
+    P: AsRef<Path>,
+{
+    for path in paths {
+        write_file(stream, path.as_ref())?;
+    }
+    hiend(stream)?;
+    Ok(paths.len())
+}
+
+fn write_file<S: Read + Write>(stream: &mut S, path: &Path) -> Result<()> {
+    let name = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| Error::Invalid(format!("no UTF-8 file name in {}", path.display())))?;
+    let len = write_contents(stream, &mut File::open(path)?)?;
+    finish_file_message(stream, name.as_bytes(), len)
+}
+
+/// End a message holding a file of `len` bytes with the `meta` bytes
+/// describing it.
+pub(crate) fn finish_file_message<S: Read + Write>(
+    stream: &mut S,
+    meta: &[u8],
+    len: u64,
+) -> Result<()> {
+    let mut trailer = pack_bytes(meta);
+    trailer.push(f64::from_bits(meta.len() as u64));
+    trailer.push(f64::from_bits(len));
+    hiwrite(stream, &trailer)?;
+    hidelimiter(stream)
+}
+
+/// Write the rest of `file` into the `stream` as part of the current *High
+/// Tension Message*, by chunks padded with zeros to whole words, returning
+/// the number of bytes written.
+pub(crate) fn write_contents<S: Read + Write>(stream: &mut S, file: &mut File) -> Result<u64> {
+    let mut buf = vec![0.0; FILE_CHUNK];
+    let mut len = 0;
+    loop {
+        let n = fill(file, bytes_of_mut(&mut buf))?;
+        let words = n.div_ceil(8);
+        bytes_of_mut(&mut buf)[n..words * 8].fill(0);
+        hiwrite(stream, &buf[..words])?;
+        len += n as u64;
+        if n < FILE_CHUNK * 8 {
+            return Ok(len);
+        }
+    }
+}
+
+/// Read from `file` until `buf` is full or the file ends, returning the
+/// number of bytes read.
+fn fill(file: &mut File, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(n)
+}
+
+/// Read the files sent by [`hiwrite_dataset`] from the `stream`, until the
+/// end of the dataset, and write each of them into the directory `dir`,
+/// created if needed.
+///
+/// This function is blocking, and returns the paths of the files written, in
+/// order. Files already in `dir` with the same name are replaced. Each
+/// message is held in memory until its file is written.
+///
+/// [`hiwrite_dataset`]: fn.hiwrite_dataset.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if a message does not hold a file, and with
+/// [`Error::Invalid`] if the name of a file is not a plain file name, which
+/// could escape `dir`.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+pub fn hiread_dataset<S: Read + Write>(
+    stream: &mut S,
+    dir: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    loop {
+        let data = hiread(stream)?;
+        if is_end(&data) {
+            return Ok(written);
+        }
+        let (name, bytes) = split_file_message(&data)?;
+        let name = std::str::from_utf8(name)
+            .map_err(|_| Error::Framing("invalid UTF-8 in file name".into()))?;
+        if Path::new(name).file_name() != Some(OsStr::new(name)) {
+            return Err(Error::Invalid(format!("unsafe file name {:?}", name)));
+        }
+        let path = dir.join(name);
+        fs::write(&path, bytes)?;
+        written.push(path);
+    }
+}
+
+/// Split a message holding a file, followed by `meta` bytes, their length
+/// and the length of the file, into the `meta` bytes and the file.
+pub(crate) fn split_file_message(data: &[f64]) -> Result<(&[u8], &[u8])> {
+    let malformed = || Error::Framing("malformed file message".into());
+    let (len, rest) = data.split_last().ok_or_else(malformed)?;
+    let (meta_len, rest) = rest.split_last().ok_or_else(malformed)?;
+    let meta_len = usize::try_from(meta_len.to_bits()).map_err(|_| malformed())?;
+    let meta_words = meta_len.checked_add(7).ok_or_else(malformed)? / 8;
+    let content_words = rest.len().checked_sub(meta_words).ok_or_else(malformed)?;
+    let (content, meta) = rest.split_at(content_words);
+
+    let len = usize::try_from(len.to_bits()).map_err(|_| malformed())?;
+    if len.div_ceil(8) != content.len() {
+        return Err(malformed());
+    }
+    Ok((&bytes_of(meta)[..meta_len], &bytes_of(content)[..len]))
+}
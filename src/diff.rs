@@ -0,0 +1,247 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use crate::{Error, HiStream, Result};
+
+fn word(n: usize) -> f64 {
+    f64::from_bits(n as u64)
+}
+
+/// A 64-bit hash of the bits of `block`, one word at a time.
+fn hash(block: &[f64]) -> u64 {
+    block.iter().fold(block.len() as u64, |hash, value| {
+        (hash.rotate_left(5) ^ value.to_bits()).wrapping_mul(0x517c_c1b7_2722_0a95)
+    })
+}
+
+/// A sender of successive versions of the same buffer, sending only the
+/// blocks which changed since the previous version.
+///
+/// The buffer is cut into blocks of `block_len` values, and the hash of each
+/// block is kept, not the buffer itself. Every [`update`] sends a single
+/// *High Tension Message* holding the blocks whose hash changed, which a
+/// [`DiffReceiver`] applies to its own copy. Iterative solvers sending a
+/// nearly identical state at every step thus only pay for what moved. The
+/// first version, and any version of a different length, is sent whole.
+///
+/// A change can go unnoticed when the hashes of the old and new blocks
+/// collide, which is a 1 in 2<sup>64</sup> chance per changed block.
+///
+/// [`update`]: #method.update
+/// [`DiffReceiver`]: struct.DiffReceiver.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, DiffReceiver, DiffSender, HiConfig, HiStream};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+///     let mut receiver = DiffReceiver::new(HiStream::server(server, HiConfig::new())?);
+///     for _ in 0..10 {
+///         receiver.recv()?;
+///     }
+///     Ok(receiver.snapshot().to_vec())
+/// });
+///
+/// let mut sender = DiffSender::new(HiStream::client(client, HiConfig::new())?, 1024);
+/// let mut state = vec![0.0; 1 << 20];
+/// assert_eq!(sender.update(&state)?, 1024);
+/// for step in 1..10 {
+///     state[step * 1000] = step as f64;
+///     // Only the block holding the changed value is sent
+///     assert_eq!(sender.update(&state)?, 1);
+/// }
+///
+/// assert_eq!(consumer.join().unwrap()?, state);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DiffSender<S> {
+    stream: HiStream<S>,
+    block_len: usize,
+    hashes: Option<Vec<u64>>,
+    len: usize,
+    message: Vec<f64>,
+}
+
+impl<S: Read + Write> DiffSender<S> {
+    /// Send versions of a buffer over `stream`, by blocks of `block_len`
+    /// values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_len` is zero.
+    pub fn new(stream: HiStream<S>, block_len: usize) -> Self {
+        assert!(block_len > 0, "blocks hold at least one value");
+        DiffSender {
+            stream,
+            block_len,
+            hashes: None,
+            len: 0,
+            message: Vec::new(),
+        }
+    }
+
+    /// Send the blocks of `data` which changed since the previous update,
+    /// returning how many were sent.
+    ///
+    /// This function is blocking. When no block changed, an update is still
+    /// sent, empty, so that both sides stay in step.
+    pub fn update(&mut self, data: &[f64]) -> Result<usize> {
+        let hashes: Vec<_> = data.chunks(self.block_len).map(hash).collect();
+        let whole = self.len != data.len() || self.hashes.is_none();
+        let changed: Vec<_> = match &self.hashes {
+            Some(previous) if !whole => (0..hashes.len())
+                .filter(|&i| previous[i] != hashes[i])
+                .collect(),
+            _ => (0..hashes.len()).collect(),
+        };
+
+        // The blocks, then their indices, their count, the length of the
+        // buffer and the length of the blocks
+        self.message.clear();
+        for &i in &changed {
+            self.message
+                .extend_from_slice(block(data, self.block_len, i));
+        }
+        self.message.extend(changed.iter().map(|&i| word(i)));
+        self.message.push(word(changed.len()));
+        self.message.push(word(data.len()));
+        self.message.push(word(self.block_len));
+        let result = self.stream.send(&self.message);
+        if result.is_err() {
+            // The receiver may have missed the update: start over
+            self.reset();
+            return result.map(|()| 0);
+        }
+        self.hashes = Some(hashes);
+        self.len = data.len();
+        Ok(changed.len())
+    }
+
+    /// Send the next update whole, such as after the receiver lost its copy.
+    pub fn reset(&mut self) {
+        self.hashes = None;
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &HiStream<S> {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut HiStream<S> {
+        &mut self.stream
+    }
+
+    /// Unwrap this `DiffSender`, returning the underlying stream.
+    pub fn into_inner(self) -> HiStream<S> {
+        self.stream
+    }
+}
+
+/// The block `i` of `data`, cut into blocks of `block_len` values.
+fn block(data: &[f64], block_len: usize, i: usize) -> &[f64] {
+    let start = i * block_len;
+    &data[start..data.len().min(start + block_len)]
+}
+
+/// The receiving end of a [`DiffSender`], keeping a copy of the buffer up to
+/// date.
+///
+/// [`DiffSender`]: struct.DiffSender.html
+#[derive(Debug)]
+pub struct DiffReceiver<S> {
+    stream: HiStream<S>,
+    snapshot: Vec<f64>,
+}
+
+impl<S: Read + Write> DiffReceiver<S> {
+    /// Receive versions of a buffer over `stream`, starting empty.
+    pub fn new(stream: HiStream<S>) -> Self {
+        DiffReceiver {
+            stream,
+            snapshot: Vec::new(),
+        }
+    }
+
+    /// Read the next update, and apply it to the copy of the buffer,
+    /// returning that copy.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Framing`] if the message is not an update of the
+    /// buffer received so far, such as a partial update coming before any
+    /// whole one. The copy is then left unchanged.
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    pub fn recv(&mut self) -> Result<&[f64]> {
+        let message = self.stream.read()?;
+        let malformed = || Error::Framing("malformed buffer update".into());
+        let size = |v: &f64| usize::try_from(v.to_bits()).map_err(|_| malformed());
+
+        let (rest, header) = match message.len().checked_sub(3) {
+            Some(n) => message.split_at(n),
+            None => return Err(malformed()),
+        };
+        let (count, len, block_len) = (size(&header[0])?, size(&header[1])?, size(&header[2])?);
+        if block_len == 0 {
+            return Err(malformed());
+        }
+        let blocks = len.div_ceil(block_len);
+        let indices_start = rest.len().checked_sub(count).ok_or_else(malformed)?;
+        let (mut values, indices) = rest.split_at(indices_start);
+
+        let whole = count == blocks;
+        if !whole && len != self.snapshot.len() {
+            return Err(malformed());
+        }
+        // Check the whole update before applying any of it
+        let mut total = 0;
+        for index in indices {
+            let i = size(index)?;
+            if i >= blocks {
+                return Err(malformed());
+            }
+            total += block_len.min(len - i * block_len);
+        }
+        if total != values.len() {
+            return Err(malformed());
+        }
+
+        self.snapshot.resize(len, 0.0);
+        for index in indices {
+            let start = index.to_bits() as usize * block_len;
+            let n = block_len.min(len - start);
+            self.snapshot[start..start + n].copy_from_slice(&values[..n]);
+            values = &values[n..];
+        }
+        Ok(&self.snapshot)
+    }
+
+    /// The copy of the buffer, as of the last update.
+    pub fn snapshot(&self) -> &[f64] {
+        &self.snapshot
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &HiStream<S> {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut HiStream<S> {
+        &mut self.stream
+    }
+
+    /// Unwrap this `DiffReceiver`, returning the underlying stream.
+    pub fn into_inner(self) -> HiStream<S> {
+        self.stream
+    }
+}
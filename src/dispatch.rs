@@ -0,0 +1,162 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::mpsc::SyncSender;
+
+use crate::{Error, HiStream, Result};
+
+type Handler<'a> = Box<dyn FnMut(&[u8], Vec<f64>) -> Result<()> + 'a>;
+
+/// Route the messages of a connection to independent consumers, by their
+/// user header.
+///
+/// Each route handles the messages tagged with a given header, as set by the
+/// sender with [`HiStream::set_header`]. The receiving stream needs
+/// [`HiConfig::user_headers`] too: without it, every message is tagged with an
+/// empty header.
+///
+/// [`HiStream::set_header`]: struct.HiStream.html#method.set_header
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, Dispatcher, HiConfig, HiStream};
+/// use std::sync::mpsc;
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let producer = thread::spawn(move || -> hi_tension::Result<()> {
+///     let mut stream = HiStream::client(client, HiConfig::new().user_headers())?;
+///     stream.set_header(b"spectrum");
+///     stream.send(&[1.0, 2.0])?;
+///     stream.set_header(b"image");
+///     stream.send(&[3.0; 16])?;
+///     stream.close()?;
+///     Ok(())
+/// });
+///
+/// let mut stream = HiStream::server(server, HiConfig::new().user_headers())?;
+/// let (images, received) = mpsc::sync_channel(4);
+/// let mut spectra = Vec::new();
+/// Dispatcher::new()
+///     .on(b"spectrum", |data| {
+///         spectra.push(data);
+///         Ok(())
+///     })
+///     .forward(b"image", images)
+///     .run(&mut stream)?;
+///
+/// producer.join().unwrap()?;
+/// assert_eq!(spectra, [[1.0, 2.0]]);
+/// assert_eq!(received.recv().unwrap(), [3.0; 16]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Dispatcher<'a> {
+    routes: Vec<(Vec<u8>, Handler<'a>)>,
+    fallback: Option<Handler<'a>>,
+}
+
+impl<'a> Dispatcher<'a> {
+    /// Create a dispatcher without any route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hand the messages tagged with `tag` over to `f`.
+    ///
+    /// An error returned by `f` stops the dispatcher, which returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` already has a route.
+    pub fn on<F>(self, tag: &[u8], mut f: F) -> Self
+    where
+        F: FnMut(Vec<f64>) -> Result<()> + 'a,
+    {
+        self.route(tag, Box::new(move |_, data| f(data)))
+    }
+
+    /// Send the messages tagged with `tag` to a channel, for a consumer on
+    /// another thread.
+    ///
+    /// The dispatcher blocks while the channel is full. Once its receiver is
+    /// dropped, the messages of the route are dropped too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` already has a route.
+    pub fn forward(self, tag: &[u8], sender: SyncSender<Vec<f64>>) -> Self {
+        self.route(
+            tag,
+            Box::new(move |_, data| {
+                let _ = sender.send(data);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Hand the messages matching no route over to `f`, with their header.
+    ///
+    /// Without a fallback, such messages stop the dispatcher with an error.
+    pub fn fallback<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&[u8], Vec<f64>) -> Result<()> + 'a,
+    {
+        self.fallback = Some(Box::new(f));
+        self
+    }
+
+    fn route(mut self, tag: &[u8], handler: Handler<'a>) -> Self {
+        assert!(
+            self.routes.iter().all(|(other, _)| other != tag),
+            "duplicate route for header {:?}",
+            tag
+        );
+        self.routes.push((tag.to_vec(), handler));
+        self
+    }
+
+    /// Read the messages of `stream` and dispatch them, until the peer closes
+    /// the connection with [`HiStream::close`].
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails on the first error of the stream or of a route, and with
+    /// [`Error::Invalid`] on a message matching no route without a fallback.
+    ///
+    /// [`HiStream::close`]: struct.HiStream.html#method.close
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    pub fn run<S: Read + Write>(&mut self, stream: &mut HiStream<S>) -> Result<()> {
+        loop {
+            let data = match stream.read() {
+                Ok(data) => data,
+                Err(Error::Closed) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let tag = stream.last_header().unwrap_or_default();
+            let handler = match self.routes.iter_mut().find(|(other, _)| other == tag) {
+                Some((_, handler)) => handler,
+                None => match &mut self.fallback {
+                    Some(fallback) => fallback,
+                    None => return Err(Error::Invalid(format!("no route for header {:?}", tag))),
+                },
+            };
+            handler(tag, data)?;
+        }
+    }
+}
+
+impl fmt::Debug for Dispatcher<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tags: Vec<_> = self.routes.iter().map(|(tag, _)| tag).collect();
+        f.debug_struct("Dispatcher")
+            .field("routes", &tags)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
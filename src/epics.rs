@@ -0,0 +1,236 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use crate::{Error, HiStream, Result};
+
+/// Updates of EPICS process variables, as reported by the `camonitor` tool
+/// of EPICS base.
+///
+/// Each update of a PV comes with all the values of the PV, a single one for
+/// scalar records, and all the elements for waveforms. The `camonitor`
+/// process runs as long as the monitor, with the Channel Access environment
+/// of the current process, such as `EPICS_CA_ADDR_LIST`, and is killed when
+/// the monitor is dropped. This requires the `epics` feature.
+///
+/// Use [`hiforward_pvs`] to forward the updates as *High Tension Messages*.
+///
+/// [`hiforward_pvs`]: fn.hiforward_pvs.html
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::PvMonitor;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut monitor = PvMonitor::spawn(&["BL13:DET1:Spectrum", "BL13:RING:Current"])?;
+/// loop {
+///     let (pv, values) = monitor.recv()?;
+///     println!("{}: {} values", pv, values.len());
+/// }
+/// # }
+/// ```
+pub struct PvMonitor {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    line: String,
+}
+
+impl PvMonitor {
+    /// Start monitoring the PVs called `pvs`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `camonitor` cannot be run. PVs which cannot be connected to
+    /// are only reported by `camonitor` on its standard error, and never
+    /// updated.
+    pub fn spawn(pvs: &[&str]) -> Result<Self> {
+        let mut child = Command::new("camonitor")
+            .args(["-t", "n"])
+            .args(pvs)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("piped standard output");
+        Ok(PvMonitor {
+            child,
+            stdout: BufReader::new(stdout),
+            line: String::new(),
+        })
+    }
+
+    /// Wait for the next update, returning the name of its PV and its
+    /// values.
+    ///
+    /// This function is blocking. Lines which are not updates, such as the
+    /// disconnections of a PV, or updates whose values are not numbers, such
+    /// as strings, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Fails with an IO error of kind [`BrokenPipe`], telling the exit
+    /// status of `camonitor`, once it exited.
+    ///
+    /// [`BrokenPipe`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.BrokenPipe
+    pub fn recv(&mut self) -> Result<(String, Vec<f64>)> {
+        loop {
+            self.line.clear();
+            if self.stdout.read_line(&mut self.line)? == 0 {
+                let status = self.child.wait()?;
+                let reason = format!("camonitor exited with {}", status);
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, reason).into());
+            }
+            if let Some(update) = parse_update(&self.line) {
+                return Ok(update);
+            }
+        }
+    }
+}
+
+/// Parse a line printed by `camonitor -t n`: the name of the PV, then its
+/// value, or the number of its elements followed by them, and the alarm
+/// status and severity, if any.
+fn parse_update(line: &str) -> Option<(String, Vec<f64>)> {
+    let mut fields = line.split_whitespace();
+    let pv = fields.next()?;
+    let fields: Vec<_> = fields.collect();
+    let parse = |values: &[&str]| -> Option<Vec<f64>> {
+        values.iter().map(|value| value.parse().ok()).collect()
+    };
+    // A scalar whose value could be a count is taken for one when what
+    // follows is its alarm, rather than values
+    let array = match fields.split_first() {
+        Some((count, rest)) if !rest.is_empty() => count
+            .parse::<usize>()
+            .ok()
+            .filter(|&count| count <= rest.len())
+            .and_then(|count| parse(&rest[..count])),
+        _ => None,
+    };
+    let values = array.or_else(|| parse(fields.get(..1)?))?;
+    Some((pv.to_owned(), values))
+}
+
+impl Drop for PvMonitor {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl fmt::Debug for PvMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PvMonitor")
+            .field("pid", &self.child.id())
+            .finish()
+    }
+}
+
+/// Write `values` into the EPICS process variable `pv`, with the `caput`
+/// tool of EPICS base.
+///
+/// This function is blocking. A single value is written as is, and more as
+/// an array, such as into a waveform record. This requires the `epics`
+/// feature.
+///
+/// Values travel on the command line of `caput`, which bounds their number
+/// to somewhere around 100 000 on most systems.
+///
+/// # Errors
+///
+/// Fails if `caput` cannot be run, and with [`Error::RemoteError`], holding
+/// what `caput` reported, if it failed.
+///
+/// [`Error::RemoteError`]: enum.Error.html#variant.RemoteError
+pub fn put_pv(pv: &str, values: &[f64]) -> Result<()> {
+    let mut command = Command::new("caput");
+    command.arg("-t");
+    let array = values.len() != 1;
+    if array {
+        command.arg("-a");
+    }
+    // Negative values are not options
+    command.args(["--", pv]);
+    if array {
+        command.arg(values.len().to_string());
+    }
+    let output = command
+        .args(values.iter().map(f64::to_string))
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::RemoteError(reason.trim().to_owned()));
+    }
+    Ok(())
+}
+
+/// Forward the updates of `monitor` as *High Tension Messages* into the
+/// `stream`, until `camonitor` exits or the stream fails.
+///
+/// This function is blocking. With [`HiConfig::user_headers`], each message
+/// carries the name of its PV as its user header, so that a [`Dispatcher`]
+/// can route them. This requires the `epics` feature.
+///
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+/// [`Dispatcher`]: struct.Dispatcher.html
+///
+/// # Errors
+///
+/// Fails like [`PvMonitor::recv`] once `camonitor` exited, and like
+/// [`HiStream::send`] otherwise.
+///
+/// [`PvMonitor::recv`]: struct.PvMonitor.html#method.recv
+/// [`HiStream::send`]: struct.HiStream.html#method.send
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{hiforward_pvs, HiConfig, HiStream, PvMonitor};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = HiStream::connect("10.0.3.7:34567", HiConfig::new().user_headers())?;
+/// let mut monitor = PvMonitor::spawn(&["BL13:DET1:Spectrum"])?;
+/// hiforward_pvs(&mut monitor, &mut stream)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiforward_pvs<S: Read + Write>(
+    monitor: &mut PvMonitor,
+    stream: &mut HiStream<S>,
+) -> Result<()> {
+    let headers = stream.config().user_headers;
+    loop {
+        let (pv, values) = monitor.recv()?;
+        if headers {
+            stream.set_header(pv.as_bytes());
+        }
+        stream.send(&values)?;
+    }
+}
+
+/// Write every *High Tension Message* received from the `stream` into the
+/// EPICS process variable `pv`, with [`put_pv`], until the peer closes the
+/// connection.
+///
+/// This function is blocking, and exposes the arrays received to the EPICS
+/// clients of `pv`, such as a waveform record of a soft IOC. This requires
+/// the `epics` feature.
+///
+/// [`put_pv`]: fn.put_pv.html
+///
+/// # Errors
+///
+/// Fails like [`put_pv`] and [`HiStream::read`].
+///
+/// [`HiStream::read`]: struct.HiStream.html#method.read
+pub fn hiexpose_pv<S: Read + Write>(stream: &mut HiStream<S>, pv: &str) -> Result<()> {
+    loop {
+        let values = match stream.read() {
+            Ok(values) => values,
+            Err(Error::Closed) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        put_pv(pv, &values)?;
+    }
+}
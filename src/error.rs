@@ -0,0 +1,113 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// A `Result` alias where the error is a `hi-tension` [`Error`].
+///
+/// [`Error`]: enum.Error.html
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The error type for `hi-tension` operations.
+///
+/// Most errors are plain IO errors coming from the underlying stream. The other
+/// variants describe protocol-level failures.
+///
+/// An `Error` converts into a [`std::io::Error`], so `?` keeps working inside
+/// functions returning `std::io::Result`.
+///
+/// [`std::io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+#[derive(Debug)]
+pub enum Error {
+    /// An IO error from the underlying stream.
+    Io(io::Error),
+    /// Authentication failed: either the HMAC attached to a received message
+    /// did not match its content, or the access token sent during the
+    /// handshake was refused.
+    AuthFailed,
+    /// The handshake could not be completed, with the reason why.
+    Handshake(String),
+    /// A received message does not follow the expected framing, with the
+    /// reason why. This usually means that both sides do not use the same
+    /// configuration.
+    Framing(String),
+    /// A received message holds invalid data, with the reason why.
+    Invalid(String),
+    /// A message is larger than the limit of the receiver, holding that
+    /// limit in floats: see [`HiConfig::max_message`].
+    ///
+    /// [`HiConfig::max_message`]: struct.HiConfig.html#method.max_message
+    TooLarge(usize),
+    /// The quota of the connection is exhausted, holding the time until its
+    /// next period starts: see [`HiConfig::send_quota`].
+    ///
+    /// [`HiConfig::send_quota`]: struct.HiConfig.html#method.send_quota
+    QuotaExceeded(Duration),
+    /// The peer deliberately closed the connection with [`HiStream::close`].
+    ///
+    /// [`HiStream::close`]: struct.HiStream.html#method.close
+    Closed,
+    /// The underlying stream would block: it is in nonblocking mode, or a
+    /// read or write timeout elapsed. Messages are transferred with blocking
+    /// semantics, so the one in progress may be left halfway. A
+    /// [`RetryPolicy`] waits for such streams instead.
+    ///
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    WouldBlock,
+    /// The peer refused the message being sent with [`HiStream::reject`],
+    /// with the reason it gave. The connection stays usable.
+    ///
+    /// [`HiStream::reject`]: struct.HiStream.html#method.reject
+    RemoteError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => e.fmt(f),
+            Error::AuthFailed => f.write_str("authentication failed"),
+            Error::Handshake(reason) => write!(f, "handshake failed: {}", reason),
+            Error::Framing(reason) => write!(f, "invalid framing: {}", reason),
+            Error::Invalid(reason) => write!(f, "invalid data: {}", reason),
+            Error::TooLarge(limit) => {
+                write!(f, "message larger than the limit of {} floats", limit)
+            }
+            Error::QuotaExceeded(wait) => {
+                write!(f, "quota exceeded, renewed in {:.0?}", wait)
+            }
+            Error::Closed => f.write_str("connection closed by peer"),
+            Error::WouldBlock => f.write_str("operation would block"),
+            Error::RemoteError(reason) => write!(f, "refused by peer: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::WouldBlock => Error::WouldBlock,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Closed => io::Error::new(io::ErrorKind::ConnectionAborted, e),
+            Error::WouldBlock => io::ErrorKind::WouldBlock.into(),
+            Error::QuotaExceeded(_) => io::Error::new(io::ErrorKind::QuotaExceeded, e),
+            Error::RemoteError(_) => io::Error::other(e),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{Read, Write};
+
+use crate::message::read_text;
+use crate::{hidelimiter, hiread, hiwrite, Result};
+
+/// Methods of the protocol on any `Read + Write` type, for those who do not
+/// want to wrap their stream in a [`HiStream`].
+///
+/// Each method is the free function of the same name, such as [`hiread`] for
+/// [`hi_read`], so both can be mixed on the same stream.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`hiread`]: fn.hiread.html
+/// [`hi_read`]: #method.hi_read
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::HiExt;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// stream.hi_text("start")?;
+/// stream.hi_send(&vec![0.0; 1_000_000])?;
+/// let answer = stream.hi_read()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait HiExt: Read + Write {
+    /// Read a *High Tension Message*, like [`hiread`].
+    ///
+    /// [`hiread`]: fn.hiread.html
+    fn hi_read(&mut self) -> Result<Vec<f64>>;
+
+    /// Send a `data` slice as part of a *High Tension Message*, like
+    /// [`hiwrite`].
+    ///
+    /// [`hiwrite`]: fn.hiwrite.html
+    fn hi_write(&mut self, data: &[f64]) -> Result<()>;
+
+    /// Signal the ending of a *High Tension Message*, like [`hidelimiter`].
+    ///
+    /// [`hidelimiter`]: fn.hidelimiter.html
+    fn hi_delimiter(&mut self) -> Result<()>;
+
+    /// Send `data` as a complete *High Tension Message*: a shorthand for
+    /// [`hi_write`] followed by [`hi_delimiter`].
+    ///
+    /// [`hi_write`]: #tymethod.hi_write
+    /// [`hi_delimiter`]: #tymethod.hi_delimiter
+    fn hi_send(&mut self, data: &[f64]) -> Result<()>;
+
+    /// Send `text` as a *Simple Text Message*, followed by its newline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `text` contains a newline.
+    fn hi_text(&mut self, text: &str) -> Result<()>;
+
+    /// Read a *Simple Text Message*, without its newline.
+    ///
+    /// The stream is read byte by byte, so that nothing past the newline is
+    /// consumed, and a *High Tension Message* can follow.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Framing`] if the text is not valid UTF-8.
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    fn hi_read_text(&mut self) -> Result<String>;
+}
+
+impl<S: Read + Write> HiExt for S {
+    fn hi_read(&mut self) -> Result<Vec<f64>> {
+        hiread(self)
+    }
+
+    fn hi_write(&mut self, data: &[f64]) -> Result<()> {
+        hiwrite(self, data)
+    }
+
+    fn hi_delimiter(&mut self) -> Result<()> {
+        hidelimiter(self)
+    }
+
+    fn hi_send(&mut self, data: &[f64]) -> Result<()> {
+        hiwrite(self, data)?;
+        hidelimiter(self)
+    }
+
+    fn hi_text(&mut self, text: &str) -> Result<()> {
+        assert!(
+            !text.contains('\n'),
+            "text messages cannot contain newlines"
+        );
+        self.write_all(text.as_bytes())?;
+        self.write_all(b"\n")?;
+        self.flush()?;
+        Ok(())
+    }
+
+    fn hi_read_text(&mut self) -> Result<String> {
+        read_text(self)
+    }
+}
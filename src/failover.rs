@@ -0,0 +1,262 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::{connect, Error, HiConfig, HiStream, Result};
+
+/// How a [`FailoverStream`] watches its connection and moves to another
+/// address.
+///
+/// [`FailoverStream`]: struct.FailoverStream.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::FailoverPolicy;
+/// use std::time::Duration;
+///
+/// let policy = FailoverPolicy::new()
+///     .health_interval(Duration::from_secs(1))
+///     .connect_timeout(Duration::from_millis(200))
+///     .ack_timeout(Duration::from_secs(2), 3);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailoverPolicy {
+    health_interval: Duration,
+    connect_timeout: Duration,
+    ack_timeout: Option<Duration>,
+    retransmits: u32,
+}
+
+impl FailoverPolicy {
+    /// Create a policy checking the connection every 5 s, and giving each
+    /// address 1 s to accept a connection.
+    pub fn new() -> Self {
+        FailoverPolicy {
+            health_interval: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(1),
+            ack_timeout: None,
+            retransmits: 1,
+        }
+    }
+
+    /// Ping the peer before sending, if nothing was sent for `interval`.
+    pub fn health_interval(mut self, interval: Duration) -> Self {
+        self.health_interval = interval;
+        self
+    }
+
+    /// Give up connecting to an address after `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        assert!(
+            !timeout.is_zero(),
+            "connections need time to be established"
+        );
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Wait at most `timeout` for the peer to acknowledge each message, with
+    /// [`HiStream::set_ack_timeout`], and send a message again at most
+    /// `retransmits` times after connection errors, instead of once.
+    ///
+    /// A message is then lost only when none of the connections it was sent
+    /// to got it through in time.
+    ///
+    /// [`HiStream::set_ack_timeout`]: struct.HiStream.html#method.set_ack_timeout
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    pub fn ack_timeout(mut self, timeout: Duration, retransmits: u32) -> Self {
+        assert!(!timeout.is_zero(), "acknowledgements need time to arrive");
+        self.ack_timeout = Some(timeout);
+        self.retransmits = retransmits;
+        self
+    }
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HiStream<TcpStream> {
+    /// Connect to the first of `addrs` to accept the connection and the
+    /// handshake, failing over to the next ones on persistent errors.
+    ///
+    /// This function is blocking. Each address may resolve to several socket
+    /// addresses, tried in turn.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the error of the last address tried if none is reachable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{FailoverPolicy, HiConfig, HiStream};
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let addrs = ["10.0.0.2:34567", "10.1.0.2:34567"];
+    /// let mut stream = HiStream::connect_any(&addrs, HiConfig::new(), FailoverPolicy::new())?;
+    ///
+    /// loop {
+    ///     stream.send(&vec![0.0; 1_000_000])?;
+    /// }
+    /// # }
+    /// ```
+    pub fn connect_any<A: ToSocketAddrs>(
+        addrs: &[A],
+        config: HiConfig,
+        policy: FailoverPolicy,
+    ) -> Result<FailoverStream> {
+        let mut resolved = Vec::new();
+        for addr in addrs {
+            resolved.extend(addr.to_socket_addrs()?);
+        }
+        if resolved.is_empty() {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to").into(),
+            );
+        }
+        let mut stream = FailoverStream {
+            stream: None,
+            addrs: resolved,
+            current: 0,
+            config,
+            policy,
+            last_used: Instant::now(),
+        };
+        stream.failover(0)?;
+        Ok(stream)
+    }
+}
+
+/// A connection to one of several addresses of the same peer, opened with
+/// [`HiStream::connect_any`], moving to the next address when the current one
+/// fails.
+///
+/// Before sending after [`FailoverPolicy::health_interval`] of inactivity,
+/// the peer is pinged with [`HiStream::ping`]. When a send or a ping fails
+/// with a connection error, the other addresses are tried in turn, and the
+/// message is sent again to the new peer, once unless
+/// [`FailoverPolicy::ack_timeout`] allows more. It may then be received
+/// twice, if only its acknowledgement was lost or late. Transient errors are better retried on
+/// the same connection, with [`HiConfig::retry`].
+///
+/// [`HiStream::connect_any`]: struct.HiStream.html#method.connect_any
+/// [`FailoverPolicy::health_interval`]: struct.FailoverPolicy.html#method.health_interval
+/// [`FailoverPolicy::ack_timeout`]: struct.FailoverPolicy.html#method.ack_timeout
+/// [`HiStream::ping`]: struct.HiStream.html#method.ping
+/// [`HiConfig::retry`]: struct.HiConfig.html#method.retry
+#[derive(Debug)]
+pub struct FailoverStream {
+    stream: Option<HiStream<TcpStream>>,
+    addrs: Vec<SocketAddr>,
+    current: usize,
+    config: HiConfig,
+    policy: FailoverPolicy,
+    last_used: Instant,
+}
+
+impl FailoverStream {
+    /// Connect to the first address to accept, starting from the one at
+    /// `start`.
+    fn failover(&mut self, start: usize) -> Result<()> {
+        self.stream = None;
+        let mut last_error = None;
+        for offset in 0..self.addrs.len() {
+            let index = (start + offset) % self.addrs.len();
+            match self.connect(self.addrs[index]) {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    self.current = index;
+                    self.last_used = Instant::now();
+                    return Ok(());
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    fn connect(&self, addr: SocketAddr) -> Result<HiStream<TcpStream>> {
+        let tcp = connect::open(vec![addr], Some(self.policy.connect_timeout), &self.config)?;
+        let mut stream = HiStream::client(tcp, self.config.clone())?;
+        if let Some(timeout) = self.policy.ack_timeout {
+            stream.set_ack_timeout(Some(timeout))?;
+        }
+        Ok(stream)
+    }
+
+    /// Run `op` on the current connection, failing over and running it
+    /// again on connection errors, as many times as the policy allows.
+    fn with_failover<T, F>(&mut self, mut op: F) -> Result<T>
+    where
+        F: FnMut(&mut HiStream<TcpStream>) -> Result<T>,
+    {
+        let mut result = match &mut self.stream {
+            Some(stream) => op(stream),
+            None => Err(Error::Closed),
+        };
+        let mut retransmits = 0;
+        while matches!(&result, Err(e) if is_connection_error(e))
+            && retransmits < self.policy.retransmits
+        {
+            self.failover(self.current + 1)?;
+            result = op(self.get_mut());
+            retransmits += 1;
+        }
+        self.last_used = Instant::now();
+        result
+    }
+
+    /// Send `data` as a complete *High Tension Message*, like
+    /// [`HiStream::send`].
+    ///
+    /// This function is blocking.
+    ///
+    /// [`HiStream::send`]: struct.HiStream.html#method.send
+    pub fn send(&mut self, data: &[f64]) -> Result<()> {
+        if self.last_used.elapsed() >= self.policy.health_interval {
+            self.check_health()?;
+        }
+        self.with_failover(|stream| stream.send(data))
+    }
+
+    /// Ping the peer, failing over if it does not answer, and return the
+    /// round-trip time.
+    ///
+    /// This function is blocking.
+    pub fn check_health(&mut self) -> Result<Duration> {
+        self.with_failover(HiStream::ping)
+    }
+
+    /// Get the address of the current peer.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.addrs[self.current]
+    }
+
+    /// Get a mutable reference to the current connection.
+    pub fn get_mut(&mut self) -> &mut HiStream<TcpStream> {
+        self.stream
+            .as_mut()
+            .expect("connected after a successful failover")
+    }
+
+    /// Unwrap this `FailoverStream`, returning the current connection.
+    pub fn into_inner(self) -> HiStream<TcpStream> {
+        self.stream.expect("connected after a successful failover")
+    }
+}
+
+/// Whether an error means that the connection itself failed.
+fn is_connection_error(e: &Error) -> bool {
+    matches!(e, Error::Io(_) | Error::Closed | Error::WouldBlock)
+}
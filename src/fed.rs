@@ -0,0 +1,101 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+/// A stream which, while feeding, reads the bytes fed to it only, failing
+/// with `WouldBlock` when they run out, for the caller to wait for more bytes
+/// without blocking. Writes always go to the inner stream.
+///
+/// [`HiServer::serve_nonblocking`] feeds the bytes each connection received to
+/// its `HiStream` this way.
+///
+/// [`HiServer::serve_nonblocking`]: struct.HiServer.html#method.serve_nonblocking
+#[derive(Debug)]
+pub(crate) struct Fed<S> {
+    pub(crate) inner: S,
+    fed: Vec<u8>,
+    consumed: usize,
+    feeding: bool,
+    rewound: bool,
+}
+
+impl<S> Fed<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Fed {
+            inner,
+            fed: Vec::new(),
+            consumed: 0,
+            feeding: false,
+            rewound: false,
+        }
+    }
+
+    /// Append `bytes` to the ones to read while feeding.
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        self.fed.drain(..self.consumed);
+        self.consumed = 0;
+        self.rewound = false;
+        self.fed.extend_from_slice(bytes);
+    }
+
+    /// The bytes fed and not read yet.
+    pub(crate) fn fed(&self) -> &[u8] {
+        &self.fed[self.consumed..]
+    }
+
+    /// Read the bytes fed rather than the inner stream, or stop doing so.
+    pub(crate) fn set_feeding(&mut self, feeding: bool) {
+        self.feeding = feeding;
+    }
+
+    pub(crate) fn is_feeding(&self) -> bool {
+        self.feeding
+    }
+
+    /// Whether feeding, with all the bytes fed read.
+    pub(crate) fn is_drained(&self) -> bool {
+        self.feeding && self.fed().is_empty()
+    }
+
+    /// Number of bytes fed and read, since the last call to `feed`.
+    pub(crate) fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Read the bytes fed again from `consumed`, a value of [`consumed`]
+    /// since the last call to `feed`, once more bytes are fed.
+    ///
+    /// [`consumed`]: #method.consumed
+    pub(crate) fn rewind(&mut self, consumed: usize) {
+        debug_assert!(consumed <= self.consumed);
+        self.consumed = consumed;
+        self.rewound = true;
+    }
+
+    /// Whether the bytes fed were rewound, and more are needed to read them.
+    pub(crate) fn is_rewound(&self) -> bool {
+        self.rewound
+    }
+}
+
+impl<S: Read> Read for Fed<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.feeding {
+            return self.inner.read(buf);
+        }
+        let n = (&self.fed[self.consumed..]).read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.consumed += n;
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Fed<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
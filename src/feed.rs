@@ -0,0 +1,180 @@
+use std::fmt;
+
+use crate::{Error, Result, DELIMITER_NAN};
+
+/// The acknowledgement of a *High Tension Message*, to be written back to
+/// the sender for every message a [`FeedReceiver`] completes.
+///
+/// [`FeedReceiver`]: struct.FeedReceiver.html
+pub const ACK: &[u8] = b"\n";
+
+/// A receiver of *High Tension Messages* which does no IO itself, for
+/// applications whose framework owns the event loop, such as LabVIEW, EPICS
+/// or C programs.
+///
+/// The loop reads the socket whenever it is readable, and hands the bytes
+/// read over to [`feed_bytes`], in as many pieces as it likes. Every message
+/// completed is handed over to the callback, and must then be acknowledged
+/// by writing [`ACK`] back to the sender. The plain protocol is spoken,
+/// like [`hiread`] does, so the sender uses [`hiwrite`] and [`hidelimiter`],
+/// or a [`Connection`]. C programs use it through the functions of the
+/// [`ffi`] module, with the `ffi` feature.
+///
+/// [`feed_bytes`]: #method.feed_bytes
+/// [`ACK`]: constant.ACK.html
+/// [`hiread`]: fn.hiread.html
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+/// [`Connection`]: struct.Connection.html
+/// [`ffi`]: ffi/index.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hidelimiter, hiwrite, pipe, FeedReceiver, ACK};
+/// use std::io::{Read, Write};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let producer = thread::spawn(move || -> hi_tension::Result<()> {
+///     for i in 0..3 {
+///         hiwrite(&mut client, &[f64::from(i); 1000])?;
+///         hidelimiter(&mut client)?;
+///     }
+///     Ok(())
+/// });
+///
+/// let mut received = Vec::new();
+/// let mut receiver = FeedReceiver::new(|data: &[f64]| received.push(data[0]));
+///
+/// // The loop of the framework, calling back whenever the socket is readable
+/// let mut buf = [0; 1500];
+/// while receiver.completed() < 3 {
+///     let n = server.read(&mut buf)?;
+///     for _ in 0..receiver.feed_bytes(&buf[..n])? {
+///         server.write_all(ACK)?;
+///     }
+/// }
+///
+/// producer.join().unwrap()?;
+/// drop(receiver);
+/// assert_eq!(received, [0.0, 1.0, 2.0]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct FeedReceiver<F> {
+    on_message: F,
+    words: Vec<f64>,
+    partial: [u8; 8],
+    partial_len: usize,
+    max_message: Option<usize>,
+    completed: u64,
+}
+
+impl<F: FnMut(&[f64])> FeedReceiver<F> {
+    /// Receive messages, handing each of them over to `on_message`.
+    ///
+    /// The message is borrowed until `on_message` returns, so that its
+    /// buffer is reused for the next one: copy it with `to_vec` to keep it.
+    pub fn new(on_message: F) -> Self {
+        FeedReceiver {
+            on_message,
+            words: Vec::new(),
+            partial: [0; 8],
+            partial_len: 0,
+            max_message: None,
+            completed: 0,
+        }
+    }
+
+    /// Reject the wire frames of more than `len` values, so that a confused or
+    /// malicious peer cannot make the receiver allocate without bounds to
+    /// receive them, like [`HiConfig::max_message`].
+    ///
+    /// [`HiConfig::max_message`]: struct.HiConfig.html#method.max_message
+    pub fn max_message(mut self, len: usize) -> Self {
+        self.max_message = Some(len);
+        self
+    }
+
+    /// Hand `bytes` received from the sender over, returning the number of
+    /// messages they completed, each of which was then handed over to the
+    /// callback and must be acknowledged with [`ACK`].
+    ///
+    /// This function does not block: it only decodes what it is given.
+    ///
+    /// [`ACK`]: constant.ACK.html
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::TooLarge`] once a message exceeds the limit set
+    /// with [`max_message`]. The receiver is then left unusable, like the
+    /// connection.
+    ///
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    /// [`max_message`]: #method.max_message
+    pub fn feed_bytes(&mut self, mut bytes: &[u8]) -> Result<usize> {
+        let mut completed = 0;
+        if self.partial_len > 0 {
+            let n = bytes.len().min(8 - self.partial_len);
+            self.partial[self.partial_len..self.partial_len + n].copy_from_slice(&bytes[..n]);
+            self.partial_len += n;
+            bytes = &bytes[n..];
+            if self.partial_len < 8 {
+                return Ok(0);
+            }
+            self.partial_len = 0;
+            completed += self.push(self.partial)?;
+        }
+
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            let mut bits = [0; 8];
+            bits.copy_from_slice(word);
+            completed += self.push(bits)?;
+        }
+        let rest = words.remainder();
+        self.partial[..rest.len()].copy_from_slice(rest);
+        self.partial_len = rest.len();
+        Ok(completed)
+    }
+
+    /// Take a received word, returning 1 if it ended a message.
+    fn push(&mut self, word: [u8; 8]) -> Result<usize> {
+        if word == DELIMITER_NAN {
+            (self.on_message)(&self.words);
+            self.words.clear();
+            self.completed += 1;
+            return Ok(1);
+        }
+        if let Some(max) = self.max_message {
+            if self.words.len() == max {
+                return Err(Error::TooLarge(max));
+            }
+        }
+        self.words.push(f64::from_le_bytes(word));
+        Ok(0)
+    }
+
+    /// Number of messages completed so far.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// Number of values of the message being received, so far.
+    pub fn pending(&self) -> usize {
+        self.words.len()
+    }
+}
+
+impl<F> fmt::Debug for FeedReceiver<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FeedReceiver")
+            .field("pending", &self.words.len())
+            .field("partial_len", &self.partial_len)
+            .field("max_message", &self.max_message)
+            .field("completed", &self.completed)
+            .finish()
+    }
+}
@@ -0,0 +1,125 @@
+//! C bindings of [`FeedReceiver`], for C programs and the frameworks built
+//! on C, such as LabVIEW or EPICS, to receive *High Tension Messages* from
+//! their own event loop.
+//!
+//! This requires the `ffi` feature. Build the crate as a library C can link,
+//! for instance with `cargo rustc --release --features ffi --crate-type
+//! cdylib`, and declare the functions as follows:
+//!
+//! ```c
+//! #include <stddef.h>
+//! #include <stdint.h>
+//!
+//! typedef struct HiFeed HiFeed;
+//! typedef void (*hi_feed_callback)(const double *data, size_t len, void *user_data);
+//!
+//! HiFeed *hi_feed_new(hi_feed_callback on_message, void *user_data, size_t max_message);
+//! intptr_t hi_feed_bytes(HiFeed *feed, const uint8_t *bytes, size_t len);
+//! void hi_feed_free(HiFeed *feed);
+//! ```
+//!
+//! Every message completed by `hi_feed_bytes` is handed over to the callback,
+//! and must then be acknowledged by writing a newline back to the sender.
+//!
+//! [`FeedReceiver`]: ../struct.FeedReceiver.html
+
+use std::fmt;
+use std::os::raw::c_void;
+use std::slice;
+
+use crate::FeedReceiver;
+
+/// The callback receiving the messages completed by a [`HiFeed`]: the values
+/// of the message, their number, and the user data given to
+/// [`hi_feed_new`].
+///
+/// The values are only valid until the callback returns.
+///
+/// [`HiFeed`]: struct.HiFeed.html
+/// [`hi_feed_new`]: fn.hi_feed_new.html
+pub type HiFeedCallback = extern "C" fn(data: *const f64, len: usize, user_data: *mut c_void);
+
+/// A [`FeedReceiver`] behind an opaque pointer, created by [`hi_feed_new`]
+/// and freed by [`hi_feed_free`].
+///
+/// [`FeedReceiver`]: ../struct.FeedReceiver.html
+/// [`hi_feed_new`]: fn.hi_feed_new.html
+/// [`hi_feed_free`]: fn.hi_feed_free.html
+pub struct HiFeed {
+    receiver: FeedReceiver<Callback>,
+}
+
+type Callback = Box<dyn FnMut(&[f64])>;
+
+impl fmt::Debug for HiFeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HiFeed")
+            .field("receiver", &self.receiver)
+            .finish()
+    }
+}
+
+/// Create a receiver handing every message over to `on_message`, along with
+/// `user_data`, and rejecting the messages of more than `max_message` values,
+/// unless it is 0.
+///
+/// The receiver must be freed with [`hi_feed_free`].
+///
+/// [`hi_feed_free`]: fn.hi_feed_free.html
+#[no_mangle]
+pub extern "C" fn hi_feed_new(
+    on_message: HiFeedCallback,
+    user_data: *mut c_void,
+    max_message: usize,
+) -> *mut HiFeed {
+    let callback = move |data: &[f64]| on_message(data.as_ptr(), data.len(), user_data);
+    let mut receiver = FeedReceiver::new(Box::new(callback) as Callback);
+    if max_message > 0 {
+        receiver = receiver.max_message(max_message);
+    }
+    Box::into_raw(Box::new(HiFeed { receiver }))
+}
+
+/// Hand the `len` bytes at `bytes` received from the sender over to `feed`,
+/// like [`FeedReceiver::feed_bytes`], returning the number of messages they
+/// completed, or -1 once a message exceeds the limit of the receiver, which
+/// is then left unusable.
+///
+/// # Safety
+///
+/// `feed` must come from [`hi_feed_new`] and not be freed yet, and `bytes`
+/// must point to `len` readable bytes, or may be null if `len` is 0.
+///
+/// [`FeedReceiver::feed_bytes`]: ../struct.FeedReceiver.html#method.feed_bytes
+/// [`hi_feed_new`]: fn.hi_feed_new.html
+#[no_mangle]
+pub unsafe extern "C" fn hi_feed_bytes(feed: *mut HiFeed, bytes: *const u8, len: usize) -> isize {
+    // SAFETY: guaranteed by the caller
+    let feed = unsafe { &mut *feed };
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        // SAFETY: guaranteed by the caller
+        unsafe { slice::from_raw_parts(bytes, len) }
+    };
+    match feed.receiver.feed_bytes(bytes) {
+        Ok(completed) => completed as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Free a receiver created by [`hi_feed_new`]. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `feed` must come from [`hi_feed_new`], or be null, and not be used
+/// afterwards.
+///
+/// [`hi_feed_new`]: fn.hi_feed_new.html
+#[no_mangle]
+pub unsafe extern "C" fn hi_feed_free(feed: *mut HiFeed) {
+    if !feed.is_null() {
+        // SAFETY: guaranteed by the caller
+        drop(unsafe { Box::from_raw(feed) });
+    }
+}
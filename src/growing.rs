@@ -0,0 +1,253 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::{read_chunks, Error, Result};
+
+#[derive(Default)]
+struct Progress {
+    segments: Vec<Arc<[f64]>>,
+    received: usize,
+    started: bool,
+    complete: bool,
+    failed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    progress: Mutex<Progress>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, Progress>) -> MutexGuard<'a, Progress> {
+        self.changed.wait(guard).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn reception_failed() -> Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "the reception failed").into()
+}
+
+/// A *High Tension Message* shared while it is being received, so that other
+/// threads can process the values already received of a huge array while the
+/// rest is still arriving.
+///
+/// One thread receives the message with [`receive`], while the others follow
+/// its progress: [`received`] is the number of values received so far, the
+/// watermark below which they can be read, and [`segments`] hands them over
+/// in order as they arrive, about 1 MB at a time. Segments are shared, not
+/// copied, and stay valid as long as they are held.
+///
+/// The plain protocol is spoken, like [`hiread`] does. `GrowingMessage` is a
+/// cheap handle: clones refer to the same message.
+///
+/// [`receive`]: #method.receive
+/// [`received`]: #method.received
+/// [`segments`]: #method.segments
+/// [`hiread`]: fn.hiread.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hidelimiter, hiwrite, pipe, GrowingMessage};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let producer = thread::spawn(move || -> hi_tension::Result<()> {
+///     hiwrite(&mut client, &vec![1.0; 10_000_000])?;
+///     hidelimiter(&mut client)
+/// });
+///
+/// let message = GrowingMessage::new();
+/// let worker = {
+///     let message = message.clone();
+///     thread::spawn(move || -> hi_tension::Result<f64> {
+///         let mut sum = 0.0;
+///         for segment in message.segments() {
+///             sum += segment?.iter().sum::<f64>();
+///         }
+///         Ok(sum)
+///     })
+/// };
+///
+/// assert_eq!(message.receive(&mut server)?, 10_000_000);
+/// assert_eq!(worker.join().unwrap()?, 10_000_000.0);
+/// producer.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct GrowingMessage {
+    shared: Arc<Shared>,
+}
+
+impl GrowingMessage {
+    /// Prepare to receive a message.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive the message from the `stream`, making every segment
+    /// available as soon as it arrived, and return its number of values.
+    ///
+    /// This function is blocking. The message is acknowledged once whole.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`hiread`]. The threads following the message then get an
+    /// error too, once they reach what was received.
+    ///
+    /// [`hiread`]: fn.hiread.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if this message was already received, even partially: each
+    /// message needs a new `GrowingMessage`.
+    pub fn receive<S: Read + Write + ?Sized>(&self, stream: &mut S) -> Result<usize> {
+        {
+            let mut progress = self.shared.lock();
+            assert!(!progress.started, "message already received");
+            progress.started = true;
+        }
+        let result = read_chunks(stream, |chunk| {
+            if !chunk.is_empty() {
+                let mut progress = self.shared.lock();
+                progress.segments.push(chunk.into());
+                progress.received += chunk.len();
+                self.shared.changed.notify_all();
+            }
+            Ok(())
+        });
+        let mut progress = self.shared.lock();
+        match result {
+            Ok(_) => progress.complete = true,
+            Err(_) => progress.failed = true,
+        }
+        self.shared.changed.notify_all();
+        result
+    }
+
+    /// Number of values received so far.
+    pub fn received(&self) -> usize {
+        self.shared.lock().received
+    }
+
+    /// Whether the message was received whole.
+    pub fn is_complete(&self) -> bool {
+        self.shared.lock().complete
+    }
+
+    /// Wait until at least `len` values are received, or the whole message
+    /// if shorter, and return the number of values received.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the reception failed before.
+    pub fn wait(&self, len: usize) -> Result<usize> {
+        let mut progress = self.shared.lock();
+        loop {
+            if progress.received >= len || progress.complete {
+                return Ok(progress.received);
+            }
+            if progress.failed {
+                return Err(reception_failed());
+            }
+            progress = self.shared.wait(progress);
+        }
+    }
+
+    /// Iterate over the segments of the message, in order, waiting for each
+    /// of them to arrive.
+    ///
+    /// The iteration ends once the message is received whole, and fails once
+    /// if the reception failed.
+    pub fn segments(&self) -> Segments {
+        Segments {
+            shared: Arc::clone(&self.shared),
+            next: 0,
+            done: false,
+        }
+    }
+
+    /// Wait for the whole message, and collect its values.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the reception failed.
+    pub fn to_vec(&self) -> Result<Vec<f64>> {
+        let mut data = Vec::with_capacity(self.received());
+        for segment in self.segments() {
+            data.extend_from_slice(&segment?);
+        }
+        Ok(data)
+    }
+}
+
+impl fmt::Debug for GrowingMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let progress = self.shared.lock();
+        f.debug_struct("GrowingMessage")
+            .field("received", &progress.received)
+            .field("complete", &progress.complete)
+            .field("failed", &progress.failed)
+            .finish()
+    }
+}
+
+/// The segments of a [`GrowingMessage`], in order, as they arrive.
+///
+/// This struct is created by [`GrowingMessage::segments`]. Iterating is
+/// blocking.
+///
+/// [`GrowingMessage`]: struct.GrowingMessage.html
+/// [`GrowingMessage::segments`]: struct.GrowingMessage.html#method.segments
+pub struct Segments {
+    shared: Arc<Shared>,
+    next: usize,
+    done: bool,
+}
+
+impl Iterator for Segments {
+    type Item = Result<Arc<[f64]>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut progress = self.shared.lock();
+        loop {
+            if let Some(segment) = progress.segments.get(self.next) {
+                self.next += 1;
+                return Some(Ok(Arc::clone(segment)));
+            }
+            if progress.complete {
+                self.done = true;
+                return None;
+            }
+            if progress.failed {
+                self.done = true;
+                return Some(Err(reception_failed()));
+            }
+            progress = self.shared.wait(progress);
+        }
+    }
+}
+
+impl fmt::Debug for Segments {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Segments")
+            .field("next", &self.next)
+            .field("done", &self.done)
+            .finish()
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::thread;
+
+use crate::{hidelimiter, hiwrite, read_exact_into, Result};
+
+#[derive(Debug)]
+struct Neighbor<S> {
+    rank: usize,
+    stream: S,
+    send: Range<usize>,
+    recv: Range<usize>,
+    halo: Vec<f64>,
+}
+
+/// The exchange of boundary values between the subdomains of a decomposed
+/// domain, as done at every step of a stencil computation.
+///
+/// Each process owns a subdomain, laid out in a local array along with ghost
+/// cells holding copies of the boundaries of its neighbors. An exchange sends
+/// the boundary slices of the local array to every neighbor, and receives
+/// theirs into the ghost slices.
+///
+/// Transfers with different neighbors overlap: each one runs on a thread of
+/// its own. With a given neighbor, the process of lower rank sends first and
+/// the other one receives first, so that both never wait for each other. Ranks
+/// only need to be unique among neighbors, and agreed on by both sides.
+///
+/// # Examples
+///
+/// A 1D domain split between 3 processes, with ghost cells on both ends of
+/// the local array. Process 1, in the middle:
+///
+/// ```no_run
+/// use hi_tension::HaloExchange;
+/// use std::net::{TcpListener, TcpStream};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let left = TcpStream::connect("10.0.0.1:34567")?;
+/// let (right, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+///
+/// let n = 1000;
+/// let mut data = vec![0.0; n + 2];
+/// let mut halo = HaloExchange::new(1)
+///     .neighbor(0, left, 1..2, 0..1)
+///     .neighbor(2, right, n..n + 1, n + 1..n + 2);
+///
+/// for step in 0..100 {
+///     // Update data[1..n + 1] from data[0..n + 2]
+///     halo.exchange(&mut data)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HaloExchange<S> {
+    rank: usize,
+    neighbors: Vec<Neighbor<S>>,
+}
+
+impl<S: Read + Write + Send> HaloExchange<S> {
+    /// Create an exchange without neighbors, for the process of rank `rank`.
+    pub fn new(rank: usize) -> Self {
+        HaloExchange {
+            rank,
+            neighbors: Vec::new(),
+        }
+    }
+
+    /// Add the neighbor of rank `rank`, at the other end of the `stream`. The
+    /// values at indices `send` of the local array are sent to it, and the
+    /// ones it sends are stored at indices `recv`.
+    ///
+    /// Both ranges are checked against the local array by [`exchange`].
+    ///
+    /// [`exchange`]: #method.exchange
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rank` is the rank of this process, or of another neighbor.
+    pub fn neighbor(
+        mut self,
+        rank: usize,
+        stream: S,
+        send: Range<usize>,
+        recv: Range<usize>,
+    ) -> Self {
+        assert_ne!(rank, self.rank, "neighbors have a rank of their own");
+        assert!(
+            self.neighbors.iter().all(|neighbor| neighbor.rank != rank),
+            "neighbor of rank {} added twice",
+            rank
+        );
+        let halo = vec![0.0; recv.len()];
+        self.neighbors.push(Neighbor {
+            rank,
+            stream,
+            send,
+            recv,
+            halo,
+        });
+        self
+    }
+
+    /// Send the boundaries of `data` to every neighbor, and receive theirs
+    /// into it.
+    ///
+    /// This function is blocking, until every neighbor called `exchange` too.
+    /// The values received are collected in buffers of their own, reused from
+    /// one exchange to the next, then copied into `data` once every transfer
+    /// succeeded.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the first error of a transfer, leaving `data` untouched. A
+    /// halo which is not of the size expected fails with [`Error::Framing`].
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    ///
+    /// # Panics
+    ///
+    /// Panics if a range of a neighbor is out of the bounds of `data`.
+    pub fn exchange(&mut self, data: &mut [f64]) -> Result<()> {
+        let rank = self.rank;
+        let shared = &*data;
+        thread::scope(|scope| {
+            let transfers: Vec<_> = self
+                .neighbors
+                .iter_mut()
+                .map(|neighbor| {
+                    let boundary = &shared[neighbor.send.clone()];
+                    scope.spawn(move || neighbor.transfer(rank, boundary))
+                })
+                .collect();
+            transfers
+                .into_iter()
+                .try_for_each(|transfer| transfer.join().unwrap())
+        })?;
+
+        for neighbor in &self.neighbors {
+            data[neighbor.recv.clone()].copy_from_slice(&neighbor.halo);
+        }
+        Ok(())
+    }
+
+    /// Unwrap this `HaloExchange`, returning the streams to the neighbors in
+    /// the order they were added.
+    pub fn into_streams(self) -> Vec<S> {
+        self.neighbors
+            .into_iter()
+            .map(|neighbor| neighbor.stream)
+            .collect()
+    }
+}
+
+impl<S: Read + Write> Neighbor<S> {
+    /// Send `boundary` and receive the halo, in the order set by the ranks.
+    fn transfer(&mut self, rank: usize, boundary: &[f64]) -> Result<()> {
+        if rank < self.rank {
+            self.send(boundary)?;
+            read_exact_into(&mut self.stream, &mut self.halo)
+        } else {
+            read_exact_into(&mut self.stream, &mut self.halo)?;
+            self.send(boundary)
+        }
+    }
+
+    fn send(&mut self, boundary: &[f64]) -> Result<()> {
+        hiwrite(&mut self.stream, boundary)?;
+        hidelimiter(&mut self.stream)
+    }
+}
@@ -0,0 +1,144 @@
+//! Text handshake exchanged when a `HiStream` is opened.
+//!
+//! Both the request and the reply are made of a first line, followed by
+//! `key value` lines, and ended by an empty line:
+//!
+//! ```text
+//! hi-tension 1
+//! token s3cr3t
+//!
+//! ```
+//!
+//! The server replies with `ok` (and its own fields), or with `error <reason>`
+//! before closing the connection.
+
+use std::io::{Read, Write};
+
+use crate::{Error, Result};
+
+pub(crate) const GREETING: &str = "hi-tension 1";
+pub(crate) const UNAUTHORIZED: &str = "unauthorized";
+const MAX_LINE: usize = 4096;
+const MAX_FIELDS: usize = 1024;
+
+/// Most bytes a handshake message may take: its first line, its fields and
+/// the empty line ending it.
+pub(crate) const MAX_MESSAGE: usize = (MAX_LINE + 1) * (MAX_FIELDS + 2);
+
+/// The `key value` lines of a handshake message.
+#[derive(Debug, Default)]
+pub(crate) struct Fields(Vec<(String, String)>);
+
+impl Fields {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&mut self, key: &str, value: impl Into<String>) {
+        self.0.push((key.to_owned(), value.into()));
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the values of every line with `key`, for fields which may repeat.
+    pub(crate) fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Read a newline terminated line, byte by byte so that nothing past the
+/// newline is consumed from the stream.
+pub(crate) fn read_line<R: Read>(stream: &mut R) -> Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let mut byte = [0];
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        if line.len() == MAX_LINE {
+            return Err(Error::Handshake("line too long".into()));
+        }
+        line.push(byte[0]);
+    }
+    String::from_utf8(line).map_err(|_| Error::Handshake("invalid UTF-8".into()))
+}
+
+pub(crate) fn write_message<W: Write>(stream: &mut W, first: &str, fields: &Fields) -> Result<()> {
+    let mut message = format!("{}\n", first);
+    for (key, value) in &fields.0 {
+        message.push_str(key);
+        message.push(' ');
+        message.push_str(value);
+        message.push('\n');
+    }
+    message.push('\n');
+    stream.write_all(message.as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+pub(crate) fn read_message<R: Read>(stream: &mut R) -> Result<(String, Fields)> {
+    let first = read_line(stream)?;
+    let mut fields = Fields::new();
+    loop {
+        let line = read_line(stream)?;
+        if line.is_empty() {
+            break;
+        }
+        if fields.0.len() == MAX_FIELDS {
+            return Err(Error::Handshake("too many fields".into()));
+        }
+        let mut split = line.splitn(2, ' ');
+        let key = split.next().unwrap_or_default();
+        fields.push(key, split.next().unwrap_or_default());
+    }
+    Ok((first, fields))
+}
+
+/// Send the client side request and wait for the reply of the server.
+pub(crate) fn client<S: Read + Write>(stream: &mut S, request: &Fields) -> Result<Fields> {
+    write_message(stream, GREETING, request)?;
+    let (status, reply) = read_message(stream)?;
+    match status.as_str() {
+        "ok" => Ok(reply),
+        _ => Err(refusal(&status)),
+    }
+}
+
+/// Read the request of a client, without replying yet.
+pub(crate) fn read_request<R: Read>(stream: &mut R) -> Result<Fields> {
+    let (greeting, request) = read_message(stream)?;
+    if greeting != GREETING {
+        return Err(Error::Handshake(format!(
+            "unsupported greeting {:?}",
+            greeting
+        )));
+    }
+    Ok(request)
+}
+
+pub(crate) fn accept<W: Write>(stream: &mut W, reply: &Fields) -> Result<()> {
+    write_message(stream, "ok", reply)
+}
+
+/// Refuse the request of a client for `reason`.
+pub(crate) fn refuse<W: Write>(stream: &mut W, reason: &str) -> Result<()> {
+    write_message(stream, &format!("error {}", reason), &Fields::new())
+}
+
+fn refusal(status: &str) -> Error {
+    match status.strip_prefix("error ") {
+        Some(UNAUTHORIZED) => Error::AuthFailed,
+        Some(reason) => Error::Handshake(format!("refused by peer: {}", reason)),
+        None => Error::Handshake(format!("unexpected reply {:?}", status)),
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::net::TcpStream;
+use std::sync::mpsc::SyncSender;
+
+use crate::handshake::Fields;
+use crate::{HiStream, Result};
+
+/// Who is at one end of a connection: a name telling this peer apart, such
+/// as `archiver-2`, and a role telling what it does, such as `archiver`.
+///
+/// An identity is given with [`HiConfig::identity`], and announced during
+/// the handshake. It is not authenticated: use [`HiConfig::token`] to keep
+/// unknown peers away.
+///
+/// [`HiConfig::identity`]: struct.HiConfig.html#method.identity
+/// [`HiConfig::token`]: struct.HiConfig.html#method.token
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Identity {
+    name: String,
+    role: String,
+}
+
+impl Identity {
+    /// Create the identity of a peer called `name`, playing `role`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` or `role` is empty, or contains a newline.
+    pub fn new(name: impl Into<String>, role: impl Into<String>) -> Self {
+        let (name, role) = (name.into(), role.into());
+        for field in [&name, &role] {
+            assert!(
+                !field.is_empty() && !field.contains('\n'),
+                "invalid identity field {:?}",
+                field
+            );
+        }
+        Identity { name, role }
+    }
+
+    /// The name of the peer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The role of the peer.
+    pub fn role(&self) -> &str {
+        &self.role
+    }
+
+    pub(crate) fn to_fields(&self, fields: &mut Fields) {
+        fields.push("name", self.name.as_str());
+        fields.push("role", self.role.as_str());
+    }
+
+    pub(crate) fn from_fields(fields: &Fields) -> Option<Self> {
+        match (fields.get("name"), fields.get("role")) {
+            (Some(name), Some(role)) if !name.is_empty() && !role.is_empty() => {
+                Some(Identity::new(name, role))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.role)
+    }
+}
+
+pub(crate) type Handler<'a> =
+    Box<dyn Fn(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()> + Sync + 'a>;
+
+enum Route<'a> {
+    Handle(Handler<'a>),
+    Forward(SyncSender<HiStream<TcpStream>>),
+}
+
+/// Route the clients of a [`HiServer`] by their [`Identity`], so that one
+/// port serves peers of different kinds, each with its own handler.
+///
+/// A route is keyed by a name or by a role: a client goes to the route of
+/// its name if there is one, and to the route of its role otherwise.
+/// Clients announce their identity with [`HiConfig::identity`]; the ones
+/// matching no route go to the fallback, if any, and are disconnected
+/// otherwise. Routers are used by [`HiServer::serve_routed`].
+///
+/// [`HiServer`]: struct.HiServer.html
+/// [`Identity`]: struct.Identity.html
+/// [`HiConfig::identity`]: struct.HiConfig.html#method.identity
+/// [`HiServer::serve_routed`]: struct.HiServer.html#method.serve_routed
+#[derive(Default)]
+pub struct Router<'a> {
+    routes: Vec<(String, Route<'a>)>,
+    fallback: Option<Handler<'a>>,
+}
+
+impl<'a> Router<'a> {
+    /// Create a router without any route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `handler` for every message of the clients named or playing
+    /// `key`, along with the stream it came from to answer on, like
+    /// [`HiServer::serve_threaded`] does.
+    ///
+    /// [`HiServer::serve_threaded`]: struct.HiServer.html#method.serve_threaded
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` already has a route.
+    pub fn on<F>(self, key: &str, handler: F) -> Self
+    where
+        F: Fn(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()> + Sync + 'a,
+    {
+        self.route(key, Route::Handle(Box::new(handler)))
+    }
+
+    /// Hand the connections of the clients named or playing `key` over to a
+    /// channel, for a consumer on another thread to serve them as it likes.
+    ///
+    /// The server blocks while the channel is full. Once its receiver is
+    /// dropped, these clients are disconnected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` already has a route.
+    pub fn forward(self, key: &str, sender: SyncSender<HiStream<TcpStream>>) -> Self {
+        self.route(key, Route::Forward(sender))
+    }
+
+    /// Call `handler` for every message of the clients matching no route,
+    /// including the ones without an identity.
+    pub fn fallback<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()> + Sync + 'a,
+    {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    fn route(mut self, key: &str, route: Route<'a>) -> Self {
+        assert!(
+            self.routes.iter().all(|(other, _)| other != key),
+            "duplicate route for {:?}",
+            key
+        );
+        self.routes.push((key.to_owned(), route));
+        self
+    }
+
+    /// Serve the connection of a client according to its identity: returns
+    /// the handler of its messages, or `None` once the connection was handed
+    /// over or dropped.
+    pub(crate) fn dispatch(
+        &self,
+        stream: HiStream<TcpStream>,
+    ) -> Option<(HiStream<TcpStream>, &Handler<'a>)> {
+        let find = |key: &str| self.routes.iter().find(|(other, _)| other == key);
+        let route = stream
+            .peer_identity()
+            .and_then(|identity| find(identity.name()).or_else(|| find(identity.role())));
+        match route {
+            Some((_, Route::Handle(handler))) => Some((stream, handler)),
+            Some((_, Route::Forward(sender))) => {
+                let _ = sender.send(stream);
+                None
+            }
+            None => self.fallback.as_ref().map(|handler| (stream, handler)),
+        }
+    }
+}
+
+impl fmt::Debug for Router<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys: Vec<_> = self.routes.iter().map(|(key, _)| key).collect();
+        f.debug_struct("Router")
+            .field("routes", &keys)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::pod::{bytes_of, bytes_of_mut};
+use crate::Result;
+
+const ENTRY_SIZE: usize = 32;
+
+/// Whether a journaled message was sent or received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The message was sent to the peer.
+    Sent,
+    /// The message was received from the peer.
+    Received,
+}
+
+/// The index entry of a message in a [`Journal`].
+///
+/// [`Journal`]: struct.Journal.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Whether the message was sent or received.
+    pub direction: Direction,
+    /// Number of floats in the message.
+    pub len: usize,
+    /// When the message was completely sent or received.
+    pub timestamp: SystemTime,
+    offset: u64,
+}
+
+impl JournalEntry {
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let nanos = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let direction = match self.direction {
+            Direction::Sent => 0u64,
+            Direction::Received => 1,
+        };
+
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8..16].copy_from_slice(&(self.len as u64).to_le_bytes());
+        bytes[16..24].copy_from_slice(&nanos.to_le_bytes());
+        bytes[24..32].copy_from_slice(&direction.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let word = |i: usize| {
+            let mut b = [0; 8];
+            b.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+            u64::from_le_bytes(b)
+        };
+        JournalEntry {
+            offset: word(0),
+            len: word(1) as usize,
+            timestamp: UNIX_EPOCH + Duration::from_nanos(word(2)),
+            direction: match word(3) {
+                0 => Direction::Sent,
+                _ => Direction::Received,
+            },
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.offset + self.len as u64 * 8
+    }
+}
+
+/// An indexed on-disk log of *High Tension Messages*.
+///
+/// Once set on a [`HiStream`] with [`set_journal`], every message sent or
+/// received through the stream is archived in the journal. Recorded messages
+/// can be read back, or sent again with [`HiStream::replay`], e.g. to
+/// reproduce an analysis bug against recorded instrument data.
+///
+/// A journal is a directory holding two files: `data`, the floats of all the
+/// messages one after the other, and `index`, a fixed size entry per message.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`set_journal`]: struct.HiStream.html#method.set_journal
+/// [`HiStream::replay`]: struct.HiStream.html#method.replay
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiStream, Journal};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+/// let mut stream = HiStream::client(tcp, HiConfig::new())?;
+/// stream.set_journal(Journal::open("run-42")?);
+///
+/// let data = stream.read()?;
+///
+/// // Much later, send the first ten recorded messages again
+/// stream.replay(0..10)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Journal {
+    data: File,
+    index: File,
+    entries: Vec<JournalEntry>,
+    end: u64,
+    pending: usize,
+}
+
+impl Journal {
+    /// Open the journal stored in the directory `dir`, creating it if needed.
+    ///
+    /// New messages are appended after the ones already recorded.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let open = |name| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(dir.join(name))
+        };
+        let data = open("data")?;
+        let mut index = open("index")?;
+
+        let mut bytes = Vec::new();
+        index.read_to_end(&mut bytes)?;
+        let entries: Vec<_> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(JournalEntry::from_bytes)
+            .collect();
+
+        // Drop what an interrupted run may have left after the last entry
+        let end = entries.last().map_or(0, JournalEntry::end);
+        data.set_len(end)?;
+        index.set_len((entries.len() * ENTRY_SIZE) as u64)?;
+        index.seek(SeekFrom::End(0))?;
+
+        Ok(Journal {
+            data,
+            index,
+            entries,
+            end,
+            pending: 0,
+        })
+    }
+
+    /// Number of messages in the journal.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the journal has no message.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The index entries of all the messages, in order.
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Read back the message at position `i`, or `None` if there is no such
+    /// message.
+    pub fn read(&self, i: usize) -> Result<Option<Vec<f64>>> {
+        let entry = match self.entries.get(i) {
+            Some(entry) => *entry,
+            None => return Ok(None),
+        };
+        let mut data = vec![0.0; entry.len];
+        let mut file = &self.data;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        file.read_exact(bytes_of_mut(&mut data))?;
+        Ok(Some(data))
+    }
+
+    /// Append `data` to the message being recorded.
+    pub(crate) fn write(&mut self, data: &[f64]) -> Result<()> {
+        self.data
+            .seek(SeekFrom::Start(self.end + self.pending as u64 * 8))?;
+        self.data.write_all(bytes_of(data))?;
+        self.pending += data.len();
+        Ok(())
+    }
+
+    /// Close the message being recorded, indexing it.
+    pub(crate) fn commit(&mut self, direction: Direction) -> Result<()> {
+        let entry = JournalEntry {
+            direction,
+            len: self.pending,
+            timestamp: SystemTime::now(),
+            offset: self.end,
+        };
+        self.index.write_all(&entry.to_bytes())?;
+        self.entries.push(entry);
+        self.end = entry.end();
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Forget the message being recorded, which will not be committed.
+    pub(crate) fn discard(&mut self) {
+        self.pending = 0;
+    }
+}
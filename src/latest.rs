@@ -0,0 +1,111 @@
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::{HiStream, Result};
+
+impl<S: Read + Write> HiStream<S> {
+    /// Read the most recent message available, skipping the older ones, for
+    /// consumers only caring about the freshest frame.
+    ///
+    /// This function is blocking, until a message arrives. Messages are
+    /// acknowledged one by one, so the only messages available past the
+    /// first one are the next ones of its batch, with
+    /// [`HiConfig::batching`]: all of them are read, and the last one is
+    /// returned.
+    ///
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`read`].
+    ///
+    /// [`read`]: #method.read
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, HiConfig, HiStream};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+    ///     let mut stream = HiStream::server(server, HiConfig::new().batching(3))?;
+    ///     stream.read_latest()
+    /// });
+    ///
+    /// let mut stream = HiStream::client(client, HiConfig::new().batching(3))?;
+    /// for frame in 0..3 {
+    ///     stream.send(&[f64::from(frame)])?;
+    /// }
+    /// assert_eq!(consumer.join().unwrap()?, [2.0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_latest(&mut self) -> Result<Vec<f64>> {
+        let mut data = self.read()?;
+        while self.has_unbatched() {
+            data = self.read()?;
+        }
+        Ok(data)
+    }
+
+    /// Read the most recent message like [`read_latest`], skipping the
+    /// messages sent more than `ttl` ago.
+    ///
+    /// This function is blocking, until a fresh message arrives. The age of
+    /// a message is measured from its [`Timestamp`], on the wall clock of
+    /// both peers: their clocks must be synchronized well below `ttl`, see
+    /// [`sync_clock`]. Messages timestamped in the future are fresh.
+    ///
+    /// [`read_latest`]: #method.read_latest
+    /// [`Timestamp`]: struct.Timestamp.html
+    /// [`sync_clock`]: #method.sync_clock
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration does not set [`HiConfig::timestamps`].
+    ///
+    /// [`HiConfig::timestamps`]: struct.HiConfig.html#method.timestamps
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`read`].
+    ///
+    /// [`read`]: #method.read
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (tcp, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+    /// let mut stream = HiStream::server(tcp, HiConfig::new().timestamps())?;
+    ///
+    /// loop {
+    ///     let frame = stream.read_fresh(Duration::from_millis(100))?;
+    ///     println!("live frame of {} floats", frame.len());
+    /// }
+    /// # }
+    /// ```
+    pub fn read_fresh(&mut self, ttl: Duration) -> Result<Vec<f64>> {
+        assert!(
+            self.config().timestamps,
+            "stale messages can only be told apart with timestamps"
+        );
+        loop {
+            let data = self.read_latest()?;
+            let fresh = self.last_timestamp().is_none_or(|stamp| {
+                SystemTime::now()
+                    .duration_since(stamp.wall)
+                    .map_or(true, |age| age <= ttl)
+            });
+            if fresh {
+                return Ok(data);
+            }
+        }
+    }
+}
@@ -5,12 +5,13 @@
 //!
 //! # Usage
 //!
-//! ```rust
+//! ```no_run
 //! use hi_tension::{hiread, hiwrite, hidelimiter};
 //!
+//! # fn main() -> hi_tension::Result<()> {
 //! // Here we use a TcpStream but anything implementing Read and Write will do
 //! use std::net::TcpStream;
-//! let mut stream = TcpStream::connect("127.0.0.1:34254");
+//! let mut stream = TcpStream::connect("127.0.0.1:34254")?;
 //! // Of course, here you need a server on the other side. Please look at the
 //! // examples to get a testing one.
 //!
@@ -23,19 +24,28 @@
 //! // Sending data over the socket is done through calling hiwrite, and then
 //! // hidelimiter to signal your array is done.
 //! hiwrite(&mut stream, &data)?;
-//! hidelimiter(&mut stream);
+//! hidelimiter(&mut stream)?;
 //!
 //! // You may send your data in multible packets
 //! hiwrite(&mut stream, &data[..500_000])?;
 //! hiwrite(&mut stream, &data[500_000..])?;
-//! hidelimiter(&mut stream);
+//! hidelimiter(&mut stream)?;
 //! // This is useful for example if you are calculating your data while
 //! // transferring it.
 //!
 //! // To receive an array, simply call hiread
 //! let vec = hiread(&mut stream)?;
+//! # Ok(())
+//! # }
 //! ```
 //!
+//! Options on top of the plain protocol, such as message authentication, are
+//! available through [`HiStream`] and [`HiConfig`].
+//!
+//! [`HiStream`]: struct.HiStream.html
+//! [`HiConfig`]: struct.HiConfig.html
+//! [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+//!
 //! # Rough protocol description
 //!
 //! The `hi-tension` protocol accepts 2 kinds of messages:
@@ -43,13 +53,13 @@
 //!   procedure calls defined by the client application.
 //! - *High Tension Messages*, for fast data transfert.
 //!
-//! Currently, this library only implements *High Tension Messages*, since *Simple
-//! Text Messages* are easily done through `writeln!` calls, but that may change in
-//! the future.
+//! The free functions only implement *High Tension Messages*, since *Simple Text
+//! Messages* are easily done through `writeln!` calls. To mix both kinds on the
+//! same connection, see [`HiConfig::typed_messages`].
 //!
 //! *High Tension Messages* are packets of `f64` (double precision floating points),
-//! separated by the magic NaN value `0x7ff800100400a05b`. A NaN value was chosen
-//! because:
+//! separated by the magic NaN value `0x7ff800100400a05b`. A packet may be empty,
+//! leaving the delimiter alone. A NaN value was chosen because:
 //! 1. They are not supposed to appear in valid calculations.
 //! 2. In the case one appears there is a `1/16777214` chance that it is exactly
 //!    `0x7ff800100400a05b`, which is less than a probability of 0.000006 %.
@@ -63,22 +73,209 @@
 //! sent by the receiver, to ensure succesfull reception.
 //!
 //! *Simple Text Messages* are newline `\n` separated UTF-8 packets.
+//!
+//! ## Handshake
+//!
+//! Connections opened through [`HiStream::client`] and [`HiStream::server`]
+//! start with a text handshake. The client sends a `hi-tension 1` line, then
+//! optional `key value` lines (such as `token <access token>`, `name <name>`
+//! and `role <role>` to announce an [`Identity`], `session <connection ID>`
+//! to resume a session, `layout column-major` to get matrices transposed,
+//! `delimiter <16 hex digits>` to pick the delimiter of the messages it
+//! receives, or `schema <name> <dtype> <shape> <units>` once per [`Schema`]
+//! it knows), and an empty line. The server answers
+//! the same way with an `ok` first line, or with an `error <reason>` line
+//! before closing the connection.
+//!
+//! [`HiStream::client`]: struct.HiStream.html#method.client
+//! [`HiStream::server`]: struct.HiStream.html#method.server
+//! [`Identity`]: struct.Identity.html
+//! [`Schema`]: struct.Schema.html
 
-use std::io::{Read, Result, Write};
+mod ack;
+mod adaptive;
+mod aligned;
+mod backfill;
+mod balance;
+mod batch;
+mod calibrate;
+mod channel;
+mod clock;
+mod collective;
+mod config;
+mod conflate;
+mod connect;
+#[cfg(feature = "csv")]
+mod csv;
+mod dataset;
+mod diff;
+mod dispatch;
+#[cfg(feature = "epics")]
+mod epics;
+mod error;
+mod ext;
+mod failover;
+mod fed;
+mod feed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod growing;
+mod halo;
+mod handshake;
+mod hmac;
+mod identity;
+mod journal;
+mod latest;
+#[cfg(target_os = "linux")]
+mod link;
+mod matrix;
+#[cfg(feature = "mdns")]
+mod mdns;
+mod memory;
+mod message;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "npy")]
+mod npy;
+mod object;
+mod parallel;
+#[cfg(feature = "parquet")]
+mod parquet;
+pub mod pattern;
+mod pause;
+mod pipe;
+mod pipeline;
+mod pod;
+mod pool;
+#[cfg(feature = "proxy")]
+mod proxy;
+mod quantize;
+mod quota;
+mod recorder;
+mod reduce;
+mod relay;
+mod reorder;
+mod request;
+mod retry;
+mod schema;
+mod server;
+mod session;
+mod shared;
+mod sparse;
+mod spool;
+#[cfg(feature = "ssh")]
+mod ssh;
+mod state;
+mod stream;
+mod tee;
+pub mod testing;
+mod transport;
+mod typed;
+mod validate;
+mod verified;
+mod window;
+
+pub use ack::AckStatus;
+pub use aligned::{hiread_aligned, AlignedBuf};
+pub use backfill::{request_resend, JournaledSender, RESEND_PREFIX};
+pub use balance::{Balance, LoadBalancer};
+pub use batch::{hiread_batch, hiwrite_batch, RecordBatch};
+pub use calibrate::hicalibrate;
+pub use channel::{spawn_receiver, spawn_sender};
+pub use clock::{ClockOffset, Timestamp};
+pub use collective::{hibarrier, hibarrier_wait, hireduce, ReduceOp};
+pub use config::HiConfig;
+pub use conflate::{spawn_conflating_sender, ConflatingSender};
+pub use connect::connect_dual_stack;
+#[cfg(feature = "csv")]
+pub use csv::CsvSink;
+pub use dataset::{hiread_dataset, hiwrite_dataset};
+pub use diff::{DiffReceiver, DiffSender};
+pub use dispatch::Dispatcher;
+#[cfg(feature = "epics")]
+pub use epics::{hiexpose_pv, hiforward_pvs, put_pv, PvMonitor};
+pub use error::{Error, Result};
+pub use ext::HiExt;
+pub use failover::{FailoverPolicy, FailoverStream};
+pub use feed::{FeedReceiver, ACK};
+pub use growing::{GrowingMessage, Segments};
+pub use halo::HaloExchange;
+pub use identity::{Identity, Router};
+pub use journal::{Direction, Journal, JournalEntry};
+#[cfg(target_os = "linux")]
+pub use link::LinkInfo;
+pub use matrix::{hiread_matrix, hiwrite_columns, hiwrite_matrix, Layout, Matrix};
+#[cfg(feature = "mdns")]
+pub use mdns::{discover, Advertisement};
+pub use memory::prefault;
+#[cfg(unix)]
+pub use memory::{lock_memory, MemoryLock};
+pub use message::{ArrayRef, Message, MessageRef};
+#[cfg(feature = "metrics")]
+pub use metrics::{ConnectionMetrics, Metrics};
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttSummaries, SummaryField};
+#[cfg(feature = "npy")]
+pub use npy::{hiread_to_npy, hiwrite_npy};
+pub use object::{hiread_object, hiwrite_object, Bytes, Codec};
+pub use parallel::{hiread_parallel, hiwrite_parallel};
+#[cfg(feature = "parquet")]
+pub use parquet::ParquetSink;
+pub use pause::{SendPause, PAUSED_TEXT, RESUMED_TEXT};
+pub use pipe::{pipe, Pipe};
+pub use pipeline::{hiread_pipelined, hiwrite_pipelined};
+pub use pod::Pod;
+pub use pool::{HiPool, PooledStream};
+pub use quantize::{hiread_quantized, hiwrite_quantized, Quantization};
+pub use quota::Quota;
+pub use recorder::FlightRecorder;
+pub use reduce::{hiread_with_reduce, FloatReport, Reducer, Stats, WindowedStats};
+pub use relay::hirelay;
+pub use reorder::ReorderPool;
+pub use request::{Request, Requests};
+pub use retry::RetryPolicy;
+pub use schema::Schema;
+pub use server::HiServer;
+pub use session::{Session, SessionStore};
+pub use shared::SyncHiStream;
+pub use sparse::{hiread_sparse, hiread_sparse_dense, hiwrite_sparse, SparseArray};
+pub use spool::Spool;
+#[cfg(feature = "ssh")]
+pub use ssh::SshStream;
+pub use state::{AwaitingAck, Connection, Idle, Sending};
+pub use stream::HiStream;
+pub use tee::{hiread_tee, Tee};
+pub use transport::Transport;
+pub use typed::{TypedReceiver, TypedSender};
+pub use validate::{hiread_validated, Validator};
+pub use verified::{hiread_verified, hiwrite_verified};
+pub use window::FlowWindow;
+
+use std::io::{Read, Write};
+use std::mem::MaybeUninit;
+use std::ops::Range;
+
+use crate::pod::{bytes_of, bytes_of_mut};
 
 const DELIMITER_NAN: [u8; 8] = [0x5b, 0xa0, 0x00, 0x04, 0x10, 0x00, 0xf8, 0x7f];
+/// The single word of the message ending a dataset: a NaN spelling `end`,
+/// like the delimiter is one.
+const END_NAN: [u8; 8] = *b"end\x00\x00\x00\xf8\x7f";
 const DEFAULT_SIZE: usize = 100_000_000;
+const CHUNK_SIZE: usize = 131_072;
+/// Byte sent by receivers instead of an acknowledgement to refuse a message,
+/// followed by the reason why on a line.
+const REFUSAL: u8 = b'!';
+/// Longest reason given when refusing a message, in bytes.
+const MAX_REASON: usize = 4096;
 
-fn as_u8_slice<T>(v: &[T]) -> &[u8] {
-    unsafe {
-        std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * std::mem::size_of::<T>())
-    }
-}
-
-fn as_u8_slice_mut<T>(v: &mut [T]) -> &mut [u8] {
-    unsafe {
-        std::slice::from_raw_parts_mut(v.as_ptr() as *mut u8, v.len() * std::mem::size_of::<T>())
-    }
+/// Pack `bytes` into whole words, padding the last one with zeros.
+fn pack_bytes(bytes: &[u8]) -> Vec<f64> {
+    let mut words = vec![0.0; bytes.len().div_ceil(8)];
+    bytes_of_mut(&mut words)[..bytes.len()].copy_from_slice(bytes);
+    words
 }
 
 /// Read a *High Tension Message* from the `stream`.
@@ -90,40 +287,700 @@ fn as_u8_slice_mut<T>(v: &mut [T]) -> &mut [u8] {
 /// minimize the number of allocations required, but may induce excessive RAM
 /// consumption. Extra space is released when the function returns.
 ///
+/// An empty message, such as sent by [`hiempty`], is returned as an empty
+/// array.
+///
+/// [`hiempty`]: fn.hiempty.html
+///
 /// # Examples
 ///
 /// Basic usage:
 ///
 /// ```no_run
+/// use hi_tension::hiread;
 /// use std::net::TcpStream;
-/// let stream = TcpStream::connect("127.0.0.1:34567")
 ///
-/// let data = hiread(&mut stream);
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let data = hiread(&mut stream)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn hiread<S: Read + Write>(stream: &mut S) -> Result<Vec<f64>> {
+pub fn hiread<S: Read + Write + ?Sized>(stream: &mut S) -> Result<Vec<f64>> {
+    read_delimited(stream, &DELIMITER_NAN)
+}
+
+/// Same as `hiread`, for messages ended by `delimiter`.
+pub(crate) fn read_delimited<S: Read + Write + ?Sized>(
+    stream: &mut S,
+    delimiter: &[u8; 8],
+) -> Result<Vec<f64>> {
+    let mut buf = vec![0.0; DEFAULT_SIZE];
+    read_into(stream, &mut buf, 0, delimiter)?;
+    Ok(buf)
+}
+
+/// Read `n` *High Tension Messages* from the `stream`.
+///
+/// This function is blocking, and allocates like [`hiread`] for every message.
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hiread_n;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let arrays = hiread_n(&mut stream, 3)?;
+/// let (x, y, z) = (&arrays[0], &arrays[1], &arrays[2]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_n<S: Read + Write + ?Sized>(stream: &mut S, n: usize) -> Result<Vec<Vec<f64>>> {
+    (0..n).map(|_| hiread(stream)).collect()
+}
+
+/// Read *High Tension Messages* from the `stream` until the end of the
+/// dataset, signaled by the sender with [`hiend`].
+///
+/// This function is blocking, and allocates like [`hiread`] for every message.
+///
+/// [`hiend`]: fn.hiend.html
+/// [`hiread`]: fn.hiread.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hiread_all;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// for array in hiread_all(&mut stream)? {
+///     println!("received {} floats", array.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_all<S: Read + Write + ?Sized>(stream: &mut S) -> Result<Vec<Vec<f64>>> {
+    let mut arrays = Vec::new();
+    loop {
+        let data = hiread(stream)?;
+        if is_end(&data) {
+            return Ok(arrays);
+        }
+        arrays.push(data);
+    }
+}
+
+/// Signal the end of a dataset, made of the *High Tension Messages* sent so
+/// far, to the other end of the `stream`.
+///
+/// This function is blocking.
+///
+/// The receiver learns that no more arrays are coming without an out-of-band
+/// count, e.g. with [`hiread_all`]. The end of a dataset is a message of its
+/// own, holding a single NaN value distinct from the delimiter.
+///
+/// [`hiread_all`]: fn.hiread_all.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hidelimiter, hiend, hiwrite};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// for i in 0..10 {
+///     hiwrite(&mut stream, &vec![i as f64; 1000])?;
+///     hidelimiter(&mut stream)?;
+/// }
+/// hiend(&mut stream)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiend<S: Read + Write + ?Sized>(stream: &mut S) -> Result<()> {
+    hiwrite(stream, &[f64::from_le_bytes(END_NAN)])?;
+    hidelimiter(stream)
+}
+
+/// Whether `data` is the message ending a dataset.
+fn is_end(data: &[f64]) -> bool {
+    matches!(data, [word] if word.to_le_bytes() == END_NAN)
+}
+
+/// Send an empty *High Tension Message* to the other end of the `stream`.
+///
+/// This function is blocking, until the other side received the message.
+///
+/// An empty message is the delimiter alone, and is received as an empty array
+/// by [`hiread`], or as [`Message::Empty`] by [`HiStream::recv`]. Since
+/// nothing but the delimiter and its acknowledgement are exchanged, it makes a
+/// cheap synchronization barrier between both sides.
+///
+/// [`hiread`]: fn.hiread.html
+/// [`Message::Empty`]: enum.Message.html#variant.Empty
+/// [`HiStream::recv`]: struct.HiStream.html#method.recv
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiempty, hiread};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// // Wait until the other side is ready too
+/// hiempty(&mut stream)?;
+/// assert!(hiread(&mut stream)?.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiempty<S: Read + Write + ?Sized>(stream: &mut S) -> Result<()> {
+    hidelimiter(stream)
+}
+
+/// Read `n` *High Tension Messages* from the `stream`, concatenated into a
+/// single array.
+///
+/// This function is blocking.
+///
+/// All the messages are received in place into a single buffer, grown like in
+/// [`hiread`], so no copy happens after reception.
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hiread_concat;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// // A dataset sent as 16 slices
+/// let dataset = hiread_concat(&mut stream, 16)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_concat<S: Read + Write + ?Sized>(stream: &mut S, n: usize) -> Result<Vec<f64>> {
+    let mut buf = vec![0.0; DEFAULT_SIZE];
+    let mut len = 0;
+    for _ in 0..n {
+        read_into(stream, &mut buf, len, &DELIMITER_NAN)?;
+        len = buf.len();
+    }
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// Read a *High Tension Message* of exactly `N` floats from the `stream`.
+///
+/// This function is blocking, and never allocates: the message is received
+/// straight into the array returned, which lives on the stack. It suits
+/// protocols with fixed frame sizes, whose shape is then checked by the
+/// compiler; large messages are better received with [`hiread`].
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the message holds another number of
+/// floats. The rest of a longer message is still received, and discarded, so
+/// that the stream stays usable for the next messages.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hiread_exact;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// // A position and a velocity
+/// let [x, y, z, vx, vy, vz] = hiread_exact::<_, 6>(&mut stream)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_exact<S: Read + Write + ?Sized, const N: usize>(stream: &mut S) -> Result<[f64; N]> {
+    let mut data = [0.0; N];
+    read_exact_into(stream, &mut data)?;
+    Ok(data)
+}
+
+/// Same as `hiread_exact`, into a slice whose length is the one expected.
+pub(crate) fn read_exact_into<S: Read + Write + ?Sized>(
+    stream: &mut S,
+    data: &mut [f64],
+) -> Result<()> {
+    let expected = data.len();
+    let bytes = bytes_of_mut(data);
+    // SAFETY: initialized bytes are valid as possibly uninitialized ones
+    let buf = unsafe { &mut *(bytes as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    match read_message_raw(stream, buf) {
+        Ok(len) if len == expected * 8 => Ok(()),
+        Ok(len) => Err(Error::Framing(format!(
+            "message of {} floats instead of {}",
+            len / 8,
+            expected
+        ))),
+        Err(Error::Framing(_)) => Err(Error::Framing(format!(
+            "message of more than {} floats",
+            expected
+        ))),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read a *High Tension Message* from the `stream` into `buf`, a buffer
+/// managed by the caller, and return its size in bytes.
+///
+/// This function is blocking, and never allocates.
+///
+/// This is a low-level building block for buffers which are not a `Vec`:
+/// memory from a custom allocator, pinned for a GPU, or shared with another
+/// process. On success, the first bytes of `buf`, as many as returned, hold
+/// the values of the message as little-endian `f64`, and are initialized.
+///
+/// Since `Read` implementations need initialized memory, `buf` is zeroed just
+/// ahead of the reads, 1 MB at a time, while it is still in cache.
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the message does not fit in `buf`. The
+/// rest of the message is still received, and discarded, so that the stream
+/// stays usable for the next messages.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::read_message_raw;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let mut buf = Box::new_uninit_slice(8 << 20);
+/// let len = read_message_raw(&mut stream, &mut buf)?;
+/// // SAFETY: the first `len` bytes were initialized by `read_message_raw`
+/// let bytes = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, len) };
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_message_raw<S: Read + Write + ?Sized>(
+    stream: &mut S,
+    buf: &mut [MaybeUninit<u8>],
+) -> Result<usize> {
     let mut i = 0;
-    let mut size = DEFAULT_SIZE;
-    let mut buf = vec![0.0; size];
-    let mut buf_view = as_u8_slice_mut(&mut buf);
+    let mut init = 0;
+    loop {
+        if i == buf.len() {
+            let len = i - i % 8;
+            read_overflow(stream, &assume_init(buf)[len..])?;
+            return Ok(len);
+        }
+        if init == i {
+            init = (i + CHUNK_SIZE * 8).min(buf.len());
+            for byte in &mut buf[i..init] {
+                byte.write(0);
+            }
+        }
+
+        let n = read_some(stream, assume_init_mut(&mut buf[i..init]))?;
+        i += n;
+
+        if i % 8 == 0 && i >= 8 && assume_init(&buf[i - 8..i]) == DELIMITER_NAN {
+            acknowledge(stream)?;
+            return Ok(i - 8);
+        }
+    }
+}
+
+/// Finish reading a message which filled the buffer of `read_message_raw`,
+/// given the `partial` word it ends with, in case the rest is the delimiter.
+fn read_overflow<S: Read + Write + ?Sized>(stream: &mut S, partial: &[u8]) -> Result<()> {
+    let mut word = [0; 8];
+    word[..partial.len()].copy_from_slice(partial);
+    stream.read_exact(&mut word[partial.len()..])?;
+    if word != DELIMITER_NAN {
+        // The rest of the message is aligned on whole words again
+        read_chunks(stream, |_| Ok(()))?;
+        return Err(Error::Framing("message larger than the buffer".into()));
+    }
+    acknowledge(stream)
+}
+
+/// View initialized bytes as such.
+fn assume_init(buf: &[MaybeUninit<u8>]) -> &[u8] {
+    // SAFETY: only called on bytes written beforehand
+    unsafe { &*(buf as *const [MaybeUninit<u8>] as *const [u8]) }
+}
+
+fn assume_init_mut(buf: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    // SAFETY: only called on bytes written beforehand
+    unsafe { &mut *(buf as *mut [MaybeUninit<u8>] as *mut [u8]) }
+}
+
+/// Read a *High Tension Message* ended by `delimiter` into `buf`, after its
+/// first `start` floats.
+///
+/// The space of `buf` past `start` is used first, then `buf` is grown by
+/// doubling its size, or to `DEFAULT_SIZE` if it is empty. On return, `buf`
+/// is truncated to the end of the message.
+fn read_into<S: Read + Write + ?Sized, B: RecvBuffer>(
+    stream: &mut S,
+    buf: &mut B,
+    start: usize,
+    delimiter: &[u8; 8],
+) -> Result<()> {
+    read_into_with(stream, buf, start, delimiter, usize::MAX, |_| {})
+}
+
+/// Same as `read_into`, handing the floats over to `f` as soon as they are
+/// received, while they are still in cache.
+///
+/// At most `limit` floats are read, delimiter included: past that, it fails
+/// with `Error::TooLarge(limit)` and leaves the rest of the message unread.
+fn read_into_with<S, B, F>(
+    stream: &mut S,
+    buf: &mut B,
+    start: usize,
+    delimiter: &[u8; 8],
+    limit: usize,
+    f: F,
+) -> Result<()>
+where
+    S: Read + Write + ?Sized,
+    B: RecvBuffer,
+    F: FnMut(&[f64]),
+{
+    read_frame(stream, buf, start, delimiter, limit, None, f)
+}
+
+/// Same as `read_into_with`, failing with `Error::Framing` when `strict` is
+/// given and bytes were received past the delimiter, which the sender is then
+/// told. `strict` is then set to whether these bytes ended with a delimiter,
+/// leaving the stream at the start of the next frame.
+fn read_frame<S, B, F>(
+    stream: &mut S,
+    buf: &mut B,
+    start: usize,
+    delimiter: &[u8; 8],
+    limit: usize,
+    mut strict: Option<&mut bool>,
+    mut f: F,
+) -> Result<()>
+where
+    S: Read + Write + ?Sized,
+    B: RecvBuffer,
+    F: FnMut(&[f64]),
+{
+    let mut checked = start;
+    let mut done = start;
+    let mut i = start * 8;
+    let mut size = buf.words_mut().len().min(limit);
+    let mut buf_view = bytes_of_mut(buf.words_mut());
     loop {
         if i == size * 8 {
-            drop(buf_view);
-            size *= 2;
-            buf.resize(size, 0.0);
-            buf_view = as_u8_slice_mut(&mut buf);
+            if size == limit {
+                return Err(Error::TooLarge(limit));
+            }
+            size = if size == 0 { DEFAULT_SIZE } else { size * 2 }.min(limit);
+            buf.resize(size);
+            buf_view = bytes_of_mut(buf.words_mut());
         }
 
-        i += stream.read(&mut buf_view[i..])?;
+        let n = read_some(stream, &mut buf_view[i..size * 8])?;
+        i += n;
+
+        if let Some(synced) = strict.as_deref_mut() {
+            // Only the last word received may be the delimiter, and only if
+            // nothing follows it
+            let stray = (checked..i / 8)
+                .find(|&k| buf_view[k * 8..k * 8 + 8] == delimiter[..] && k * 8 + 8 < i);
+            let frames = match stray {
+                // Answer every frame the sender ended, so that it does not
+                // wait for the acknowledgement of any of them
+                Some(k) => {
+                    let tail = &buf_view[k * 8 + 8..i];
+                    *synced = tail.len().is_multiple_of(8) && tail.ends_with(delimiter);
+                    1 + tail.chunks_exact(8).filter(|w| *w == delimiter).count()
+                }
+                // A delimiter out of step with the words, after bytes which
+                // are not whole words
+                None if !i.is_multiple_of(8) && buf_view[..i].ends_with(delimiter) => {
+                    *synced = true;
+                    1
+                }
+                None => 0,
+            };
+            if frames > 0 {
+                for _ in 0..frames {
+                    let _ = refuse(stream, "stray bytes after the delimiter");
+                }
+                return Err(Error::Framing("stray bytes after the delimiter".into()));
+            }
+            checked = i / 8;
+        }
 
-        if buf_view[i - 8..i] == DELIMITER_NAN {
-            stream.write(b"\n")?;
-            stream.flush()?;
+        // Values are whole words, so the delimiter cannot end anywhere else
+        let end = i.is_multiple_of(8) && i >= start * 8 + 8 && buf_view[i - 8..i] == delimiter[..];
+        // The last complete word may turn out to be the delimiter
+        let received = (i / 8).saturating_sub(1).max(done);
+        if received > done {
+            f(&buf.words_mut()[done..received]);
+            done = received;
+            buf_view = bytes_of_mut(buf.words_mut());
+        }
+        if end {
+            acknowledge(stream)?;
             break;
         }
     }
-    size = i / 8 - 1;
-    buf.truncate(size);
-    Ok(buf)
+    buf.truncate(i / 8 - 1);
+    Ok(())
+}
+
+/// A growable buffer messages can be received into.
+trait RecvBuffer {
+    fn words_mut(&mut self) -> &mut [f64];
+    /// Resize to `len` floats, filling new space with zeros.
+    fn resize(&mut self, len: usize);
+    fn truncate(&mut self, len: usize);
+}
+
+impl RecvBuffer for Vec<f64> {
+    fn words_mut(&mut self) -> &mut [f64] {
+        self
+    }
+
+    fn resize(&mut self, len: usize) {
+        Vec::resize(self, len, 0.0);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+}
+
+/// A buffer reused across messages. It keeps the length of its `Vec`, so that
+/// its memory is not zeroed again for every message.
+#[derive(Default)]
+struct Reused {
+    buf: Vec<f64>,
+    len: usize,
+}
+
+impl Reused {
+    /// Get ready to receive a message, allocating like `hiread` the first
+    /// time.
+    fn prepare(&mut self) {
+        if self.buf.is_empty() {
+            self.buf = vec![0.0; DEFAULT_SIZE];
+        }
+        self.len = self.buf.len();
+    }
+
+    /// Hold `data` instead of a received message.
+    fn set(&mut self, data: Vec<f64>) {
+        self.len = data.len();
+        self.buf = data;
+    }
+
+    fn as_slice(&self) -> &[f64] {
+        &self.buf[..self.len]
+    }
+}
+
+impl RecvBuffer for Reused {
+    fn words_mut(&mut self) -> &mut [f64] {
+        &mut self.buf
+    }
+
+    fn resize(&mut self, len: usize) {
+        if len > self.buf.len() {
+            self.buf.resize(len, 0.0);
+        }
+        self.len = len;
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+impl std::fmt::Debug for Reused {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Reused")
+            .field("capacity", &self.buf.len())
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Allocate `len` floats set to `value`, `len` coming from a peer: fails with
+/// `Error::Framing` naming `what` was received, rather than aborting, if they
+/// cannot be allocated.
+pub(crate) fn try_filled(len: usize, value: f64, what: &str) -> Result<Vec<f64>> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(len)
+        .map_err(|_| Error::Framing(format!("{} too large to allocate", what)))?;
+    data.resize(len, value);
+    Ok(data)
+}
+
+/// Read a *High Tension Message* from the `stream`, handing it over to `f` one
+/// chunk at a time instead of collecting it.
+///
+/// Only a buffer of `CHUNK_SIZE` floats is allocated. The message is
+/// acknowledged once `f` accepted its last chunk. Returns the number of floats
+/// received.
+pub(crate) fn read_chunks<S, F>(stream: &mut S, f: F) -> Result<usize>
+where
+    S: Read + Write + ?Sized,
+    F: FnMut(&[f64]) -> Result<()>,
+{
+    let total = read_chunks_unacked(stream, f)?;
+    acknowledge(stream)?;
+    Ok(total)
+}
+
+/// Same as `read_chunks`, but leaves the acknowledgement to the caller.
+pub(crate) fn read_chunks_unacked<R, F>(stream: &mut R, f: F) -> Result<usize>
+where
+    R: Read + ?Sized,
+    F: FnMut(&[f64]) -> Result<()>,
+{
+    read_chunks_until(stream, &DELIMITER_NAN, f)
+}
+
+/// Same as `read_chunks_unacked`, for a message ended by `delimiter`.
+pub(crate) fn read_chunks_until<R, F>(
+    stream: &mut R,
+    delimiter: &[u8; 8],
+    mut f: F,
+) -> Result<usize>
+where
+    R: Read + ?Sized,
+    F: FnMut(&[f64]) -> Result<()>,
+{
+    let mut buf = vec![0.0; CHUNK_SIZE];
+    let mut filled = 0;
+    let mut total = 0;
+    loop {
+        if filled == CHUNK_SIZE * 8 {
+            // The last word may turn out to be the delimiter, keep it for later
+            let words = CHUNK_SIZE - 1;
+            f(&buf[..words])?;
+            total += words;
+            bytes_of_mut(&mut buf).copy_within(words * 8.., 0);
+            filled = 8;
+        }
+
+        let buf_view = bytes_of_mut(&mut buf);
+        let n = read_some(stream, &mut buf_view[filled..])?;
+        filled += n;
+
+        if filled % 8 == 0 && filled >= 8 && buf_view[filled - 8..filled] == *delimiter {
+            let words = filled / 8 - 1;
+            f(&buf[..words])?;
+            return Ok(total + words);
+        }
+    }
+}
+
+/// Read at least one byte from the `stream` into `buf`, which must not be
+/// empty, retrying reads interrupted by a signal.
+fn read_some<R: Read + ?Sized>(stream: &mut R, buf: &mut [u8]) -> Result<usize> {
+    loop {
+        match stream.read(buf) {
+            Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => return Ok(n),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Write at least one byte of `buf`, which must not be empty, to the
+/// `stream`, retrying writes interrupted by a signal.
+pub(crate) fn write_some<W: Write + ?Sized>(stream: &mut W, buf: &[u8]) -> Result<usize> {
+    loop {
+        match stream.write(buf) {
+            Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into()),
+            Ok(n) => return Ok(n),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Acknowledge the reception of a *High Tension Message*.
+fn acknowledge<W: Write + ?Sized>(stream: &mut W) -> Result<()> {
+    stream.write_all(b"\n")?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Refuse a *High Tension Message* instead of acknowledging it, sending the
+/// `reason` why on a line of at most `MAX_REASON` bytes.
+pub(crate) fn refuse<W: Write + ?Sized>(stream: &mut W, reason: &str) -> Result<()> {
+    let mut end = reason.len().min(MAX_REASON);
+    while !reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut line = Vec::with_capacity(end + 2);
+    line.push(REFUSAL);
+    line.extend(
+        reason[..end]
+            .bytes()
+            .map(|b| if b == b'\n' { b' ' } else { b }),
+    );
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Wait for the acknowledgement of a *High Tension Message*, failing with
+/// `Error::RemoteError` if the receiver refused it.
+pub(crate) fn read_ack<R: Read + ?Sized>(stream: &mut R) -> Result<()> {
+    let mut byte = [0];
+    stream.read_exact(&mut byte)?;
+    if byte[0] == REFUSAL {
+        let reason = message::read_text_limited(stream, MAX_REASON)?;
+        return Err(Error::RemoteError(reason));
+    }
+    Ok(())
 }
 
 /// Send a `data` slice as a *High Tension Message* into the `stream`.
@@ -141,30 +998,60 @@ pub fn hiread<S: Read + Write>(stream: &mut S) -> Result<Vec<f64>> {
 /// Basic usage:
 ///
 /// ```no_run
+/// use hi_tension::{hidelimiter, hiwrite};
 /// use std::net::TcpStream;
-/// let stream = TcpStream::connect("127.0.0.1:34567")
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
 ///
 /// let data = vec![0.0; 1_000_000]; // 8 MB
 /// // Of course you can go much higher, your RAM is the limit.
 /// // let data = vec![0.0; 1_000_000_000]; // 8 GB
 ///
 /// hiwrite(&mut stream, &data)?;
-/// hidelimiter(&mut stream);
+/// hidelimiter(&mut stream)?;
 ///
 /// // You may send your data in multible packets
 /// hiwrite(&mut stream, &data[..500_000])?;
 /// hiwrite(&mut stream, &data[500_000..])?;
-/// hidelimiter(&mut stream);
+/// hidelimiter(&mut stream)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn hiwrite<W: Write>(stream: &mut W, data: &[f64]) -> Result<()> {
+pub fn hiwrite<W: Write + ?Sized>(stream: &mut W, data: &[f64]) -> Result<()> {
     let mut i = 0;
-    let slice = as_u8_slice(&data[i..]);
-    loop {
-        i += stream.write(&slice[i..])?;
+    let slice = bytes_of(&data[i..]);
+    while i < slice.len() {
+        i += write_some(stream, &slice[i..])?;
+    }
+    Ok(())
+}
 
-        if i == slice.len() {
-            break;
-        }
+/// Send a `data` slice as part of a *High Tension Message* like [`hiwrite`],
+/// writing at most `chunk_size` bytes at a time.
+///
+/// This function is blocking.
+///
+/// The size of the writes matters for throughput; see [`hicalibrate`] to find
+/// the best one for a connection.
+///
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hicalibrate`]: fn.hicalibrate.html
+///
+/// # Panics
+///
+/// Panics if `chunk_size` is zero.
+pub fn hiwrite_chunked<W: Write + ?Sized>(
+    stream: &mut W,
+    data: &[f64],
+    chunk_size: usize,
+) -> Result<()> {
+    assert!(chunk_size > 0, "chunks hold at least one byte");
+    let slice = bytes_of(data);
+    let mut i = 0;
+    while i < slice.len() {
+        let end = slice.len().min(i.saturating_add(chunk_size));
+        i += write_some(stream, &slice[i..end])?;
     }
     Ok(())
 }
@@ -184,16 +1071,147 @@ pub fn hiwrite<W: Write>(stream: &mut W, data: &[f64]) -> Result<()> {
 /// Basic usage:
 ///
 /// ```no_run
+/// use hi_tension::{hidelimiter, hiwrite};
 /// use std::net::TcpStream;
-/// let stream = TcpStream::connect("127.0.0.1:34567")
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
 ///
 /// let data = vec![0.0; 1_000_000]; // 8 MB
 ///
 /// hiwrite(&mut stream, &data)?;
-/// hidelimiter(&mut stream);
+/// hidelimiter(&mut stream)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hidelimiter<S: Read + Write + ?Sized>(stream: &mut S) -> Result<()> {
+    write_delimiter(stream, &DELIMITER_NAN)
+}
+
+/// Send each of the `segments` of `data` as a *High Tension Message* of its
+/// own into the `stream`.
+///
+/// This function is blocking.
+///
+/// This is the same as calling [`hiwrite`] and [`hidelimiter`] for every
+/// segment, the buffer being split into frames without copies. The receiver
+/// reads them as usual, with [`hiread`] for instance.
+///
+/// Each message is acknowledged before the next one is sent, rather than all
+/// of them in a single pass at the end: receivers read large chunks at a
+/// time, and only look for the delimiter at the end of what they read, so a
+/// message arriving before the previous one was acknowledged would be merged
+/// into it. To acknowledge segments by batches, send them with
+/// [`HiStream::send`] over a stream configured with [`HiConfig::batching`].
+///
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+/// [`hiread`]: fn.hiread.html
+/// [`HiStream::send`]: struct.HiStream.html#method.send
+/// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+///
+/// # Panics
+///
+/// Panics if a segment is out of the bounds of `data`.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use hi_tension::{hiread_n, hiwrite_segments, pipe};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || hiread_n(&mut server, 3));
+///
+/// let data = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+/// hiwrite_segments(&mut client, &data, &[0..1, 1..3, 3..6])?;
+///
+/// let frames = consumer.join().unwrap()?;
+/// assert_eq!(frames, [&data[0..1], &data[1..3], &data[3..6]]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_segments<S: Read + Write + ?Sized>(
+    stream: &mut S,
+    data: &[f64],
+    segments: &[Range<usize>],
+) -> Result<()> {
+    // Messages cannot be pipelined: a receiver reads past the delimiter of a
+    // message if the next one already arrived
+    segments.iter().try_for_each(|segment| {
+        hiwrite(stream, &data[segment.clone()])?;
+        hidelimiter(stream)
+    })
+}
+
+/// Send each of the fixed-size `frames` as a *High Tension Message* of its
+/// own into the `stream`, and return how many were sent.
+///
+/// This function is blocking.
+///
+/// This is meant for instruments producing a steady stream of equal-sized
+/// records. Each frame is copied along with its delimiter into a buffer
+/// allocated once, and written in a single call. The receiver reads the
+/// frames as usual, with [`hiread_exact`] for instance.
+///
+/// Each frame is still acknowledged before the next one is sent. To
+/// acknowledge frames by batches, send them with [`HiStream::send_frames`]
+/// over a stream configured with [`HiConfig::batching`].
+///
+/// [`hiread_exact`]: fn.hiread_exact.html
+/// [`HiStream::send_frames`]: struct.HiStream.html#method.send_frames
+/// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+///
+/// # Examples
+///
+/// Basic usage:
+///
 /// ```
-pub fn hidelimiter<S: Read + Write>(stream: &mut S) -> Result<()> {
-    stream.write(&DELIMITER_NAN)?;
+/// use hi_tension::{hiread_exact, hiwrite_frames, pipe};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || {
+///     (0..100)
+///         .map(|_| hiread_exact::<_, 4>(&mut server))
+///         .collect::<hi_tension::Result<Vec<_>>>()
+/// });
+///
+/// let frames = (0..100).map(|i| [f64::from(i); 4]);
+/// assert_eq!(hiwrite_frames(&mut client, frames)?, 100);
+/// assert_eq!(consumer.join().unwrap()?[42], [42.0; 4]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_frames<S, I, const N: usize>(stream: &mut S, frames: I) -> Result<usize>
+where
+    S: Read + Write + ?Sized,
+    I: IntoIterator<Item = [f64; N]>,
+{
+    let mut buf = Vec::with_capacity(N * 8 + 8);
+    let mut sent = 0;
+    for frame in frames {
+        buf.clear();
+        buf.extend_from_slice(bytes_of(&frame));
+        buf.extend_from_slice(&DELIMITER_NAN);
+        stream.write_all(&buf)?;
+        stream.flush()?;
+        read_ack(stream)?;
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+/// Same as `hidelimiter`, ending the message with `delimiter`.
+pub(crate) fn write_delimiter<S: Read + Write + ?Sized>(
+    stream: &mut S,
+    delimiter: &[u8; 8],
+) -> Result<()> {
+    stream.write_all(delimiter)?;
     stream.flush()?;
-    stream.read_exact(&mut [0])
+    read_ack(stream)
 }
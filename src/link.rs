@@ -0,0 +1,158 @@
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::net::TcpStream;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use crate::{HiStream, Result};
+
+const IPPROTO_TCP: c_int = 6;
+const TCP_INFO: c_int = 11;
+
+extern "C" {
+    fn getsockopt(
+        socket: c_int,
+        level: c_int,
+        name: c_int,
+        value: *mut c_void,
+        len: *mut u32,
+    ) -> c_int;
+}
+
+/// The beginning of `struct tcp_info`, from `linux/tcp.h`. Older kernels fill
+/// less of it, leaving the rest zeroed.
+#[repr(C)]
+#[derive(Default)]
+struct TcpInfo {
+    state: u8,
+    ca_state: u8,
+    retransmits: u8,
+    probes: u8,
+    backoff: u8,
+    options: u8,
+    wscale: u8,
+    flags: u8,
+    rto: u32,
+    ato: u32,
+    snd_mss: u32,
+    rcv_mss: u32,
+    unacked: u32,
+    sacked: u32,
+    lost: u32,
+    retrans: u32,
+    fackets: u32,
+    last_data_sent: u32,
+    last_ack_sent: u32,
+    last_data_recv: u32,
+    last_ack_recv: u32,
+    pmtu: u32,
+    rcv_ssthresh: u32,
+    rtt: u32,
+    rttvar: u32,
+    snd_ssthresh: u32,
+    snd_cwnd: u32,
+    advmss: u32,
+    reordering: u32,
+    rcv_rtt: u32,
+    rcv_space: u32,
+    total_retrans: u32,
+    pacing_rate: u64,
+    max_pacing_rate: u64,
+    bytes_acked: u64,
+    bytes_received: u64,
+}
+
+/// Statistics of a TCP connection, as tracked by the kernel.
+///
+/// See [`HiStream::link_info`]. They tell whether poor throughput comes from
+/// the network (retransmissions, a high round trip time) or from the
+/// application (a congestion window left unused).
+///
+/// [`HiStream::link_info`]: struct.HiStream.html#method.link_info
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkInfo {
+    /// Smoothed round trip time.
+    pub rtt: Duration,
+    /// Variance of the round trip time.
+    pub rtt_var: Duration,
+    /// Segments sent but not acknowledged yet.
+    pub unacked: u32,
+    /// Segments considered lost.
+    pub lost: u32,
+    /// Segments being retransmitted.
+    pub retransmitting: u32,
+    /// Segments retransmitted over the lifetime of the connection.
+    pub total_retransmits: u32,
+    /// Congestion window, in segments.
+    pub cwnd: u32,
+    /// Maximum segment size, in bytes.
+    pub mss: u32,
+    /// Pacing rate, in bytes per second.
+    pub pacing_rate: u64,
+    /// Bytes sent and acknowledged by the peer.
+    pub bytes_acked: u64,
+    /// Bytes received.
+    pub bytes_received: u64,
+}
+
+/// Query the kernel statistics of `tcp`.
+pub(crate) fn link_info(tcp: &TcpStream) -> io::Result<LinkInfo> {
+    let mut info = MaybeUninit::new(TcpInfo::default());
+    let mut len = mem::size_of::<TcpInfo>() as u32;
+    // SAFETY: the kernel writes at most `len` bytes into `info`
+    let ret = unsafe {
+        getsockopt(
+            tcp.as_raw_fd(),
+            IPPROTO_TCP,
+            TCP_INFO,
+            info.as_mut_ptr() as *mut c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: initialized with zeros, and then by the kernel
+    let info = unsafe { info.assume_init() };
+    Ok(LinkInfo {
+        rtt: Duration::from_micros(info.rtt.into()),
+        rtt_var: Duration::from_micros(info.rttvar.into()),
+        unacked: info.unacked,
+        lost: info.lost,
+        retransmitting: info.retrans,
+        total_retransmits: info.total_retrans,
+        cwnd: info.snd_cwnd,
+        mss: info.snd_mss,
+        pacing_rate: info.pacing_rate,
+        bytes_acked: info.bytes_acked,
+        bytes_received: info.bytes_received,
+    })
+}
+
+impl HiStream<TcpStream> {
+    /// Get the kernel statistics of the underlying socket: round trip time,
+    /// retransmissions, congestion window and pacing rate.
+    ///
+    /// This is only available on Linux, through `TCP_INFO`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, HiConfig::new())?;
+    ///
+    /// stream.send(&vec![0.0; 100_000_000])?;
+    /// let info = stream.link_info()?;
+    /// println!("rtt {:?}, {} retransmits", info.rtt, info.total_retransmits);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn link_info(&self) -> Result<LinkInfo> {
+        Ok(link_info(self.get_ref())?)
+    }
+}
@@ -0,0 +1,276 @@
+use std::io::{Read, Write};
+
+use crate::{hidelimiter, hiread, hiwrite, Error, HiStream, Result};
+
+const WORDS_PER_WRITE: usize = 65_536;
+
+/// Columns gathered at once by `hiwrite_columns`: one cache line of each row.
+const TILE_COLS: usize = 8;
+
+/// Memory layout of a [`Matrix`].
+///
+/// [`Matrix`]: struct.Matrix.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// Rows are stored one after the other.
+    RowMajor,
+    /// Columns are stored one after the other.
+    ColumnMajor,
+}
+
+/// A two-dimensional array received as one *High Tension Message*.
+///
+/// Matrices travel along with their shape and layout. They are sent with
+/// [`hiwrite_matrix`] or [`HiStream::send_matrix`], and received with
+/// [`hiread_matrix`].
+///
+/// [`hiwrite_matrix`]: fn.hiwrite_matrix.html
+/// [`HiStream::send_matrix`]: struct.HiStream.html#method.send_matrix
+/// [`hiread_matrix`]: fn.hiread_matrix.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{Layout, Matrix};
+///
+/// let matrix = Matrix {
+///     rows: 2,
+///     cols: 3,
+///     layout: Layout::ColumnMajor,
+///     data: vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0],
+/// };
+/// assert_eq!(matrix.get(0, 2), Some(3.0));
+/// assert_eq!(matrix.get(1, 0), Some(4.0));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    /// Number of rows.
+    pub rows: usize,
+    /// Number of columns.
+    pub cols: usize,
+    /// How the values are laid out in `data`.
+    pub layout: Layout,
+    /// The `rows * cols` values.
+    pub data: Vec<f64>,
+}
+
+impl Matrix {
+    /// Get the value at row `i` and column `j`, or `None` if out of bounds.
+    pub fn get(&self, i: usize, j: usize) -> Option<f64> {
+        if i >= self.rows || j >= self.cols {
+            return None;
+        }
+        match self.layout {
+            Layout::RowMajor => self.data.get(i * self.cols + j).copied(),
+            Layout::ColumnMajor => self.data.get(j * self.rows + i).copied(),
+        }
+    }
+
+    fn decode(mut data: Vec<f64>) -> Result<Self> {
+        let malformed = || Error::Framing("malformed matrix message".into());
+        if data.len() < 3 {
+            return Err(malformed());
+        }
+        let shape = data.split_off(data.len() - 3);
+        let rows = shape[0].to_bits() as usize;
+        let cols = shape[1].to_bits() as usize;
+        let layout = match shape[2].to_bits() {
+            0 => Layout::RowMajor,
+            1 => Layout::ColumnMajor,
+            _ => return Err(malformed()),
+        };
+        if rows.checked_mul(cols) != Some(data.len()) {
+            return Err(malformed());
+        }
+        Ok(Matrix {
+            rows,
+            cols,
+            layout,
+            data,
+        })
+    }
+}
+
+/// Write the message words of the row-major matrix `data` into `f`, in the
+/// requested wire `layout`.
+///
+/// The values are followed by the number of rows and columns, and the layout,
+/// so that the receiver strips them without moving the values.
+fn encode<F>(data: &[f64], rows: usize, cols: usize, layout: Layout, mut f: F) -> Result<()>
+where
+    F: FnMut(&[f64]) -> Result<()>,
+{
+    assert!(
+        rows.checked_mul(cols) == Some(data.len()),
+        "matrix data does not match its shape"
+    );
+
+    let tag = match layout {
+        Layout::RowMajor => {
+            f(data)?;
+            0
+        }
+        Layout::ColumnMajor => {
+            let mut buf = Vec::with_capacity(WORDS_PER_WRITE.max(rows));
+            for j in 0..cols {
+                buf.extend(data.iter().skip(j).step_by(cols).take(rows));
+                if buf.len() >= WORDS_PER_WRITE {
+                    f(&buf)?;
+                    buf.clear();
+                }
+            }
+            f(&buf)?;
+            1
+        }
+    };
+    f(&[
+        f64::from_bits(rows as u64),
+        f64::from_bits(cols as u64),
+        f64::from_bits(tag),
+    ])
+}
+
+/// Send the row-major matrix `data` of shape `rows` by `cols` as a single
+/// *High Tension Message*, laid out on the wire as `layout`.
+///
+/// This function is blocking. Unlike [`hiwrite`], the message is complete: no
+/// call to [`hidelimiter`] is needed.
+///
+/// With [`Layout::ColumnMajor`], the matrix is transposed while it is sent. The
+/// message must be received with [`hiread_matrix`].
+///
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+/// [`Layout::ColumnMajor`]: enum.Layout.html#variant.ColumnMajor
+/// [`hiread_matrix`]: fn.hiread_matrix.html
+///
+/// # Panics
+///
+/// Panics if `data` does not hold `rows * cols` values.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiwrite_matrix, Layout};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let image = vec![0.0; 1024 * 768];
+///
+/// hiwrite_matrix(&mut stream, &image, 768, 1024, Layout::RowMajor)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_matrix<S: Read + Write>(
+    stream: &mut S,
+    data: &[f64],
+    rows: usize,
+    cols: usize,
+    layout: Layout,
+) -> Result<()> {
+    encode(data, rows, cols, layout, |words| hiwrite(stream, words))?;
+    hidelimiter(stream)
+}
+
+/// Read a matrix sent by [`hiwrite_matrix`] or [`HiStream::send_matrix`].
+///
+/// This function is blocking.
+///
+/// [`hiwrite_matrix`]: fn.hiwrite_matrix.html
+/// [`HiStream::send_matrix`]: struct.HiStream.html#method.send_matrix
+pub fn hiread_matrix<S: Read + Write>(stream: &mut S) -> Result<Matrix> {
+    Matrix::decode(hiread(stream)?)
+}
+
+/// Send each column of the row-major matrix `data` with `ncols` columns as a
+/// *High Tension Message* of its own, for receivers processing columns one at
+/// a time.
+///
+/// This function is blocking.
+///
+/// The transposed matrix is never built: columns are gathered by tiles of 8,
+/// reading whole cache lines of each row, so that only 8 columns are held in
+/// memory at a time. Each message is acknowledged before the next one is
+/// sent, as the receiver reads them as usual, with [`hiread`] for instance.
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Panics
+///
+/// Panics if `ncols` is zero, or if `data` does not hold whole rows.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use hi_tension::{hiread_n, hiwrite_columns, pipe};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || hiread_n(&mut server, 3));
+///
+/// let data = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+/// hiwrite_columns(&mut client, &data, 3)?;
+///
+/// let columns = consumer.join().unwrap()?;
+/// assert_eq!(columns, [[1.0, 4.0], [2.0, 5.0], [3.0, 6.0]]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_columns<S: Read + Write>(stream: &mut S, data: &[f64], ncols: usize) -> Result<()> {
+    assert!(ncols > 0, "matrix without columns");
+    assert!(
+        data.len().is_multiple_of(ncols),
+        "matrix data does not hold whole rows"
+    );
+    let rows = data.len() / ncols;
+    let mut tile = vec![0.0; rows * TILE_COLS.min(ncols)];
+    for first in (0..ncols).step_by(TILE_COLS) {
+        let width = TILE_COLS.min(ncols - first);
+        for (i, row) in data.chunks_exact(ncols).enumerate() {
+            for (k, &value) in row[first..first + width].iter().enumerate() {
+                tile[k * rows + i] = value;
+            }
+        }
+        for k in 0..width {
+            hiwrite(stream, &tile[k * rows..(k + 1) * rows])?;
+            hidelimiter(stream)?;
+        }
+    }
+    Ok(())
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Send the row-major matrix `data` of shape `rows` by `cols` as a single
+    /// *High Tension Message*.
+    ///
+    /// The matrix is sent in column-major order if the peer asked for it with
+    /// [`HiConfig::column_major`], and as is otherwise.
+    ///
+    /// [`HiConfig::column_major`]: struct.HiConfig.html#method.column_major
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` does not hold `rows * cols` values.
+    pub fn send_matrix(&mut self, data: &[f64], rows: usize, cols: usize) -> Result<()> {
+        let layout = if self.peer_column_major() {
+            Layout::ColumnMajor
+        } else {
+            Layout::RowMajor
+        };
+        encode(data, rows, cols, layout, |words| self.write(words))?;
+        self.finish()
+    }
+
+    /// Read a matrix sent by [`send_matrix`].
+    ///
+    /// [`send_matrix`]: #method.send_matrix
+    pub fn read_matrix(&mut self) -> Result<Matrix> {
+        Matrix::decode(self.read()?)
+    }
+}
@@ -0,0 +1,439 @@
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{HiServer, Result};
+
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+/// DNS-SD service type of `hi-tension` servers.
+const SERVICE: [&str; 3] = ["_hi-tension", "_tcp", "local"];
+
+const TYPE_A: u16 = 1;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const TTL: u32 = 120;
+
+/// How long [`discover`] waits for answers.
+const DISCOVERY_TIME: Duration = Duration::from_secs(1);
+
+/// How often the responder checks whether the advertisement ended.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// An advertisement of a [`HiServer`] on the local network, started by
+/// [`HiServer::advertise`].
+///
+/// The server is advertised until this is dropped.
+///
+/// [`HiServer`]: struct.HiServer.html
+/// [`HiServer::advertise`]: struct.HiServer.html#method.advertise
+pub struct Advertisement {
+    name: String,
+    stop: Arc<AtomicBool>,
+    responder: Option<JoinHandle<()>>,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(responder) = self.responder.take() {
+            let _ = responder.join();
+        }
+    }
+}
+
+impl fmt::Debug for Advertisement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Advertisement")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl HiServer {
+    /// Advertise this server on the local network as `name`, with multicast
+    /// DNS, so that clients find it with [`discover`].
+    ///
+    /// The server answers the mDNS queries for the
+    /// `name._hi-tension._tcp.local` service, as long as the returned
+    /// [`Advertisement`] is alive. Several servers may share a name.
+    ///
+    /// [`discover`]: fn.discover.html
+    /// [`Advertisement`]: struct.Advertisement.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is empty, or longer than 63 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiServer};
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let server = HiServer::bind("0.0.0.0:34567", HiConfig::new())?;
+    /// let _advertisement = server.advertise("beamline-3")?;
+    /// loop {
+    ///     let mut stream = server.accept()?;
+    ///     println!("received {} floats", stream.read()?.len());
+    /// }
+    /// # }
+    /// ```
+    pub fn advertise(&self, name: &str) -> Result<Advertisement> {
+        check_name(name);
+        let local = self.local_addr()?;
+        let host = match local.ip() {
+            IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+            _ => None,
+        };
+        let socket = bind_shared(MDNS_PORT)?;
+        socket.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let responder = {
+            let stop = stop.clone();
+            let name = name.to_owned();
+            thread::spawn(move || respond(socket, &name, local.port(), host, &stop))
+        };
+        Ok(Advertisement {
+            name: name.to_owned(),
+            stop,
+            responder: Some(responder),
+        })
+    }
+}
+
+/// Find the servers advertised as `name` on the local network, with
+/// [`HiServer::advertise`].
+///
+/// This function is blocking: it waits 1 s for servers to answer, and
+/// returns the addresses of all of those which did, possibly none.
+///
+/// [`HiServer::advertise`]: struct.HiServer.html#method.advertise
+///
+/// # Panics
+///
+/// Panics if `name` is empty, or longer than 63 bytes.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{discover, HiConfig, HiStream};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// for addr in discover("beamline-3")? {
+///     let mut stream = HiStream::connect(addr, HiConfig::new())?;
+///     stream.send(&[1.0, 2.0, 3.0])?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn discover(name: &str) -> Result<Vec<SocketAddr>> {
+    check_name(name);
+    let instance = instance(name);
+    let mut query = header(0, 0, 1, 0, 0);
+    question(&mut query, &instance, TYPE_SRV);
+
+    // Queries from another port than 5353 are answered directly, in unicast
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let group = SocketAddrV4::new(MDNS_GROUP, MDNS_PORT);
+    socket.send_to(&query, group)?;
+
+    let mut found = Vec::new();
+    let mut resent = false;
+    let start = Instant::now();
+    let mut packet = [0; 1500];
+    while let Some(left) = DISCOVERY_TIME.checked_sub(start.elapsed()) {
+        // Ask again once, in case the first query got lost
+        let wait = if resent {
+            left
+        } else {
+            left.saturating_sub(DISCOVERY_TIME / 2)
+        };
+        if wait.is_zero() {
+            socket.send_to(&query, group)?;
+            resent = true;
+            continue;
+        }
+        socket.set_read_timeout(Some(wait))?;
+        let (len, from) = match socket.recv_from(&mut packet) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(addr) = parse_answer(&packet[..len], &instance, from.ip()) {
+            if !found.contains(&addr) {
+                found.push(addr);
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn check_name(name: &str) {
+    assert!(
+        !name.is_empty() && name.len() <= 63,
+        "service names hold between 1 and 63 bytes"
+    );
+}
+
+/// The labels of the DNS-SD instance `name`.
+fn instance(name: &str) -> Vec<&str> {
+    let mut labels = vec![name];
+    labels.extend_from_slice(&SERVICE);
+    labels
+}
+
+/// Answer the queries for `name` received on `socket`, until `stop` is set.
+fn respond(socket: UdpSocket, name: &str, port: u16, host: Option<Ipv4Addr>, stop: &AtomicBool) {
+    let instance = instance(name);
+    let target = [name, "local"];
+    let mut packet = [0; 1500];
+    while !stop.load(Ordering::SeqCst) {
+        let (len, from) = match socket.recv_from(&mut packet) {
+            Ok(received) => received,
+            Err(_) => continue,
+        };
+        let id = match parse_query(&packet[..len], &instance) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let mut answer = header(id, 0x8400, 1, 1, host.is_some() as u16);
+        question(&mut answer, &instance, TYPE_SRV);
+        let mut srv = vec![0, 0, 0, 0];
+        srv.extend_from_slice(&port.to_be_bytes());
+        encode_name(&mut srv, &target);
+        record(&mut answer, &instance, TYPE_SRV, &srv);
+        if let Some(host) = host {
+            record(&mut answer, &target, TYPE_A, &host.octets());
+        }
+        // Answers are sent back to the querier, whether it listens on 5353 or
+        // asked from another port
+        let _ = socket.send_to(&answer, from);
+    }
+}
+
+fn header(id: u16, flags: u16, questions: u16, answers: u16, additional: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    for field in [id, flags, questions, answers, 0, additional] {
+        packet.extend_from_slice(&field.to_be_bytes());
+    }
+    packet
+}
+
+fn encode_name(packet: &mut Vec<u8>, labels: &[&str]) {
+    for label in labels {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+}
+
+fn question(packet: &mut Vec<u8>, labels: &[&str], kind: u16) {
+    encode_name(packet, labels);
+    packet.extend_from_slice(&kind.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+}
+
+fn record(packet: &mut Vec<u8>, labels: &[&str], kind: u16, data: &[u8]) {
+    encode_name(packet, labels);
+    packet.extend_from_slice(&kind.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    packet.extend_from_slice(&TTL.to_be_bytes());
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+}
+
+/// A reader of DNS messages, from RFC 1035.
+struct Reader<'a> {
+    packet: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.packet.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Read a name, following compression pointers.
+    fn name(&mut self) -> Option<Vec<&'a [u8]>> {
+        let mut labels = Vec::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        // Bound the pointers followed, against loops
+        for _ in 0..128 {
+            let len = *self.packet.get(pos)? as usize;
+            match len {
+                0 => {
+                    self.pos = end.unwrap_or(pos + 1);
+                    return Some(labels);
+                }
+                0xc0..=0xff => {
+                    let offset = (len & 0x3f) << 8 | *self.packet.get(pos + 1)? as usize;
+                    end.get_or_insert(pos + 2);
+                    pos = offset;
+                }
+                1..=63 => {
+                    labels.push(self.packet.get(pos + 1..pos + 1 + len)?);
+                    pos += 1 + len;
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+fn same_name(name: &[&[u8]], labels: &[&str]) -> bool {
+    name.len() == labels.len()
+        && name
+            .iter()
+            .zip(labels)
+            .all(|(a, b)| a.eq_ignore_ascii_case(b.as_bytes()))
+}
+
+/// The identifier of a query asking for `instance`, if `packet` is one.
+fn parse_query(packet: &[u8], instance: &[&str]) -> Option<u16> {
+    let mut reader = Reader { packet, pos: 0 };
+    let id = reader.u16()?;
+    let flags = reader.u16()?;
+    if flags & 0x8000 != 0 {
+        return None;
+    }
+    let questions = reader.u16()?;
+    reader.bytes(6)?;
+    for _ in 0..questions {
+        let name = reader.name()?;
+        let kind = reader.u16()?;
+        let class = reader.u16()? & 0x7fff;
+        if same_name(&name, instance) && matches!(kind, TYPE_SRV | TYPE_ANY) && class == CLASS_IN {
+            return Some(id);
+        }
+    }
+    None
+}
+
+/// The address advertised for `instance`, if `packet` is an answer to it,
+/// defaulting to the address of the sender `from`.
+fn parse_answer(packet: &[u8], instance: &[&str], from: IpAddr) -> Option<SocketAddr> {
+    let mut reader = Reader { packet, pos: 0 };
+    reader.bytes(2)?;
+    if reader.u16()? & 0x8000 == 0 {
+        return None;
+    }
+    let questions = reader.u16()?;
+    let records = reader.u16()? as usize + reader.u16()? as usize + reader.u16()? as usize;
+    for _ in 0..questions {
+        reader.name()?;
+        reader.bytes(4)?;
+    }
+
+    let mut port = None;
+    let mut ip = None;
+    for _ in 0..records {
+        let name = reader.name()?;
+        let kind = reader.u16()?;
+        reader.bytes(6)?;
+        let len = reader.u16()? as usize;
+        let data = reader.bytes(len)?;
+        match kind {
+            TYPE_SRV if same_name(&name, instance) && len >= 6 => {
+                port = Some(u16::from_be_bytes([data[4], data[5]]));
+            }
+            TYPE_A if len == 4 => ip = Some(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            _ => {}
+        }
+    }
+    Some(SocketAddr::new(ip.map_or(from, IpAddr::V4), port?))
+}
+
+/// Bind a UDP socket to `port`, sharing it with the other mDNS responders
+/// of the host.
+#[cfg(target_os = "linux")]
+fn bind_shared(port: u16) -> io::Result<UdpSocket> {
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::FromRawFd;
+
+    const AF_INET: c_int = 2;
+    const SOCK_DGRAM: c_int = 2;
+    const SOCK_CLOEXEC: c_int = 0o2000000;
+    const SOL_SOCKET: c_int = 1;
+    const SO_REUSEADDR: c_int = 2;
+    const SO_REUSEPORT: c_int = 15;
+
+    #[repr(C)]
+    struct SockaddrIn {
+        family: u16,
+        port: u16,
+        addr: [u8; 4],
+        zero: [u8; 8],
+    }
+
+    extern "C" {
+        fn socket(domain: c_int, kind: c_int, protocol: c_int) -> c_int;
+        fn setsockopt(
+            socket: c_int,
+            level: c_int,
+            name: c_int,
+            value: *const c_void,
+            len: u32,
+        ) -> c_int;
+        fn bind(socket: c_int, addr: *const SockaddrIn, len: u32) -> c_int;
+    }
+
+    // SAFETY: plain system calls, on a descriptor owned by the socket as soon
+    // as it is created
+    unsafe {
+        let fd = socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = UdpSocket::from_raw_fd(fd);
+        let on: c_int = 1;
+        for option in [SO_REUSEADDR, SO_REUSEPORT] {
+            let value = &on as *const c_int as *const c_void;
+            if setsockopt(fd, SOL_SOCKET, option, value, 4) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let addr = SockaddrIn {
+            family: AF_INET as u16,
+            port: port.to_be(),
+            addr: [0; 4],
+            zero: [0; 8],
+        };
+        if bind(fd, &addr, std::mem::size_of::<SockaddrIn>() as u32) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(socket)
+    }
+}
+
+/// Bind a UDP socket to `port`, which fails if another mDNS responder runs
+/// on the host.
+#[cfg(not(target_os = "linux"))]
+fn bind_shared(port: u16) -> io::Result<UdpSocket> {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))
+}
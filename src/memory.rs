@@ -0,0 +1,121 @@
+#[cfg(unix)]
+use std::fmt;
+#[cfg(unix)]
+use std::os::raw::{c_int, c_void};
+
+#[cfg(unix)]
+use crate::Result;
+
+/// Stride of the prefaulting reads: the smallest page size in use.
+const PAGE_SIZE: usize = 4096;
+
+#[cfg(unix)]
+extern "C" {
+    fn mlock(addr: *const c_void, len: usize) -> c_int;
+    fn munlock(addr: *const c_void, len: usize) -> c_int;
+}
+
+/// Touch every memory page of `data`, so that none of them faults while it is
+/// sent.
+///
+/// The pages of a freshly allocated array are only mapped on first access.
+/// Faulting in the pages of an 8 GB array takes a noticeable time, which is
+/// better spent before a timing-critical send than in the middle of it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{hidelimiter, hiwrite, prefault};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let data = vec![0.0; 1_000_000_000];
+/// prefault(&data);
+/// // Wait for the trigger, then
+/// hiwrite(&mut stream, &data)?;
+/// hidelimiter(&mut stream)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn prefault(data: &[f64]) {
+    let bytes = crate::pod::bytes_of(data);
+    for i in (0..bytes.len()).step_by(PAGE_SIZE) {
+        // SAFETY: in bounds, and volatile so that the read is not elided
+        unsafe { std::ptr::read_volatile(bytes.as_ptr().add(i)) };
+    }
+}
+
+/// Memory locked into RAM by [`lock_memory`], until dropped.
+///
+/// [`lock_memory`]: fn.lock_memory.html
+#[cfg(unix)]
+pub struct MemoryLock<'a> {
+    data: &'a [f64],
+}
+
+/// Lock the memory pages of `data` into RAM, so that they neither fault nor
+/// get swapped out while it is sent.
+///
+/// This is only available on Unix, through `mlock`. The pages are faulted in
+/// by the call, and stay locked until the returned guard is dropped. Systems
+/// bound the memory a process may lock, see `ulimit -l`.
+///
+/// # Errors
+///
+/// Fails with the IO error of `mlock`, typically `ENOMEM` past the bound of
+/// the system, or `EPERM`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{hidelimiter, hiwrite, lock_memory};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let data = vec![0.0; 100_000_000];
+/// let lock = lock_memory(&data)?;
+/// hiwrite(&mut stream, &data)?;
+/// hidelimiter(&mut stream)?;
+/// drop(lock);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(unix)]
+pub fn lock_memory(data: &[f64]) -> Result<MemoryLock<'_>> {
+    if !data.is_empty() {
+        // SAFETY: the range is a live allocation, borrowed by the guard
+        let ret = unsafe { mlock(data.as_ptr() as *const c_void, std::mem::size_of_val(data)) };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(MemoryLock { data })
+}
+
+#[cfg(unix)]
+impl fmt::Debug for MemoryLock<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryLock")
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MemoryLock<'_> {
+    fn drop(&mut self) {
+        if !self.data.is_empty() {
+            // SAFETY: the range was locked by `lock_memory`
+            unsafe {
+                munlock(
+                    self.data.as_ptr() as *const c_void,
+                    std::mem::size_of_val(self.data),
+                )
+            };
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::Read;
+use std::ops::Deref;
+
+use crate::{Error, Result};
+
+/// Prefix of the *Simple Text Messages*, with [`HiConfig::typed_messages`].
+///
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+pub(crate) const TEXT_PREFIX: u8 = b'T';
+
+/// Prefix of the *High Tension Messages*, with [`HiConfig::typed_messages`].
+///
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+pub(crate) const ARRAY_PREFIX: u8 = b'A';
+
+/// A message of either kind, as received by [`HiStream::recv`].
+///
+/// [`HiStream::recv`]: struct.HiStream.html#method.recv
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// A *Simple Text Message*, without its newline.
+    Text(String),
+    /// A *High Tension Message*.
+    Array(Vec<f64>),
+    /// An empty *High Tension Message*, such as sent by [`hiempty`].
+    ///
+    /// [`hiempty`]: fn.hiempty.html
+    Empty,
+}
+
+/// Read the type prefix of the next message.
+pub(crate) fn read_prefix<R: Read>(stream: &mut R) -> Result<u8> {
+    let mut prefix = [0];
+    stream.read_exact(&mut prefix)?;
+    match prefix[0] {
+        TEXT_PREFIX | ARRAY_PREFIX => Ok(prefix[0]),
+        other => Err(Error::Framing(format!(
+            "unknown message type {:#04x}",
+            other
+        ))),
+    }
+}
+
+/// Read a *Simple Text Message*, byte by byte so that nothing past its
+/// newline is consumed from the stream.
+pub(crate) fn read_text<R: Read + ?Sized>(stream: &mut R) -> Result<String> {
+    read_text_limited(stream, usize::MAX)
+}
+
+/// Same as `read_text`, failing with `Error::Framing` past `limit` bytes.
+pub(crate) fn read_text_limited<R: Read + ?Sized>(stream: &mut R, limit: usize) -> Result<String> {
+    let mut text = Vec::new();
+    loop {
+        let mut byte = [0];
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        if text.len() == limit {
+            return Err(Error::Framing("text message too long".into()));
+        }
+        text.push(byte[0]);
+    }
+    String::from_utf8(text).map_err(|_| Error::Framing("invalid UTF-8 in text message".into()))
+}
+
+/// A message of either kind, as received by [`HiStream::recv_ref`], whose
+/// array borrows the receive buffer of the stream.
+///
+/// [`HiStream::recv_ref`]: struct.HiStream.html#method.recv_ref
+#[derive(Debug, PartialEq)]
+pub enum MessageRef<'a> {
+    /// A *Simple Text Message*, without its newline.
+    Text(String),
+    /// A *High Tension Message*.
+    Array(ArrayRef<'a>),
+    /// An empty *High Tension Message*, such as sent by [`hiempty`].
+    ///
+    /// [`hiempty`]: fn.hiempty.html
+    Empty,
+}
+
+impl MessageRef<'_> {
+    /// Copy the message out of the receive buffer.
+    pub fn to_owned(&self) -> Message {
+        match self {
+            MessageRef::Text(text) => Message::Text(text.clone()),
+            MessageRef::Array(data) => Message::Array(data.to_vec()),
+            MessageRef::Empty => Message::Empty,
+        }
+    }
+}
+
+/// The values of a *High Tension Message*, borrowed from the receive buffer
+/// of a [`HiStream`] until the next message is received.
+///
+/// It dereferences to a slice.
+///
+/// [`HiStream`]: struct.HiStream.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArrayRef<'a> {
+    data: &'a [f64],
+}
+
+impl<'a> ArrayRef<'a> {
+    pub(crate) fn new(data: &'a [f64]) -> Self {
+        ArrayRef { data }
+    }
+
+    /// Get the values, with the lifetime of the borrow of the stream.
+    pub fn as_slice(&self) -> &'a [f64] {
+        self.data
+    }
+}
+
+impl Deref for ArrayRef<'_> {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        self.data
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt::{self, Write as _};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+/// How long the exporter waits for a scraper to send its request.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest HTTP request head read from a scraper.
+const MAX_REQUEST: usize = 8192;
+
+#[derive(Debug, Default)]
+struct Counters {
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    errors: AtomicU64,
+    send_nanos: AtomicU64,
+}
+
+/// The counters of every connection, by name.
+type Registry = Vec<(String, Arc<Counters>)>;
+
+/// The counters exported, as name, help text and field.
+type Counter = (&'static str, &'static str, fn(&Counters) -> &AtomicU64);
+
+const COUNTERS: [Counter; 5] = [
+    (
+        "hi_tension_messages_sent_total",
+        "High Tension Messages sent.",
+        |c| &c.messages_sent,
+    ),
+    (
+        "hi_tension_messages_received_total",
+        "High Tension Messages received.",
+        |c| &c.messages_received,
+    ),
+    (
+        "hi_tension_bytes_sent_total",
+        "Bytes of values sent, trailers excluded.",
+        |c| &c.bytes_sent,
+    ),
+    (
+        "hi_tension_bytes_received_total",
+        "Bytes of values received, trailers excluded.",
+        |c| &c.bytes_received,
+    ),
+    (
+        "hi_tension_errors_total",
+        "Failed sends and receives.",
+        |c| &c.errors,
+    ),
+];
+
+/// A registry of per-connection metrics, published in the Prometheus text
+/// format.
+///
+/// It requires the `metrics` feature. Each connection to monitor gets its
+/// counters from [`connection`], and handed over to it with
+/// [`HiStream::set_metrics`]. The registry is then scraped through the
+/// endpoint of [`serve`], or rendered with [`render`] to be published
+/// otherwise. Counters are kept for the lifetime of the registry, so that
+/// connections opened again under the same name carry on counting.
+///
+/// `Metrics` is a cheap handle: clones refer to the same registry.
+///
+/// [`connection`]: #method.connection
+/// [`HiStream::set_metrics`]: struct.HiStream.html#method.set_metrics
+/// [`serve`]: #method.serve
+/// [`render`]: #method.render
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiServer, Metrics};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let metrics = Metrics::new();
+/// metrics.serve("0.0.0.0:9100")?;
+///
+/// let server = HiServer::bind("0.0.0.0:34567", HiConfig::new())?;
+/// loop {
+///     let mut stream = server.accept()?;
+///     let peer = stream.get_ref().peer_addr()?;
+///     stream.set_metrics(metrics.connection(&peer.to_string()));
+///     while let Ok(data) = stream.read() {
+///         println!("received {} floats", data.len());
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Metrics {
+    connections: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the counters of the connection called `name`, registering it the
+    /// first time.
+    pub fn connection(&self, name: &str) -> ConnectionMetrics {
+        let mut connections = self.lock();
+        let counters = match connections.iter().find(|(other, _)| other == name) {
+            Some((_, counters)) => counters.clone(),
+            None => {
+                let counters = Arc::<Counters>::default();
+                connections.push((name.to_owned(), counters.clone()));
+                counters
+            }
+        };
+        ConnectionMetrics {
+            counters,
+            started: None,
+            pending_bytes: 0,
+        }
+    }
+
+    /// Render every metric of every connection, in the Prometheus text
+    /// format.
+    pub fn render(&self) -> String {
+        let connections = self.lock();
+        let mut text = String::new();
+        for (name, help, field) in &COUNTERS {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} counter", name);
+            for (connection, counters) in connections.iter() {
+                let value = field(counters).load(Ordering::Relaxed);
+                let _ = writeln!(text, "{}{} {}", name, Label(connection), value);
+            }
+        }
+
+        let name = "hi_tension_send_duration_seconds";
+        let _ = writeln!(
+            text,
+            "# HELP {} Time from the first write of a message to its acknowledgement.",
+            name
+        );
+        let _ = writeln!(text, "# TYPE {} summary", name);
+        for (connection, counters) in connections.iter() {
+            let nanos = counters.send_nanos.load(Ordering::Relaxed);
+            let count = counters.messages_sent.load(Ordering::Relaxed);
+            let label = Label(connection);
+            let _ = writeln!(text, "{}_sum{} {}", name, label, nanos as f64 * 1e-9);
+            let _ = writeln!(text, "{}_count{} {}", name, label, count);
+        }
+        text
+    }
+
+    /// Serve the metrics over HTTP on `addr`, from a thread of its own, and
+    /// return the address bound.
+    ///
+    /// Every request gets the output of [`render`], whatever its path. The
+    /// thread runs for the lifetime of the process, answering one scraper at
+    /// a time.
+    ///
+    /// [`render`]: #method.render
+    pub fn serve(&self, addr: impl ToSocketAddrs) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let metrics = self.clone();
+        thread::spawn(move || {
+            for scraper in listener.incoming().flatten() {
+                // A failing scraper only loses its own scrape
+                let _ = metrics.answer(scraper);
+            }
+        });
+        Ok(local_addr)
+    }
+
+    fn answer(&self, mut scraper: TcpStream) -> std::io::Result<()> {
+        scraper.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") && request.len() < MAX_REQUEST {
+            let n = scraper.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+
+        let body = self.render();
+        write!(
+            scraper,
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        scraper.flush()
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("connections", &self.lock().len())
+            .finish()
+    }
+}
+
+/// The `connection` label of a metric, escaped.
+struct Label<'a>(&'a str);
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("{connection=\"")?;
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_str("\"}")
+    }
+}
+
+/// The counters of a single connection, from [`Metrics::connection`].
+///
+/// [`Metrics::connection`]: struct.Metrics.html#method.connection
+#[derive(Debug)]
+pub struct ConnectionMetrics {
+    counters: Arc<Counters>,
+    started: Option<Instant>,
+    pending_bytes: u64,
+}
+
+impl ConnectionMetrics {
+    /// Account for `len` values written as part of the current message.
+    pub(crate) fn write(&mut self, len: usize) {
+        self.started.get_or_insert_with(Instant::now);
+        self.pending_bytes += len as u64 * 8;
+    }
+
+    /// Account for the current message, acknowledged by the peer.
+    pub(crate) fn sent(&mut self) {
+        let nanos = self
+            .started
+            .take()
+            .map_or(0, |start| start.elapsed().as_nanos());
+        let bytes = std::mem::take(&mut self.pending_bytes);
+        let counters = &self.counters;
+        counters.messages_sent.fetch_add(1, Ordering::Relaxed);
+        counters.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        counters
+            .send_nanos
+            .fetch_add(nanos.min(u64::MAX as u128) as u64, Ordering::Relaxed);
+    }
+
+    /// Account for a message of `len` values received.
+    pub(crate) fn received(&self, len: usize) {
+        let counters = &self.counters;
+        counters.messages_received.fetch_add(1, Ordering::Relaxed);
+        counters
+            .bytes_received
+            .fetch_add(len as u64 * 8, Ordering::Relaxed);
+    }
+
+    /// Account for a failure, forgetting the message being sent if any.
+    pub(crate) fn error(&mut self) {
+        self.started = None;
+        self.pending_bytes = 0;
+        self.counters.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
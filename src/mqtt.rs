@@ -0,0 +1,241 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Write as _};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{Error, Result, Timestamp};
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const DISCONNECT: u8 = 0xe0;
+
+/// A piece of the summary of a message, published by [`MqttSummaries`].
+///
+/// [`MqttSummaries`]: struct.MqttSummaries.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SummaryField {
+    /// The number of values, as `len`.
+    Len,
+    /// The smallest value, as `min`.
+    Min,
+    /// The largest value, as `max`.
+    Max,
+    /// The mean of the values, as `mean`.
+    Mean,
+    /// When the message was produced, in seconds since the Unix epoch, as
+    /// `timestamp`.
+    Timestamp,
+}
+
+/// A publisher of summaries of *High Tension Messages* to an MQTT broker,
+/// for dashboards to follow a data stream without receiving it.
+///
+/// The bulk data keeps flowing over `hi-tension`, and every message
+/// received is handed over to [`publish`], which publishes a small JSON
+/// object such as `{"len":4096,"min":-1.5,"max":3,"mean":0.25}` on the
+/// topic. The fields are picked with [`fields`], and [`min_interval`] keeps
+/// the rate low. This requires the `mqtt` feature.
+///
+/// Summaries are published with MQTT 3.1.1, at most once, to a broker
+/// accepting anonymous clients. Values which JSON cannot hold, such as
+/// NaN, are published as `null`.
+///
+/// [`publish`]: #method.publish
+/// [`fields`]: #method.fields
+/// [`min_interval`]: #method.min_interval
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiStream, MqttSummaries};
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut summaries = MqttSummaries::connect("broker.lab:1883", "detector-1", "bl13/det1")?
+///     .min_interval(Duration::from_secs(1));
+///
+/// let (tcp, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+/// let mut stream = HiStream::server(tcp, HiConfig::new().timestamps())?;
+/// loop {
+///     let data = stream.read()?;
+///     summaries.publish(&data, stream.last_timestamp())?;
+///     // Process the data
+/// }
+/// # }
+/// ```
+pub struct MqttSummaries {
+    stream: TcpStream,
+    topic: String,
+    fields: Vec<SummaryField>,
+    min_interval: Option<Duration>,
+    last_published: Option<Instant>,
+}
+
+impl MqttSummaries {
+    /// Connect to the MQTT broker at `addr` as `client_id`, to publish on
+    /// `topic`, with every field but the timestamp.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Handshake`] if the broker refused the connection.
+    ///
+    /// [`Error::Handshake`]: enum.Error.html#variant.Handshake
+    ///
+    /// # Panics
+    ///
+    /// Panics if `client_id` or `topic` is longer than 65535 bytes.
+    pub fn connect(addr: impl ToSocketAddrs, client_id: &str, topic: &str) -> Result<Self> {
+        assert!(u16::try_from(topic.len()).is_ok(), "MQTT topic too long");
+        let mut stream = TcpStream::connect(addr)?;
+
+        // Protocol name and level, clean session, no keep alive
+        let mut connect = vec![0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 0];
+        put_string(&mut connect, client_id);
+        write_packet(&mut stream, CONNECT, &connect)?;
+
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack)?;
+        match connack {
+            [CONNACK, 2, _, 0] => {}
+            [CONNACK, 2, _, code] => {
+                return Err(Error::Handshake(format!(
+                    "MQTT broker refused the connection with code {}",
+                    code
+                )))
+            }
+            _ => return Err(Error::Handshake("invalid MQTT CONNACK".into())),
+        }
+
+        Ok(MqttSummaries {
+            stream,
+            topic: topic.to_owned(),
+            fields: vec![
+                SummaryField::Len,
+                SummaryField::Min,
+                SummaryField::Max,
+                SummaryField::Mean,
+            ],
+            min_interval: None,
+            last_published: None,
+        })
+    }
+
+    /// Publish `fields` in the summaries, in that order.
+    pub fn fields(mut self, fields: &[SummaryField]) -> Self {
+        self.fields = fields.to_vec();
+        self
+    }
+
+    /// Publish a summary at most once per `interval`, skipping the messages
+    /// in between.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = Some(interval);
+        self
+    }
+
+    /// Publish the summary of `data`, produced at `timestamp`, such as given
+    /// by [`HiStream::last_timestamp`], or now if `None`.
+    ///
+    /// This function is blocking, and returns whether the summary was
+    /// published, or skipped because of the [`min_interval`].
+    ///
+    /// [`HiStream::last_timestamp`]: struct.HiStream.html#method.last_timestamp
+    /// [`min_interval`]: #method.min_interval
+    pub fn publish(&mut self, data: &[f64], timestamp: Option<Timestamp>) -> Result<bool> {
+        let now = Instant::now();
+        if let (Some(interval), Some(last)) = (self.min_interval, self.last_published) {
+            if now.duration_since(last) < interval {
+                return Ok(false);
+            }
+        }
+        let payload = self.summary(data, timestamp);
+        let mut publish = Vec::with_capacity(2 + self.topic.len() + payload.len());
+        put_string(&mut publish, &self.topic);
+        publish.extend_from_slice(payload.as_bytes());
+        write_packet(&mut self.stream, PUBLISH, &publish)?;
+        self.last_published = Some(now);
+        Ok(true)
+    }
+
+    /// The summary of `data`, as a JSON object.
+    fn summary(&self, data: &[f64], timestamp: Option<Timestamp>) -> String {
+        let mut summary = String::from("{");
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                summary.push(',');
+            }
+            let (name, value) = match field {
+                SummaryField::Len => ("len", data.len() as f64),
+                SummaryField::Min => ("min", data.iter().copied().fold(f64::NAN, f64::min)),
+                SummaryField::Max => ("max", data.iter().copied().fold(f64::NAN, f64::max)),
+                SummaryField::Mean => ("mean", data.iter().sum::<f64>() / data.len() as f64),
+                SummaryField::Timestamp => {
+                    let wall = timestamp.map_or_else(SystemTime::now, |t| t.wall);
+                    let since_epoch = wall.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    ("timestamp", since_epoch.as_secs_f64())
+                }
+            };
+            if value.is_finite() {
+                let _ = write!(summary, "\"{}\":{}", name, value);
+            } else {
+                let _ = write!(summary, "\"{}\":null", name);
+            }
+        }
+        summary.push('}');
+        summary
+    }
+
+    /// Get a reference to the connection to the broker.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+}
+
+impl Drop for MqttSummaries {
+    fn drop(&mut self) {
+        let _ = write_packet(&mut self.stream, DISCONNECT, &[]);
+    }
+}
+
+impl fmt::Debug for MqttSummaries {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MqttSummaries")
+            .field("topic", &self.topic)
+            .field("fields", &self.fields)
+            .field("min_interval", &self.min_interval)
+            .finish()
+    }
+}
+
+/// Append `s` as an MQTT string, prefixed by its length.
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    let len = u16::try_from(s.len()).expect("MQTT string too long");
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Write an MQTT packet of type `kind`, prefixed by the remaining length of
+/// its `body`.
+fn write_packet<W: Write>(stream: &mut W, kind: u8, body: &[u8]) -> Result<()> {
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    packet.push(kind);
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend_from_slice(body);
+    stream.write_all(&packet)?;
+    stream.flush()?;
+    Ok(())
+}
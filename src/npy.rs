@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::dataset::{finish_file_message, split_file_message, write_contents};
+use crate::{hiread, Error, Result};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Read the preamble of a `.npy` file, from its magic string to the end of
+/// its header, leaving `file` at the start of its data.
+fn read_preamble(file: &mut File) -> Result<Vec<u8>> {
+    let mut preamble = vec![0; 10];
+    file.read_exact(&mut preamble)?;
+    if !preamble.starts_with(MAGIC) {
+        return Err(Error::Invalid("not a .npy file".into()));
+    }
+    let header_len = match preamble[6] {
+        1 => u16::from_le_bytes([preamble[8], preamble[9]]) as usize,
+        2 | 3 => {
+            let mut rest = [0; 2];
+            file.read_exact(&mut rest)?;
+            preamble.extend_from_slice(&rest);
+            u32::from_le_bytes([preamble[8], preamble[9], rest[0], rest[1]]) as usize
+        }
+        version => {
+            return Err(Error::Invalid(format!(
+                "unsupported .npy format version {}",
+                version
+            )))
+        }
+    };
+    let start = preamble.len();
+    preamble.resize(start + header_len, 0);
+    file.read_exact(&mut preamble[start..])?;
+    check_preamble(&preamble)?;
+    Ok(preamble)
+}
+
+/// Check that `preamble` is one of a `.npy` file, whose data starts on a
+/// word boundary.
+fn check_preamble(preamble: &[u8]) -> Result<()> {
+    if !preamble.starts_with(MAGIC) || preamble.last() != Some(&b'\n') {
+        return Err(Error::Invalid("invalid .npy header".into()));
+    }
+    if !preamble.len().is_multiple_of(8) {
+        return Err(Error::Invalid("unaligned .npy data".into()));
+    }
+    Ok(())
+}
+
+/// Send the `.npy` file at `path` as a single *High Tension Message* into the
+/// `stream`, to be written back by [`hiread_to_npy`].
+///
+/// This function is blocking. The data of the file is sent first, as is, by
+/// chunks, followed by its header: the dtype, memory order and shape are
+/// kept to the byte, whatever they are. For arrays of little-endian
+/// `float64`, the message starts with the values themselves.
+///
+/// `.npz` archives are zip files of `.npy` files: extract them first.
+///
+/// [`hiread_to_npy`]: fn.hiread_to_npy.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Invalid`] if the file is not a `.npy` file whose data
+/// is aligned on 8 bytes, as NumPy writes them.
+///
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::hiwrite_npy;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// hiwrite_npy(&mut stream, "spectrum.npy")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_npy<S: Read + Write>(stream: &mut S, path: impl AsRef<Path>) -> Result<()> {
+    let mut file = File::open(path)?;
+    let preamble = read_preamble(&mut file)?;
+    let len = write_contents(stream, &mut file)?;
+    finish_file_message(stream, &preamble, len)
+}
+
+/// Read a message sent by [`hiwrite_npy`] from the `stream`, and write it
+/// into a `.npy` file at `path`, replacing any file already there.
+///
+/// This function is blocking.
+///
+/// [`hiwrite_npy`]: fn.hiwrite_npy.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the message does not hold a file, and
+/// with [`Error::Invalid`] if its header is not one of a `.npy` file.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread_to_npy, hiwrite_npy, pipe};
+/// use std::{env, fs, thread};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// // A 2 by 2 float32 array, as NumPy writes it
+/// let mut npy = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+/// let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 2), }";
+/// npy.extend_from_slice(format!("{:<117}\n", header).as_bytes());
+/// for value in &[1.0f32, 2.0, 3.0, 4.0] {
+///     npy.extend_from_slice(&value.to_le_bytes());
+/// }
+/// let source = env::temp_dir().join(format!("hi-tension-{}.npy", std::process::id()));
+/// fs::write(&source, &npy)?;
+///
+/// let (mut client, mut server) = pipe();
+/// let target = source.with_extension("copy.npy");
+/// let copy = target.clone();
+/// let consumer = thread::spawn(move || hiread_to_npy(&mut server, copy));
+///
+/// hiwrite_npy(&mut client, &source)?;
+/// consumer.join().unwrap()?;
+/// assert_eq!(fs::read(&target)?, npy);
+/// # fs::remove_file(source)?;
+/// # fs::remove_file(target)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_to_npy<S: Read + Write>(stream: &mut S, path: impl AsRef<Path>) -> Result<()> {
+    let message = hiread(stream)?;
+    let (preamble, data) = split_file_message(&message)?;
+    check_preamble(preamble)?;
+    let mut file = File::create(path)?;
+    file.write_all(preamble)?;
+    file.write_all(data)?;
+    Ok(())
+}
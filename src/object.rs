@@ -0,0 +1,157 @@
+use std::io::{Read, Write};
+
+use crate::pod::bytes_of;
+use crate::{hidelimiter, hiread, hiwrite, pack_bytes, Error, HiStream, Result};
+
+/// A serialization format for the objects sent by [`hiwrite_object`].
+///
+/// Configurations and small results then share the connection, and its fast
+/// path, with the arrays. Plugging in a serde format takes a few lines, with
+/// `bincode::serialize` and `bincode::deserialize` for instance.
+///
+/// [`hiwrite_object`]: fn.hiwrite_object.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{Codec, Error, Result};
+///
+/// struct Settings {
+///     gain: f64,
+///     name: String,
+/// }
+///
+/// /// Settings as `gain name` text.
+/// struct SettingsCodec;
+///
+/// impl Codec<Settings> for SettingsCodec {
+///     fn encode(&self, settings: &Settings) -> Result<Vec<u8>> {
+///         Ok(format!("{} {}", settings.gain, settings.name).into_bytes())
+///     }
+///
+///     fn decode(&self, bytes: &[u8]) -> Result<Settings> {
+///         let invalid = || Error::Invalid("malformed settings".into());
+///         let text = std::str::from_utf8(bytes).map_err(|_| invalid())?;
+///         let (gain, name) = text.split_once(' ').ok_or_else(invalid)?;
+///         Ok(Settings {
+///             gain: gain.parse().map_err(|_| invalid())?,
+///             name: name.to_owned(),
+///         })
+///     }
+/// }
+/// ```
+pub trait Codec<T> {
+    /// Serialize `value` into bytes.
+    fn encode(&self, value: &T) -> Result<Vec<u8>>;
+
+    /// Deserialize a value from the `bytes` produced by [`encode`].
+    ///
+    /// [`encode`]: #tymethod.encode
+    fn decode(&self, bytes: &[u8]) -> Result<T>;
+}
+
+/// The identity codec, sending opaque byte payloads as they are.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bytes;
+
+impl Codec<Vec<u8>> for Bytes {
+    fn encode(&self, value: &Vec<u8>) -> Result<Vec<u8>> {
+        Ok(value.clone())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Send `value`, serialized by `codec`, as a *High Tension Message* into the
+/// `stream`.
+///
+/// This function is blocking.
+///
+/// The bytes are padded to whole words and followed by their length, as a
+/// word too.
+///
+/// # Errors
+///
+/// Fails with the error of `codec` if `value` cannot be serialized, before
+/// anything is sent.
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread_object, hiwrite_object, pipe, Bytes};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || hiread_object(&mut server, &Bytes));
+///
+/// hiwrite_object(&mut client, &b"run 42".to_vec(), &Bytes)?;
+/// assert_eq!(consumer.join().unwrap()?, b"run 42");
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_object<S, T, C>(stream: &mut S, value: &T, codec: &C) -> Result<()>
+where
+    S: Read + Write,
+    C: Codec<T>,
+{
+    hiwrite(stream, &encode(value, codec)?)?;
+    hidelimiter(stream)
+}
+
+/// Read an object sent by [`hiwrite_object`], deserialized by `codec`.
+///
+/// This function is blocking.
+///
+/// [`hiwrite_object`]: fn.hiwrite_object.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the message does not hold an object, and
+/// with the error of `codec` if it cannot be deserialized.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+pub fn hiread_object<S, T, C>(stream: &mut S, codec: &C) -> Result<T>
+where
+    S: Read + Write,
+    C: Codec<T>,
+{
+    decode(&hiread(stream)?, codec)
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Send `value`, serialized by `codec`, as a single *High Tension
+    /// Message*, like [`hiwrite_object`].
+    ///
+    /// [`hiwrite_object`]: fn.hiwrite_object.html
+    pub fn send_object<T, C: Codec<T>>(&mut self, value: &T, codec: &C) -> Result<()> {
+        self.send(&encode(value, codec)?)
+    }
+
+    /// Read an object sent by [`send_object`], deserialized by `codec`.
+    ///
+    /// [`send_object`]: #method.send_object
+    pub fn recv_object<T, C: Codec<T>>(&mut self, codec: &C) -> Result<T> {
+        decode(&self.read()?, codec)
+    }
+}
+
+fn encode<T, C: Codec<T>>(value: &T, codec: &C) -> Result<Vec<f64>> {
+    let bytes = codec.encode(value)?;
+    let mut words = pack_bytes(&bytes);
+    words.push(f64::from_bits(bytes.len() as u64));
+    Ok(words)
+}
+
+fn decode<T, C: Codec<T>>(words: &[f64], codec: &C) -> Result<T> {
+    let malformed = || Error::Framing("malformed object".into());
+    let (len, words) = words.split_last().ok_or_else(malformed)?;
+    let len = len.to_bits() as usize;
+    let bytes = bytes_of(words);
+    if len > bytes.len() || bytes.len() - len >= 8 {
+        return Err(malformed());
+    }
+    codec.decode(&bytes[..len])
+}
@@ -0,0 +1,205 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{connect, hidelimiter, hiread, hiwrite, read_exact_into, Error, Result};
+
+/// Connections accepted for a single message by [`hiread_parallel`].
+const MAX_CONNECTIONS: usize = 256;
+
+/// Time [`hiread_parallel`] waits for each temporary connection to be
+/// opened, and for each read on them.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn word(n: usize) -> f64 {
+    f64::from_bits(n as u64)
+}
+
+/// Bounds of the `i`-th of `k` contiguous segments of `len` values.
+fn segment(len: usize, k: usize, i: usize) -> (usize, usize) {
+    let bound = |i: usize| (len as u128 * i as u128 / k as u128) as usize;
+    (bound(i), bound(i + 1))
+}
+
+/// Accept a connection on `listener`, failing with a timeout once
+/// `deadline` passed.
+fn accept_before(listener: &TcpListener, deadline: Instant) -> Result<TcpStream> {
+    listener.set_nonblocking(true)?;
+    let accepted = loop {
+        match listener.accept() {
+            Ok((tcp, _)) => break Ok(tcp),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    break Err(io::Error::from(io::ErrorKind::TimedOut));
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => break Err(e),
+        }
+    };
+    listener.set_nonblocking(false)?;
+    let tcp = accepted?;
+    tcp.set_nonblocking(false)?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    Ok(tcp)
+}
+
+/// Send a huge `data` slice as a single message over `connections`
+/// temporary TCP connections to `addr`, each carrying a contiguous segment.
+///
+/// This function is blocking.
+///
+/// The message is announced on the `stream`, a connection to the same peer,
+/// which accepts the temporary connections on `addr` with
+/// [`hiread_parallel`]. A single TCP connection seldom fills a long fat
+/// network on its own: spreading one message over several ones uses the
+/// bandwidth left by the congestion control of each, without striping the
+/// whole session.
+///
+/// [`hiread_parallel`]: fn.hiread_parallel.html
+///
+/// # Panics
+///
+/// Panics if `connections` is zero, or more than 256.
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread_parallel, hiwrite_parallel, pipe};
+/// use std::net::TcpListener;
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let listener = TcpListener::bind("127.0.0.1:0")?;
+/// let addr = listener.local_addr()?;
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || hiread_parallel(&mut server, &listener));
+///
+/// let data: Vec<f64> = (0..1_000_000).map(f64::from).collect();
+/// hiwrite_parallel(&mut client, addr, &data, 4)?;
+/// assert_eq!(consumer.join().unwrap()?, data);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_parallel<S: Read + Write>(
+    stream: &mut S,
+    addr: impl ToSocketAddrs,
+    data: &[f64],
+    connections: usize,
+) -> Result<()> {
+    assert!(
+        (1..=MAX_CONNECTIONS).contains(&connections),
+        "messages are sent over 1 to 256 connections"
+    );
+    let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+    // Tells the temporary connections of this message from stray ones
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_nanos() as u64);
+    hiwrite(
+        stream,
+        &[f64::from_bits(id), word(data.len()), word(connections)],
+    )?;
+    hidelimiter(stream)?;
+
+    thread::scope(|scope| {
+        let senders: Vec<_> = (0..connections)
+            .map(|i| {
+                let addrs = addrs.clone();
+                let (start, end) = segment(data.len(), connections, i);
+                let segment = &data[start..end];
+                scope.spawn(move || -> Result<()> {
+                    let mut tcp = connect::race(addrs, None)?;
+                    hiwrite(&mut tcp, &[f64::from_bits(id), word(i)])?;
+                    hidelimiter(&mut tcp)?;
+                    hiwrite(&mut tcp, segment)?;
+                    hidelimiter(&mut tcp)
+                })
+            })
+            .collect();
+        senders
+            .into_iter()
+            .try_for_each(|sender| sender.join().unwrap())
+    })
+}
+
+/// Read a message sent by [`hiwrite_parallel`], announced on the `stream`,
+/// accepting its temporary connections on `listener`.
+///
+/// This function is blocking, and allocates the whole message once: every
+/// segment is written in place, as it arrives.
+///
+/// Messages sent in parallel must be read one at a time on a `listener`.
+/// Every temporary connection must be opened within 30 seconds of the
+/// previous one, and none may stay silent longer than that.
+///
+/// [`hiwrite_parallel`]: fn.hiwrite_parallel.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the announcement is malformed, or if a
+/// connection accepted does not carry a segment of the announced message,
+/// and with an IO error of kind `TimedOut` if a sender is missing or stalls.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+pub fn hiread_parallel<S: Read + Write>(
+    stream: &mut S,
+    listener: &TcpListener,
+) -> Result<Vec<f64>> {
+    let malformed = || Error::Framing("malformed parallel message".into());
+    let announcement = hiread(stream)?;
+    let (id, len, connections) = match announcement[..] {
+        [id, len, connections] => (
+            id.to_bits(),
+            len.to_bits() as usize,
+            connections.to_bits() as usize,
+        ),
+        _ => return Err(malformed()),
+    };
+    if !(1..=MAX_CONNECTIONS).contains(&connections) || len > isize::MAX as usize / 8 {
+        return Err(malformed());
+    }
+
+    let mut data = Vec::new();
+    data.try_reserve_exact(len)
+        .map_err(|_| Error::Framing("parallel message too large to allocate".into()))?;
+    data.resize(len, 0.0);
+    let mut segments = Vec::with_capacity(connections);
+    let mut rest = &mut data[..];
+    for i in 0..connections {
+        let (start, end) = segment(len, connections, i);
+        let (segment, tail) = rest.split_at_mut(end - start);
+        segments.push(Some(segment));
+        rest = tail;
+    }
+    let segments = Mutex::new(segments);
+
+    thread::scope(|scope| {
+        let receivers: Vec<_> = (0..connections)
+            .map(|_| -> Result<_> {
+                let mut tcp = accept_before(listener, Instant::now() + TIMEOUT)?;
+                let segments = &segments;
+                Ok(scope.spawn(move || -> Result<()> {
+                    let header = hiread(&mut tcp)?;
+                    let segment = match header[..] {
+                        [peer_id, i] if peer_id.to_bits() == id => {
+                            let mut segments = segments.lock().unwrap_or_else(|e| e.into_inner());
+                            segments
+                                .get_mut(i.to_bits() as usize)
+                                .and_then(Option::take)
+                        }
+                        _ => None,
+                    };
+                    read_exact_into(&mut tcp, segment.ok_or_else(malformed)?)
+                }))
+            })
+            .collect::<Result<_>>()?;
+        receivers
+            .into_iter()
+            .try_for_each(|receiver| receiver.join().unwrap())
+    })?;
+    Ok(data)
+}
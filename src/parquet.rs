@@ -0,0 +1,341 @@
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::pod::bytes_of;
+use crate::{hiread, is_end, Error, Result};
+
+const MAGIC: &[u8] = b"PAR1";
+
+/// Values per data page, bounding the size of the pages.
+const PAGE_VALUES: usize = 131_072;
+
+// Parquet enumerations
+const DOUBLE: i32 = 5;
+const REQUIRED: i32 = 0;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+// Thrift compact protocol types
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+/// A Thrift compact protocol encoder, for the few structures of a Parquet
+/// file.
+#[derive(Default)]
+struct Compact {
+    buf: Vec<u8>,
+    last_ids: Vec<i16>,
+    last_id: i16,
+}
+
+impl Compact {
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.buf.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.buf.push(n as u8);
+    }
+
+    fn zigzag(&mut self, n: i64) {
+        self.varint(((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.last_id;
+        if 0 < delta && delta <= 15 {
+            self.buf.push((delta as u8) << 4 | kind);
+        } else {
+            self.buf.push(kind);
+            self.zigzag(id.into());
+        }
+        self.last_id = id;
+    }
+
+    fn i32(&mut self, id: i16, n: i32) {
+        self.field(id, I32);
+        self.zigzag(n.into());
+    }
+
+    fn i64(&mut self, id: i16, n: i64) {
+        self.field(id, I64);
+        self.zigzag(n);
+    }
+
+    fn binary(&mut self, id: i16, bytes: &[u8]) {
+        self.field(id, BINARY);
+        self.bytes(bytes);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, LIST);
+        self.list_header(kind, len);
+    }
+
+    fn list_header(&mut self, kind: u8, len: usize) {
+        if len < 15 {
+            self.buf.push((len as u8) << 4 | kind);
+        } else {
+            self.buf.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Start a structure, as a field if `id` is given, or as a list element.
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, STRUCT);
+        }
+        self.last_ids.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    fn end(&mut self) {
+        self.buf.push(0);
+        self.last_id = self.last_ids.pop().unwrap_or(0);
+    }
+}
+
+/// Where the pages of a column chunk were written.
+struct ColumnChunk {
+    offset: u64,
+    size: u64,
+    values: u64,
+}
+
+struct RowGroup {
+    columns: Vec<ColumnChunk>,
+    rows: u64,
+}
+
+/// A sink writing the arrays received as record batches to a Parquet file,
+/// one row group per batch, as they arrive.
+///
+/// Each batch is a row-major array of whole rows, such as a message read
+/// with [`hiread`] or [`HiStream::read`]. Columns are required `DOUBLE`
+/// values, stored uncompressed with the plain encoding, which any Parquet
+/// reader supports. The file is only readable once the sink is finished with
+/// [`finish`], writing its footer.
+///
+/// [`hiread`]: fn.hiread.html
+/// [`HiStream::read`]: struct.HiStream.html#method.read
+/// [`finish`]: #method.finish
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::ParquetSink;
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let file = BufWriter::new(File::create("events.parquet")?);
+/// let mut sink = ParquetSink::new(file, &["time", "energy", "angle"])?;
+///
+/// while sink.receive(&mut stream)? > 0 {}
+/// sink.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ParquetSink<W: Write> {
+    writer: W,
+    names: Vec<String>,
+    position: u64,
+    row_groups: Vec<RowGroup>,
+    column: Vec<f64>,
+}
+
+impl<W: Write> ParquetSink<W> {
+    /// Create a sink writing rows with one value per name of `names` into
+    /// `writer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `names` is empty.
+    pub fn new(mut writer: W, names: &[&str]) -> Result<Self> {
+        assert!(!names.is_empty(), "Parquet rows hold at least one column");
+        writer.write_all(MAGIC)?;
+        Ok(ParquetSink {
+            writer,
+            names: names.iter().map(|&name| name.to_owned()).collect(),
+            position: MAGIC.len() as u64,
+            row_groups: Vec::new(),
+            column: Vec::new(),
+        })
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Write the rows of the row-major record batch `data` as a row group.
+    ///
+    /// Empty batches write nothing.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Invalid`] if `data` does not hold whole rows, and
+    /// then writes nothing.
+    ///
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    pub fn write_batch(&mut self, data: &[f64]) -> Result<()> {
+        let columns = self.names.len();
+        if !data.len().is_multiple_of(columns) {
+            return Err(Error::Invalid(format!(
+                "batch of {} values, not rows of {} columns",
+                data.len(),
+                columns
+            )));
+        }
+        let rows = data.len() / columns;
+        if rows == 0 {
+            return Ok(());
+        }
+        let mut chunks = Vec::with_capacity(columns);
+        for j in 0..columns {
+            let mut column = std::mem::take(&mut self.column);
+            column.clear();
+            column.extend(data[j..].iter().step_by(columns));
+            let offset = self.position;
+            for page in column.chunks(PAGE_VALUES) {
+                self.write_page(page)?;
+            }
+            self.column = column;
+            chunks.push(ColumnChunk {
+                offset,
+                size: self.position - offset,
+                values: rows as u64,
+            });
+        }
+        self.row_groups.push(RowGroup {
+            columns: chunks,
+            rows: rows as u64,
+        });
+        Ok(())
+    }
+
+    fn write_page(&mut self, values: &[f64]) -> Result<()> {
+        let size = (values.len() * 8) as i32;
+        let mut header = Compact::default();
+        header.i32(1, DATA_PAGE);
+        header.i32(2, size);
+        header.i32(3, size);
+        header.begin(Some(5));
+        header.i32(1, values.len() as i32);
+        header.i32(2, PLAIN);
+        header.i32(3, RLE);
+        header.i32(4, RLE);
+        header.end();
+        header.end();
+        self.put(&header.buf)?;
+        self.put(bytes_of(values))
+    }
+
+    /// Read a *High Tension Message* from the `stream`, and write it as a
+    /// record batch.
+    ///
+    /// This function is blocking, and returns the number of rows written. An
+    /// empty message, sent by [`hiempty`], or the end of a dataset, sent by
+    /// [`hiend`], writes none: either signals the end of the data.
+    ///
+    /// [`hiempty`]: fn.hiempty.html
+    /// [`hiend`]: fn.hiend.html
+    pub fn receive<S: Read + Write>(&mut self, stream: &mut S) -> Result<usize> {
+        let data = hiread(stream)?;
+        if is_end(&data) {
+            return Ok(0);
+        }
+        self.write_batch(&data)?;
+        Ok(data.len() / self.names.len())
+    }
+
+    /// Number of rows written so far.
+    pub fn rows(&self) -> u64 {
+        self.row_groups.iter().map(|group| group.rows).sum()
+    }
+
+    /// Write the footer of the file, describing its schema and row groups,
+    /// and give the writer back.
+    pub fn finish(mut self) -> Result<W> {
+        let footer = self.footer();
+        self.put(&footer)?;
+        self.put(&(footer.len() as u32).to_le_bytes())?;
+        self.put(MAGIC)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Encode the `FileMetaData` structure of the file.
+    fn footer(&self) -> Vec<u8> {
+        let mut meta = Compact::default();
+        meta.i32(1, 1);
+
+        meta.list(2, STRUCT, self.names.len() + 1);
+        meta.begin(None);
+        meta.binary(4, b"schema");
+        meta.i32(5, self.names.len() as i32);
+        meta.end();
+        for name in &self.names {
+            meta.begin(None);
+            meta.i32(1, DOUBLE);
+            meta.i32(3, REQUIRED);
+            meta.binary(4, name.as_bytes());
+            meta.end();
+        }
+
+        meta.i64(3, self.rows() as i64);
+        meta.list(4, STRUCT, self.row_groups.len());
+        for group in &self.row_groups {
+            meta.begin(None);
+            meta.list(1, STRUCT, group.columns.len());
+            for (chunk, name) in group.columns.iter().zip(&self.names) {
+                meta.begin(None);
+                meta.i64(2, chunk.offset as i64);
+                meta.begin(Some(3));
+                meta.i32(1, DOUBLE);
+                meta.list(2, I32, 1);
+                meta.zigzag(PLAIN.into());
+                meta.list(3, BINARY, 1);
+                meta.bytes(name.as_bytes());
+                meta.i32(4, UNCOMPRESSED);
+                meta.i64(5, chunk.values as i64);
+                meta.i64(6, chunk.size as i64);
+                meta.i64(7, chunk.size as i64);
+                meta.i64(9, chunk.offset as i64);
+                meta.end();
+                meta.end();
+            }
+            let size = group.columns.iter().map(|chunk| chunk.size).sum::<u64>();
+            meta.i64(2, size as i64);
+            meta.i64(3, group.rows as i64);
+            meta.end();
+        }
+        meta.binary(6, b"hi-tension");
+        meta.end();
+        meta.buf
+    }
+}
+
+impl<W: Write> fmt::Debug for ParquetSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParquetSink")
+            .field("names", &self.names)
+            .field("row_groups", &self.row_groups.len())
+            .field("rows", &self.rows())
+            .finish()
+    }
+}
@@ -0,0 +1,148 @@
+//! Deterministic test patterns, to validate a link before trusting it with
+//! real data.
+//!
+//! [`send`] generates a pseudo-random array from a seed, and [`verify`]
+//! regenerates it on the other side to check every value. Running both in a
+//! loop overnight catches flaky cables, NICs or switches before an experiment
+//! does.
+//!
+//! The seed travels in the message, as its first two values, so that the
+//! receiver needs no configuration. The other values are uniform in `[0, 1)`,
+//! and never NaN.
+//!
+//! [`send`]: fn.send.html
+//! [`verify`]: fn.verify.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use hi_tension::pattern;
+//! use std::net::TcpStream;
+//!
+//! # fn main() -> hi_tension::Result<()> {
+//! let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+//!
+//! for seed in 0.. {
+//!     pattern::send(&mut stream, seed, 10_000_000)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::{hidelimiter, hiwrite, read_chunks, Error, Result, CHUNK_SIZE};
+
+/// The SplitMix64 generator: tiny, fast, and good enough to shake out
+/// corrupted bits.
+#[derive(Debug)]
+pub(crate) struct Generator(u64);
+
+impl Generator {
+    pub(crate) fn new(seed: u64) -> Self {
+        Generator(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value uniform in `[0, 1)`, from the 53 high bits.
+    pub(crate) fn next(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Send a *High Tension Message* of `len` pseudo-random values generated from
+/// `seed`, to be checked with [`verify`].
+///
+/// This function is blocking. The values are generated and sent one chunk at
+/// a time, so that arrays larger than memory can be sent.
+///
+/// [`verify`]: fn.verify.html
+pub fn send<S: Read + Write>(stream: &mut S, seed: u64, len: usize) -> Result<()> {
+    // Halves of the seed are exact as floats, and never a NaN
+    let header = [(seed >> 32) as f64, (seed & 0xffff_ffff) as f64];
+    hiwrite(stream, &header)?;
+
+    let mut generator = Generator::new(seed);
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE.min(len));
+    let mut left = len;
+    while left > 0 {
+        let n = left.min(CHUNK_SIZE);
+        chunk.clear();
+        chunk.extend((0..n).map(|_| generator.next()));
+        hiwrite(stream, &chunk)?;
+        left -= n;
+    }
+    hidelimiter(stream)
+}
+
+/// Receive a *High Tension Message* sent by [`send`], and check that every
+/// value is the expected one. Returns its number of values.
+///
+/// This function is blocking. The message is checked one chunk at a time,
+/// without collecting it.
+///
+/// [`send`]: fn.send.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Invalid`] on the first value which differs, describing
+/// it along with its index, or if the message is too short to hold a seed.
+/// The rest of the message is still received, so that the stream stays usable
+/// for the next messages.
+///
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::pattern;
+/// use std::net::TcpListener;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut stream, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+///
+/// let mut total = 0;
+/// loop {
+///     total += pattern::verify(&mut stream)?;
+///     println!("{} values verified", total);
+/// }
+/// # }
+/// ```
+pub fn verify<S: Read + Write>(stream: &mut S) -> Result<usize> {
+    let mut header = Vec::with_capacity(2);
+    let mut generator = None;
+    let mut index = 0;
+    let mut invalid = None;
+    read_chunks(stream, |mut values| {
+        if generator.is_none() {
+            let n = values.len().min(2 - header.len());
+            header.extend_from_slice(&values[..n]);
+            values = &values[n..];
+            if let [hi, lo] = header[..] {
+                generator = Some(Generator::new(((hi as u64) << 32) | lo as u64));
+            }
+        }
+        if let (Some(generator), None) = (&mut generator, &invalid) {
+            invalid = values.iter().enumerate().find_map(|(i, &x)| {
+                let expected = generator.next();
+                (x.to_bits() != expected.to_bits())
+                    .then(|| format!("value {} instead of {} at index {}", x, expected, index + i))
+            });
+        }
+        index += values.len();
+        Ok(())
+    })?;
+
+    match (generator, invalid) {
+        (None, _) => Err(Error::Invalid("pattern message without seed".into())),
+        (_, Some(reason)) => Err(Error::Invalid(reason)),
+        _ => Ok(index),
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// Text message notifying the peer that sending is paused, with
+/// [`HiConfig::typed_messages`].
+///
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+pub const PAUSED_TEXT: &str = "hi-tension paused";
+
+/// Text message notifying the peer that sending resumed, with
+/// [`HiConfig::typed_messages`].
+///
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+pub const RESUMED_TEXT: &str = "hi-tension resumed";
+
+#[derive(Default)]
+struct State {
+    paused: Mutex<bool>,
+    changed: Condvar,
+}
+
+/// A switch pausing the messages sent by a [`HiStream`], from any thread.
+///
+/// Get it with [`HiStream::pause_handle`]. While paused, the stream stalls at
+/// the start of its next *High Tension Message*, so that the network is freed
+/// at a message boundary: the message being written, if any, is completed
+/// first. Text messages keep flowing.
+///
+/// With [`HiConfig::typed_messages`], the stream notifies the peer with a
+/// [`PAUSED_TEXT`] text message when it stalls, and with [`RESUMED_TEXT`]
+/// when it carries on.
+///
+/// `SendPause` is a cheap handle: clones refer to the same switch.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`HiStream::pause_handle`]: struct.HiStream.html#method.pause_handle
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+/// [`PAUSED_TEXT`]: constant.PAUSED_TEXT.html
+/// [`RESUMED_TEXT`]: constant.RESUMED_TEXT.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, HiConfig, HiStream, Message, PAUSED_TEXT, RESUMED_TEXT};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<Message>> {
+///     let mut stream = HiStream::server(server, HiConfig::new().typed_messages())?;
+///     (0..3).map(|_| stream.recv()).collect()
+/// });
+///
+/// let mut stream = HiStream::client(client, HiConfig::new().typed_messages())?;
+/// let pause = stream.pause_handle();
+/// pause.pause();
+/// let operator = thread::spawn(move || {
+///     thread::sleep(Duration::from_millis(10));
+///     pause.resume();
+/// });
+///
+/// // Stalls until the operator resumes sending
+/// stream.send(&[1.0, 2.0])?;
+/// operator.join().unwrap();
+///
+/// let messages = consumer.join().unwrap()?;
+/// assert_eq!(
+///     messages,
+///     [
+///         Message::Text(PAUSED_TEXT.into()),
+///         Message::Text(RESUMED_TEXT.into()),
+///         Message::Array(vec![1.0, 2.0]),
+///     ]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct SendPause {
+    state: Arc<State>,
+}
+
+impl SendPause {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, bool> {
+        self.state.paused.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Pause sending, from the next message boundary on.
+    pub fn pause(&self) {
+        *self.lock() = true;
+    }
+
+    /// Resume sending, waking up the stream if it stalled.
+    pub fn resume(&self) {
+        *self.lock() = false;
+        self.state.changed.notify_all();
+    }
+
+    /// Whether sending is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.lock()
+    }
+
+    /// Block while paused.
+    pub(crate) fn wait(&self) {
+        let mut paused = self.lock();
+        while *paused {
+            paused = self
+                .state
+                .changed
+                .wait(paused)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl fmt::Debug for SendPause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SendPause")
+            .field("paused", &self.is_paused())
+            .finish()
+    }
+}
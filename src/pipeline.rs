@@ -0,0 +1,157 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+use crate::verified::checksum;
+use crate::{hidelimiter, hiread, hiwrite, Error, Result};
+
+/// Chunks held between two stages of [`hiwrite_pipelined`].
+///
+/// [`hiwrite_pipelined`]: fn.hiwrite_pipelined.html
+const PIPELINE_DEPTH: usize = 4;
+
+/// Send the `chunks` as a single *High Tension Message* into the `stream`,
+/// each encoded by `encode` and followed by its CRC-32, the stages running
+/// on threads of their own.
+///
+/// This function is blocking, and returns the number of chunks sent.
+///
+/// The send path is a pipeline: the chunks are computed by iterating over
+/// `chunks` on a first thread, encoded by `encode` on a second one, such as
+/// by a compression codec, checksummed on a third one, and written on the
+/// calling thread. At most 4 chunks wait between two stages, bounding the
+/// memory used. CPU-heavy codecs thus overlap with the socket writes, instead
+/// of holding them up, and the message is sent at the pace of the slowest
+/// stage. The receiver checks and decodes the chunks with
+/// [`hiread_pipelined`].
+///
+/// [`hiread_pipelined`]: fn.hiread_pipelined.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread_pipelined, hiwrite_pipelined, pipe};
+/// use std::thread;
+///
+/// // A toy codec, sending each value as a difference to the previous one
+/// fn delta(mut chunk: Vec<f64>) -> Vec<f64> {
+///     for i in (1..chunk.len()).rev() {
+///         chunk[i] -= chunk[i - 1];
+///     }
+///     chunk
+/// }
+/// fn undelta(mut chunk: Vec<f64>) -> Vec<f64> {
+///     for i in 1..chunk.len() {
+///         chunk[i] += chunk[i - 1];
+///     }
+///     chunk
+/// }
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || hiread_pipelined(&mut server, undelta));
+///
+/// let chunks = (0..16).map(|i| (0..4096).map(|j| f64::from(i * 4096 + j)).collect());
+/// assert_eq!(hiwrite_pipelined(&mut client, chunks, delta)?, 16);
+///
+/// let data = consumer.join().unwrap()?;
+/// assert_eq!(data, (0..65536).map(f64::from).collect::<Vec<_>>());
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_pipelined<S, I, E>(stream: &mut S, chunks: I, encode: E) -> Result<usize>
+where
+    S: Read + Write,
+    I: IntoIterator<Item = Vec<f64>>,
+    I::IntoIter: Send,
+    E: Fn(Vec<f64>) -> Vec<f64> + Send,
+{
+    let chunks = chunks.into_iter();
+    let (computed, to_encode) = sync_channel(PIPELINE_DEPTH);
+    let (encoded, to_check) = sync_channel::<Vec<f64>>(PIPELINE_DEPTH);
+    let (checked, to_write) = sync_channel(PIPELINE_DEPTH);
+
+    thread::scope(|scope| {
+        // Each stage stops as soon as the next one hangs up, after an error
+        scope.spawn(move || {
+            for chunk in chunks {
+                if computed.send(chunk).is_err() {
+                    break;
+                }
+            }
+        });
+        scope.spawn(move || {
+            for chunk in to_encode {
+                if encoded.send(encode(chunk)).is_err() {
+                    break;
+                }
+            }
+        });
+        scope.spawn(move || {
+            for chunk in to_check {
+                let trailer = [f64::from_bits(chunk.len() as u64), checksum(&chunk)];
+                if checked.send((chunk, trailer)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut sent = 0;
+        for (chunk, trailer) in to_write {
+            hiwrite(stream, &chunk)?;
+            hiwrite(stream, &trailer)?;
+            sent += 1;
+        }
+        hidelimiter(stream)?;
+        Ok(sent)
+    })
+}
+
+/// Read a *High Tension Message* sent by [`hiwrite_pipelined`], checking
+/// each of its chunks and decoding it with `decode`.
+///
+/// This function is blocking, and returns the decoded chunks put together.
+///
+/// [`hiwrite_pipelined`]: fn.hiwrite_pipelined.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the message does not hold checksummed
+/// chunks, and with [`Error::Invalid`] if a chunk was corrupted.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+pub fn hiread_pipelined<S, D>(stream: &mut S, decode: D) -> Result<Vec<f64>>
+where
+    S: Read + Write,
+    D: Fn(Vec<f64>) -> Vec<f64>,
+{
+    let malformed = || Error::Framing("malformed pipelined chunks".into());
+    let message = hiread(stream)?;
+
+    // Each chunk is followed by its length and checksum: walk them backwards
+    let mut chunks = Vec::new();
+    let mut rest = &message[..];
+    while let [body @ .., len, crc] = rest {
+        let start = usize::try_from(len.to_bits())
+            .ok()
+            .and_then(|len| body.len().checked_sub(len))
+            .ok_or_else(malformed)?;
+        let chunk = &body[start..];
+        if checksum(chunk).to_bits() != crc.to_bits() {
+            return Err(Error::Invalid("corrupted pipelined chunk".into()));
+        }
+        chunks.push(chunk);
+        rest = &body[..start];
+    }
+    if !rest.is_empty() {
+        return Err(malformed());
+    }
+
+    let mut data = Vec::new();
+    for chunk in chunks.into_iter().rev() {
+        data.extend(decode(chunk.to_vec()));
+    }
+    Ok(data)
+}
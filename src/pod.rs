@@ -0,0 +1,106 @@
+use std::mem;
+
+/// Plain old data: types which can be sent as their raw bytes.
+///
+/// This is what [`TypedSender`] ships, so that slices of `#[repr(C)]` records
+/// travel without conversion, and the only kind of values the crate ever
+/// views as bytes.
+///
+/// [`TypedSender`]: struct.TypedSender.html
+///
+/// # Safety
+///
+/// Implementors must be inhabited, `Copy`, and free of padding bytes,
+/// pointers and references, and any bit pattern must be a valid value. For a
+/// struct, that means `#[repr(C)]` or `#[repr(transparent)]`, with fields
+/// which are all `Pod`, laid out without gaps.
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::Pod;
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Particle {
+///     position: [f64; 3],
+///     velocity: [f32; 3],
+///     id: u32,
+/// }
+///
+/// // SAFETY: `repr(C)`, 40 bytes of `Pod` fields without padding
+/// unsafe impl Pod for Particle {}
+/// ```
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(
+            // SAFETY: primitive numbers have no padding, and no invalid values
+            unsafe impl Pod for $t {}
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+// SAFETY: arrays have no padding between their elements
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// View the values of `v` as bytes.
+pub(crate) fn bytes_of<T: Pod>(v: &[T]) -> &[u8] {
+    // SAFETY: `Pod` values have no padding, so all their bytes are initialized
+    unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, mem::size_of_val(v)) }
+}
+
+/// View the values of `v` as mutable bytes.
+pub(crate) fn bytes_of_mut<T: Pod>(v: &mut [T]) -> &mut [u8] {
+    // SAFETY: any bytes written make valid `Pod` values
+    unsafe { std::slice::from_raw_parts_mut(v.as_mut_ptr() as *mut u8, mem::size_of_val(v)) }
+}
+
+/// View the values of `v` as values of type `B`, if its bytes are aligned
+/// for them, and make whole values.
+pub(crate) fn try_cast_slice<A: Pod, B: Pod>(v: &[A]) -> Option<&[B]> {
+    let bytes = bytes_of(v);
+    let size = mem::size_of::<B>();
+    if size == 0
+        || !bytes.len().is_multiple_of(size)
+        || !(bytes.as_ptr() as usize).is_multiple_of(mem::align_of::<B>())
+    {
+        return None;
+    }
+    // SAFETY: the bytes are aligned for `B`, make whole values, and any bytes
+    // make valid `Pod` values
+    Some(unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const B, bytes.len() / size) })
+}
+
+/// Turn `v` into a vector of values of type `B` without copies, if its
+/// allocation suits them, or give it back.
+pub(crate) fn try_cast_vec<A: Pod, B: Pod>(v: Vec<A>) -> std::result::Result<Vec<B>, Vec<A>> {
+    let (from, to) = (mem::size_of::<A>(), mem::size_of::<B>());
+    if mem::align_of::<A>() != mem::align_of::<B>()
+        || from == 0
+        || to == 0
+        || !(v.len() * from).is_multiple_of(to)
+        || !(v.capacity() * from).is_multiple_of(to)
+    {
+        return Err(v);
+    }
+    let mut v = mem::ManuallyDrop::new(v);
+    let (len, capacity) = (v.len() * from / to, v.capacity() * from / to);
+    // SAFETY: the allocation keeps its size and alignment, and its bytes
+    // make valid `Pod` values
+    Ok(unsafe { Vec::from_raw_parts(v.as_mut_ptr() as *mut B, len, capacity) })
+}
+
+/// Read a value from its `bytes`, wherever they are.
+///
+/// # Panics
+///
+/// Panics if `bytes` does not hold exactly one value.
+pub(crate) fn read_unaligned<T: Pod>(bytes: &[u8]) -> T {
+    assert_eq!(bytes.len(), mem::size_of::<T>(), "bytes of another type");
+    // SAFETY: the bytes make a valid `Pod` value, read without alignment
+    unsafe { (bytes.as_ptr() as *const T).read_unaligned() }
+}
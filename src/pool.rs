@@ -0,0 +1,215 @@
+use std::fmt;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::{connect, HiConfig, HiStream, Result};
+
+#[derive(Debug)]
+struct State {
+    idle: Vec<HiStream<TcpStream>>,
+    open: usize,
+}
+
+struct Shared {
+    addrs: Vec<SocketAddr>,
+    config: HiConfig,
+    size: usize,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+/// A pool of at most `size` connections to the same server.
+///
+/// Each thread producing data checks a [`HiStream`] out of the pool with
+/// [`checkout`], uses it exclusively, and gives it back by dropping it. The
+/// connections are opened on demand and reused afterwards, so tasks do not pay
+/// for a handshake each, and streams are never shared between threads.
+///
+/// `HiPool` is a cheap handle: clones refer to the same pool.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`checkout`]: #method.checkout
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiPool};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let pool = HiPool::new("127.0.0.1:34567", HiConfig::new(), 4)?;
+///
+/// let producers: Vec<_> = (0..16)
+///     .map(|i| {
+///         let pool = pool.clone();
+///         thread::spawn(move || -> hi_tension::Result<()> {
+///             let mut stream = pool.checkout()?;
+///             stream.send(&vec![i as f64; 1_000_000])
+///         })
+///     })
+///     .collect();
+/// for producer in producers {
+///     producer.join().unwrap()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HiPool {
+    shared: Arc<Shared>,
+}
+
+impl HiPool {
+    /// Create a pool of at most `size` connections to `addr`, each opened with
+    /// [`HiStream::client`] using `config`.
+    ///
+    /// No connection is opened yet, but `addr` is resolved right away.
+    ///
+    /// [`HiStream::client`]: struct.HiStream.html#method.client
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(addr: impl ToSocketAddrs, config: HiConfig, size: usize) -> Result<Self> {
+        assert!(size > 0, "a pool needs room for at least one connection");
+        Ok(HiPool {
+            shared: Arc::new(Shared {
+                addrs: addr.to_socket_addrs()?.collect(),
+                config,
+                size,
+                state: Mutex::new(State {
+                    idle: Vec::new(),
+                    open: 0,
+                }),
+                returned: Condvar::new(),
+            }),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get a connection for the exclusive use of the caller.
+    ///
+    /// This function is blocking: when all the connections of the pool are in
+    /// use, it waits for one to be given back.
+    ///
+    /// An idle connection is reused if there is one. Otherwise a new one is
+    /// opened, as long as the pool is not full.
+    pub fn checkout(&self) -> Result<PooledStream> {
+        let mut state = self.lock();
+        loop {
+            if let Some(stream) = state.idle.pop() {
+                return Ok(self.pooled(stream));
+            }
+            if state.open < self.shared.size {
+                break;
+            }
+            state = self
+                .shared
+                .returned
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        state.open += 1;
+        drop(state);
+
+        match self.connect() {
+            Ok(stream) => Ok(self.pooled(stream)),
+            Err(e) => {
+                self.release(None);
+                Err(e)
+            }
+        }
+    }
+
+    fn connect(&self) -> Result<HiStream<TcpStream>> {
+        let tcp = connect::open(self.shared.addrs.clone(), None, &self.shared.config)?;
+        HiStream::client(tcp, self.shared.config.clone())
+    }
+
+    fn pooled(&self, stream: HiStream<TcpStream>) -> PooledStream {
+        PooledStream {
+            stream: Some(stream),
+            pool: self.clone(),
+        }
+    }
+
+    /// Give a connection back, or forget a closed one.
+    fn release(&self, stream: Option<HiStream<TcpStream>>) {
+        let mut state = self.lock();
+        match stream {
+            Some(stream) => state.idle.push(stream),
+            None => state.open -= 1,
+        }
+        self.shared.returned.notify_one();
+    }
+
+    /// Number of connections currently open, idle or not.
+    pub fn open(&self) -> usize {
+        self.lock().open
+    }
+
+    /// Number of connections open and waiting to be checked out.
+    pub fn idle(&self) -> usize {
+        self.lock().idle.len()
+    }
+}
+
+impl fmt::Debug for HiPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("HiPool")
+            .field("addrs", &self.shared.addrs)
+            .field("config", &self.shared.config)
+            .field("size", &self.shared.size)
+            .field("open", &state.open)
+            .field("idle", &state.idle.len())
+            .finish()
+    }
+}
+
+/// A connection checked out of a [`HiPool`].
+///
+/// It dereferences to a [`HiStream`], and goes back to the pool when dropped.
+/// A connection left in the middle of a message, because a transfer failed or
+/// the message was never finished, is closed instead of being reused.
+///
+/// [`HiPool`]: struct.HiPool.html
+/// [`HiStream`]: struct.HiStream.html
+#[derive(Debug)]
+pub struct PooledStream {
+    stream: Option<HiStream<TcpStream>>,
+    pool: HiPool,
+}
+
+impl PooledStream {
+    /// Close this connection rather than giving it back to the pool, e.g.
+    /// after a failure the pool cannot detect.
+    pub fn discard(mut self) {
+        self.stream = None;
+    }
+}
+
+impl Deref for PooledStream {
+    type Target = HiStream<TcpStream>;
+
+    fn deref(&self) -> &Self::Target {
+        self.stream.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledStream {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledStream {
+    fn drop(&mut self) {
+        let stream = self.stream.take().filter(HiStream::is_reusable);
+        self.pool.release(stream);
+    }
+}
@@ -0,0 +1,219 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::connect;
+
+/// Bytes accepted in the response of an HTTP proxy to `CONNECT`.
+const MAX_HTTP_RESPONSE: usize = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Protocol {
+    Socks5,
+    Http,
+}
+
+/// A proxy to tunnel connections through, set with [`HiConfig::proxy`].
+///
+/// [`HiConfig::proxy`]: struct.HiConfig.html#method.proxy
+#[derive(Clone)]
+pub(crate) struct Proxy {
+    protocol: Protocol,
+    addr: String,
+    credentials: Option<(String, String)>,
+}
+
+impl Proxy {
+    /// Parse a `socks5://[user:password@]host[:port]` or
+    /// `http://[user:password@]host[:port]` URL.
+    pub(crate) fn parse(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.split_once("://")?;
+        let (protocol, default_port) = match scheme {
+            "socks5" | "socks5h" => (Protocol::Socks5, 1080),
+            "http" => (Protocol::Http, 8080),
+            _ => return None,
+        };
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        let (credentials, host) = match rest.rsplit_once('@') {
+            Some((credentials, host)) => {
+                let (user, password) = credentials.split_once(':')?;
+                (Some((user.to_owned(), password.to_owned())), host)
+            }
+            None => (None, rest),
+        };
+        if host.is_empty() || host.contains('/') {
+            return None;
+        }
+        let has_port = match host.rsplit_once(':') {
+            Some((_, port)) => !port.ends_with(']'),
+            None => false,
+        };
+        let addr = if has_port {
+            host.to_owned()
+        } else {
+            format!("{}:{}", host, default_port)
+        };
+        Some(Proxy {
+            protocol,
+            addr,
+            credentials,
+        })
+    }
+
+    /// Open a tunnel to the first of `targets` the proxy reaches.
+    pub(crate) fn connect(
+        &self,
+        targets: &[SocketAddr],
+        timeout: Option<Duration>,
+    ) -> io::Result<TcpStream> {
+        let mut last_error =
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to");
+        for &target in targets {
+            let mut tcp = connect::race(self.addr.to_socket_addrs()?.collect(), timeout)?;
+            let tunnel = match self.protocol {
+                Protocol::Socks5 => self.socks5(&mut tcp, target),
+                Protocol::Http => self.http(&mut tcp, target),
+            };
+            match tunnel {
+                Ok(()) => return Ok(tcp),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Ask for a tunnel to `target` with the SOCKS5 protocol, from RFC 1928.
+    fn socks5(&self, tcp: &mut TcpStream, target: SocketAddr) -> io::Result<()> {
+        let method = if self.credentials.is_some() { 2 } else { 0 };
+        tcp.write_all(&[5, 1, method])?;
+        let mut reply = [0; 2];
+        tcp.read_exact(&mut reply)?;
+        if reply[0] != 5 || reply[1] != method {
+            return Err(refused(
+                "SOCKS5 proxy refused the authentication method".into(),
+            ));
+        }
+
+        // Username and password authentication, from RFC 1929
+        if let Some((user, password)) = &self.credentials {
+            let mut request = vec![1];
+            for field in [user, password] {
+                let len = u8::try_from(field.len())
+                    .map_err(|_| refused("SOCKS5 credentials are too long".into()))?;
+                request.push(len);
+                request.extend_from_slice(field.as_bytes());
+            }
+            tcp.write_all(&request)?;
+            tcp.read_exact(&mut reply)?;
+            if reply[1] != 0 {
+                return Err(refused("SOCKS5 proxy rejected the credentials".into()));
+            }
+        }
+
+        let mut request = vec![5, 1, 0];
+        match target {
+            SocketAddr::V4(addr) => {
+                request.push(1);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+            SocketAddr::V6(addr) => {
+                request.push(4);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+        }
+        request.extend_from_slice(&target.port().to_be_bytes());
+        tcp.write_all(&request)?;
+
+        let mut reply = [0; 4];
+        tcp.read_exact(&mut reply)?;
+        if reply[0] != 5 || reply[1] != 0 {
+            return Err(refused(format!(
+                "SOCKS5 proxy could not connect to {} (reply {})",
+                target, reply[1]
+            )));
+        }
+        // Skip the address bound by the proxy, and its port
+        let bound = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => {
+                let mut len = [0];
+                tcp.read_exact(&mut len)?;
+                len[0].into()
+            }
+            _ => return Err(refused("malformed SOCKS5 reply".into())),
+        };
+        io::copy(&mut (&*tcp).take(bound + 2), &mut io::sink())?;
+        Ok(())
+    }
+
+    /// Ask for a tunnel to `target` with the `CONNECT` method of HTTP.
+    fn http(&self, tcp: &mut TcpStream, target: SocketAddr) -> io::Result<()> {
+        let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+        if let Some((user, password)) = &self.credentials {
+            let credentials = base64(format!("{}:{}", user, password).as_bytes());
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+        }
+        request.push_str("\r\n");
+        tcp.write_all(request.as_bytes())?;
+
+        // Read byte by byte, not to consume the start of the tunnel
+        let mut response = Vec::new();
+        let mut byte = [0];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() == MAX_HTTP_RESPONSE {
+                return Err(refused("HTTP proxy response is too long".into()));
+            }
+            tcp.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status = response.lines().next().unwrap_or_default();
+        match status.split(' ').nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(refused(format!(
+                "HTTP proxy could not connect to {}: {}",
+                target, status
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("protocol", &self.protocol)
+            .field("addr", &self.addr)
+            .field(
+                "credentials",
+                &self.credentials.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+fn refused(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, message)
+}
+
+/// Standard base64 encoding of `bytes`, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
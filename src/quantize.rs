@@ -0,0 +1,222 @@
+use std::io::{Read, Write};
+
+use crate::{hidelimiter, hiread, hiwrite, try_filled, Error, HiStream, Result};
+
+const HEADER_WORDS: usize = 4;
+const RAW: u32 = 64;
+
+/// How precisely a lossy message has to be transferred.
+///
+/// This is the parameter of [`hiwrite_quantized`].
+///
+/// [`hiwrite_quantized`]: fn.hiwrite_quantized.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quantization {
+    /// Quantize every value on this number of bits, from 1 to 32.
+    Bits(u32),
+    /// Use as few bits as possible, while keeping the absolute error on every
+    /// value below this bound.
+    MaxAbsError(f64),
+    /// Use as few bits as possible, while keeping the error on every value
+    /// below this fraction of the range of the array (its maximum minus its
+    /// minimum).
+    MaxRelError(f64),
+}
+
+impl Quantization {
+    /// Number of bits needed for values spanning `range`.
+    fn bits(self, range: f64) -> u32 {
+        let bound = match self {
+            Quantization::Bits(bits) => return bits.clamp(1, 32),
+            Quantization::MaxAbsError(bound) => bound,
+            Quantization::MaxRelError(bound) => bound * range,
+        };
+        if range == 0.0 {
+            return 0;
+        }
+        if bound.is_nan() || bound <= 0.0 {
+            return RAW;
+        }
+        // Keep a margin below the bound for rounding errors
+        let levels = range / (2.0 * bound * (1.0 - 1e-9)) + 1.0;
+        match levels.log2().ceil() as u32 {
+            bits if bits > 32 => RAW,
+            bits => bits.max(1),
+        }
+    }
+}
+
+/// Send `data` as a lossy *High Tension Message*, quantized as requested.
+///
+/// This function is blocking. Unlike [`hiwrite`], the message is complete: no
+/// call to [`hidelimiter`] is needed.
+///
+/// Values are uniformly quantized between the minimum and the maximum of
+/// `data`, which are sent along. With `n` bits, the error on each value is at
+/// most `(max - min) / (2^n - 1) / 2`. When an error bound would need more
+/// than 32 bits, or when `data` holds non-finite values, the data is sent at
+/// full precision instead.
+///
+/// The message must be received with [`hiread_quantized`].
+///
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+/// [`hiread_quantized`]: fn.hiread_quantized.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiwrite_quantized, Quantization};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let frame = vec![0.0; 1_000_000];
+///
+/// // Plenty for a display, at a fraction of the bandwidth
+/// hiwrite_quantized(&mut stream, &frame, Quantization::MaxRelError(1e-3))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_quantized<S: Read + Write>(
+    stream: &mut S,
+    data: &[f64],
+    quantization: Quantization,
+) -> Result<()> {
+    hiwrite(stream, &encode(data, quantization))?;
+    hidelimiter(stream)
+}
+
+/// Read a lossy *High Tension Message* sent by [`hiwrite_quantized`].
+///
+/// This function is blocking.
+///
+/// [`hiwrite_quantized`]: fn.hiwrite_quantized.html
+pub fn hiread_quantized<S: Read + Write>(stream: &mut S) -> Result<Vec<f64>> {
+    decode(&hiread(stream)?, |_| Ok(()))
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Send `data` as a lossy *High Tension Message*, like
+    /// [`hiwrite_quantized`].
+    ///
+    /// [`hiwrite_quantized`]: fn.hiwrite_quantized.html
+    pub fn send_quantized(&mut self, data: &[f64], quantization: Quantization) -> Result<()> {
+        self.send(&encode(data, quantization))
+    }
+
+    /// Read a lossy *High Tension Message* sent by [`send_quantized`].
+    ///
+    /// The number of values decoded is checked against
+    /// [`HiConfig::max_message`], like the message itself.
+    ///
+    /// [`send_quantized`]: #method.send_quantized
+    /// [`HiConfig::max_message`]: struct.HiConfig.html#method.max_message
+    pub fn read_quantized(&mut self) -> Result<Vec<f64>> {
+        let words = self.read()?;
+        decode(&words, |len| self.check_len(len))
+    }
+}
+
+/// Encode `data` as a header followed by packed quantized values.
+///
+/// The header words are the number of bits, the number of values, and the
+/// minimum and maximum values.
+fn encode(data: &[f64], quantization: Quantization) -> Vec<f64> {
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for &x in data {
+        min = min.min(x);
+        max = max.max(x);
+    }
+    let finite = data.iter().all(|x| x.is_finite());
+    if data.is_empty() {
+        min = 0.0;
+        max = 0.0;
+    }
+
+    let bits = if finite {
+        quantization.bits(max - min)
+    } else {
+        RAW
+    };
+    let mut out = Vec::with_capacity(HEADER_WORDS + packed_words(data.len(), bits));
+    out.push(f64::from_bits(bits as u64));
+    out.push(f64::from_bits(data.len() as u64));
+    out.push(min);
+    out.push(max);
+
+    match bits {
+        0 => {}
+        RAW => out.extend_from_slice(data),
+        _ => {
+            let levels = ((1u64 << bits) - 1) as f64;
+            let scale = levels / (max - min);
+            let mut acc = 0u64;
+            let mut filled = 0;
+            for &x in data {
+                let q = (((x - min) * scale).round() as u64).min(levels as u64);
+                acc |= q << filled;
+                filled += bits;
+                if filled >= 64 {
+                    out.push(f64::from_bits(acc));
+                    filled -= 64;
+                    acc = if filled > 0 { q >> (bits - filled) } else { 0 };
+                }
+            }
+            if filled > 0 {
+                out.push(f64::from_bits(acc));
+            }
+        }
+    }
+    out
+}
+
+/// Decode a message, checking the number of values it holds with `check_len`
+/// before allocating them.
+fn decode<F: FnOnce(usize) -> Result<()>>(words: &[f64], check_len: F) -> Result<Vec<f64>> {
+    let malformed = || Error::Framing("malformed quantized message".into());
+    if words.len() < HEADER_WORDS {
+        return Err(malformed());
+    }
+    let bits = words[0].to_bits();
+    let len = words[1].to_bits() as usize;
+    let (min, max) = (words[2], words[3]);
+    let packed = &words[HEADER_WORDS..];
+    if bits > 32 && bits != RAW as u64 || packed.len() != packed_words(len, bits as u32) {
+        return Err(malformed());
+    }
+    check_len(len)?;
+    let bits = bits as u32;
+
+    match bits {
+        // Nothing bounds the number of constant values but the allocation
+        0 => try_filled(len, min, "quantized message"),
+        RAW => Ok(packed.to_vec()),
+        _ => {
+            let mask = (1u64 << bits) - 1;
+            let step = (max - min) / mask as f64;
+            let mut out = Vec::with_capacity(len);
+            let mut offset = 0;
+            for _ in 0..len {
+                let word = offset / 64;
+                let shift = offset % 64;
+                let mut q = packed[word].to_bits() >> shift;
+                if shift + bits as usize > 64 {
+                    q |= packed[word + 1].to_bits() << (64 - shift);
+                }
+                out.push(min + (q & mask) as f64 * step);
+                offset += bits as usize;
+            }
+            Ok(out)
+        }
+    }
+}
+
+fn packed_words(len: usize, bits: u32) -> usize {
+    match bits {
+        RAW => len,
+        _ => len.saturating_mul(bits as usize).div_ceil(64),
+    }
+}
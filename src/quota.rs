@@ -0,0 +1,127 @@
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// A bound on the bytes a connection transfers per period of time, set with
+/// [`HiConfig::send_quota`] and [`HiConfig::receive_quota`].
+///
+/// Only the values of the messages count, not their trailers. Periods are
+/// fixed windows, starting with the first message of the connection.
+///
+/// [`HiConfig::send_quota`]: struct.HiConfig.html#method.send_quota
+/// [`HiConfig::receive_quota`]: struct.HiConfig.html#method.receive_quota
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{HiConfig, Quota};
+///
+/// let config = HiConfig::new()
+///     .send_quota(Quota::per_hour(10 << 30))
+///     .receive_quota(Quota::per_day(100 << 30));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quota {
+    bytes: u64,
+    period: Duration,
+}
+
+impl Quota {
+    /// Allow `bytes` per `period`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(bytes: u64, period: Duration) -> Self {
+        assert!(!period.is_zero(), "quotas apply to a period of time");
+        Quota { bytes, period }
+    }
+
+    /// Allow `bytes` per hour.
+    pub fn per_hour(bytes: u64) -> Self {
+        Self::new(bytes, Duration::from_secs(3600))
+    }
+
+    /// Allow `bytes` per day.
+    pub fn per_day(bytes: u64) -> Self {
+        Self::new(bytes, Duration::from_secs(24 * 3600))
+    }
+
+    /// Bytes allowed per period.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Duration of a period.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// The bytes transferred by a connection in the current period of a quota.
+#[derive(Debug)]
+pub(crate) struct Usage {
+    quota: Quota,
+    start: Option<Instant>,
+    used: u64,
+}
+
+impl Usage {
+    pub(crate) fn new(quota: Quota) -> Self {
+        Usage {
+            quota,
+            start: None,
+            used: 0,
+        }
+    }
+
+    /// Start a new period if the current one is over.
+    fn roll(&mut self) -> Instant {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now.duration_since(start);
+        if elapsed < self.quota.period {
+            return start;
+        }
+        let start = u32::try_from(elapsed.as_nanos() / self.quota.period.as_nanos())
+            .ok()
+            .and_then(|periods| self.quota.period.checked_mul(periods))
+            .and_then(|skipped| start.checked_add(skipped))
+            .unwrap_or(now);
+        self.start = Some(start);
+        self.used = 0;
+        start
+    }
+
+    fn exceeded(&self, start: Instant) -> Error {
+        Error::QuotaExceeded((start + self.quota.period).saturating_duration_since(Instant::now()))
+    }
+
+    /// Account for `bytes` about to be sent, unless they exceed the quota.
+    pub(crate) fn reserve(&mut self, bytes: u64) -> Result<()> {
+        let start = self.roll();
+        match self.used.checked_add(bytes) {
+            Some(used) if used <= self.quota.bytes => {
+                self.used = used;
+                Ok(())
+            }
+            _ => Err(self.exceeded(start)),
+        }
+    }
+
+    /// Check that the quota is not exhausted yet, before receiving.
+    pub(crate) fn check(&mut self) -> Result<()> {
+        let start = self.roll();
+        if self.used >= self.quota.bytes {
+            return Err(self.exceeded(start));
+        }
+        Ok(())
+    }
+
+    /// Account for `bytes` received.
+    pub(crate) fn add(&mut self, bytes: u64) {
+        self.roll();
+        self.used = self.used.saturating_add(bytes);
+    }
+}
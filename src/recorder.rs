@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::pod::{bytes_of, bytes_of_mut};
+use crate::{Error, HiStream, Result, Timestamp};
+
+/// Bytes of the header of each record: its sequence number, its length in
+/// floats and its wall clock time, in nanoseconds since the Unix epoch.
+const HEADER: u64 = 24;
+
+/// A message held by the recorder.
+#[derive(Debug)]
+struct Entry {
+    seq: u64,
+    offset: u64,
+    len: usize,
+    wall: SystemTime,
+}
+
+impl Entry {
+    fn size(&self) -> u64 {
+        HEADER + self.len as u64 * 8
+    }
+}
+
+/// A black box keeping the last *High Tension Messages* received in a
+/// circular buffer on disk, for post-mortem analysis of transient anomalies.
+///
+/// The buffer is a single file of fixed size, such as a few GB, which the
+/// newest messages overwrite the oldest ones in. When something interesting
+/// happens, [`freeze`] stops recording, so that the messages around the
+/// event are not overwritten, and [`export`] writes the messages of a time
+/// window out as `.f64` files, one per message, to be analysed or sent with
+/// [`hiwrite_dataset`].
+///
+/// Messages are dated by their [`Timestamp`] when they carry one, and by the
+/// time they were recorded otherwise. The index of the messages is kept in
+/// memory: the buffer cannot be read back after the recorder is dropped.
+///
+/// [`freeze`]: #method.freeze
+/// [`export`]: #method.export
+/// [`hiwrite_dataset`]: fn.hiwrite_dataset.html
+/// [`Timestamp`]: struct.Timestamp.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::FlightRecorder;
+/// use std::time::{Duration, SystemTime};
+/// use std::{env, fs};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let buffer = env::temp_dir().join(format!("hi-tension-recorder-{}", std::process::id()));
+/// let mut recorder = FlightRecorder::create(&buffer, 1 << 20)?;
+///
+/// // Each message takes 8 kB: only the last ones fit in 1 MB
+/// for i in 0..1000 {
+///     recorder.record(&[f64::from(i); 1024], None)?;
+/// }
+/// assert_eq!(recorder.len(), 127);
+///
+/// // Something went wrong: keep what led to it
+/// recorder.freeze();
+/// let event = SystemTime::now();
+/// let window = event - Duration::from_secs(10)..event;
+/// let messages = recorder.messages(window)?;
+/// assert_eq!(messages.last().unwrap().1[0], 999.0);
+/// # fs::remove_file(buffer)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FlightRecorder {
+    file: File,
+    capacity: u64,
+    head: u64,
+    entries: VecDeque<Entry>,
+    next_seq: u64,
+    frozen: bool,
+}
+
+impl FlightRecorder {
+    /// Record into the file at `path`, replacing it, holding at most
+    /// `capacity` bytes of messages.
+    pub fn create(path: impl AsRef<Path>, capacity: u64) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(capacity)?;
+        Ok(FlightRecorder {
+            file,
+            capacity,
+            head: 0,
+            entries: VecDeque::new(),
+            next_seq: 0,
+            frozen: false,
+        })
+    }
+
+    /// Record `data`, produced at `timestamp`, or now if `None`, dropping
+    /// the oldest messages to make room for it.
+    ///
+    /// Returns whether the message was recorded, which it is not while the
+    /// recorder is frozen.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::TooLarge`] if the message alone does not fit in
+    /// the buffer.
+    ///
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    pub fn record(&mut self, data: &[f64], timestamp: Option<Timestamp>) -> Result<bool> {
+        if self.frozen {
+            return Ok(false);
+        }
+        let size = HEADER + data.len() as u64 * 8;
+        if size > self.capacity {
+            let max = self.capacity.saturating_sub(HEADER) / 8;
+            return Err(Error::TooLarge(max as usize));
+        }
+
+        // Messages are not split: past the end, start over from the
+        // beginning, dropping the messages left at the end
+        if self.head + size > self.capacity {
+            while self.entries.front().is_some_and(|e| e.offset >= self.head) {
+                self.entries.pop_front();
+            }
+            self.head = 0;
+        }
+        while self
+            .entries
+            .front()
+            .is_some_and(|e| e.offset >= self.head && e.offset < self.head + size)
+        {
+            self.entries.pop_front();
+        }
+
+        let wall = timestamp.map_or_else(SystemTime::now, |t| t.wall);
+        let nanos = wall
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let mut header = [0; HEADER as usize];
+        header[..8].copy_from_slice(&self.next_seq.to_le_bytes());
+        header[8..16].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        header[16..].copy_from_slice(&nanos.to_le_bytes());
+        self.file.seek(SeekFrom::Start(self.head))?;
+        self.file.write_all(&header)?;
+        self.file.write_all(bytes_of(data))?;
+
+        self.entries.push_back(Entry {
+            seq: self.next_seq,
+            offset: self.head,
+            len: data.len(),
+            wall: UNIX_EPOCH + Duration::from_nanos(nanos),
+        });
+        self.head += size;
+        self.next_seq += 1;
+        Ok(true)
+    }
+
+    /// Read a *High Tension Message* from the `stream`, like
+    /// [`HiStream::read`], and record it.
+    ///
+    /// This function is blocking.
+    ///
+    /// [`HiStream::read`]: struct.HiStream.html#method.read
+    pub fn read<S: Read + Write>(&mut self, stream: &mut HiStream<S>) -> Result<Vec<f64>> {
+        let data = stream.read()?;
+        self.record(&data, stream.last_timestamp())?;
+        Ok(data)
+    }
+
+    /// Stop recording, keeping the messages held.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// Record again, the newest messages overwriting the oldest ones.
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// Whether the recorder is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Number of messages held.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no message is held.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the messages held dated within `window`, oldest first, with their
+    /// date.
+    pub fn messages(&self, window: Range<SystemTime>) -> Result<Vec<(SystemTime, Vec<f64>)>> {
+        self.within(&window)
+            .map(|entry| Ok((entry.wall, self.load(entry)?)))
+            .collect()
+    }
+
+    /// Write the messages held dated within `window` into the directory
+    /// `dir`, creating it if needed, as raw little-endian `.f64` files named
+    /// after their sequence number, so that they sort oldest first.
+    ///
+    /// Returns the number of messages written.
+    pub fn export(&self, window: Range<SystemTime>, dir: impl AsRef<Path>) -> Result<usize> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut written = 0;
+        for entry in self.within(&window) {
+            let data = self.load(entry)?;
+            fs::write(dir.join(format!("{:020}.f64", entry.seq)), bytes_of(&data))?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    fn within<'a>(&'a self, window: &'a Range<SystemTime>) -> impl Iterator<Item = &'a Entry> {
+        self.entries
+            .iter()
+            .filter(move |entry| window.contains(&entry.wall))
+    }
+
+    /// Read the values of `entry` back from the buffer.
+    fn load(&self, entry: &Entry) -> Result<Vec<f64>> {
+        let mut data = vec![0.0; entry.len];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(entry.offset + HEADER))?;
+        file.read_exact(bytes_of_mut(&mut data))?;
+        debug_assert!(entry.offset + entry.size() <= self.capacity);
+        Ok(data)
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::{Read, Write};
+use std::num::FpCategory;
+
+use crate::{read_into_with, Result, DEFAULT_SIZE, DELIMITER_NAN};
+
+/// A computation fed with the values of a message while it is received.
+///
+/// See [`hiread_with_reduce`]. Closures taking a slice are reducers too.
+///
+/// [`hiread_with_reduce`]: fn.hiread_with_reduce.html
+pub trait Reducer {
+    /// Account for the next `values` of the message.
+    fn update(&mut self, values: &[f64]);
+}
+
+impl<F: FnMut(&[f64])> Reducer for F {
+    fn update(&mut self, values: &[f64]) {
+        self(values)
+    }
+}
+
+/// Summary statistics of the values of a message: count, minimum, maximum,
+/// sum and sum of squares.
+///
+/// NaN values are counted, and propagate to the sums, but are ignored by the
+/// minimum and the maximum.
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{Reducer, Stats};
+///
+/// let mut stats = Stats::new();
+/// stats.update(&[1.0, 2.0]);
+/// stats.update(&[3.0]);
+/// assert_eq!(stats.count, 3);
+/// assert_eq!(stats.min, 1.0);
+/// assert_eq!(stats.max, 3.0);
+/// assert_eq!(stats.mean(), 2.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    /// Number of values.
+    pub count: usize,
+    /// Smallest value, or positive infinity if there is none.
+    pub min: f64,
+    /// Largest value, or negative infinity if there is none.
+    pub max: f64,
+    /// Sum of the values.
+    pub sum: f64,
+    /// Sum of the squares of the values.
+    pub sum_sq: f64,
+}
+
+impl Stats {
+    /// Create statistics of no value at all.
+    pub fn new() -> Self {
+        Stats {
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            sum_sq: 0.0,
+        }
+    }
+
+    /// Mean of the values, or NaN if there is none.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    /// Population variance of the values, or NaN if there is none.
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        (self.sum_sq / self.count as f64 - mean * mean).max(0.0)
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reducer for Stats {
+    fn update(&mut self, values: &[f64]) {
+        self.count += values.len();
+        for &x in values {
+            self.min = self.min.min(x);
+            self.max = self.max.max(x);
+            self.sum += x;
+            self.sum_sq += x * x;
+        }
+    }
+}
+
+/// Counts of the special values of a message: NaN, infinite, subnormal and
+/// zero values.
+///
+/// These cheap diagnostics catch solver blow-ups at the communication
+/// boundary. A [`HiStream`] reports them for every message received with
+/// [`HiConfig::float_report`].
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`HiConfig::float_report`]: struct.HiConfig.html#method.float_report
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{FloatReport, Reducer};
+///
+/// let mut report = FloatReport::new();
+/// report.update(&[1.0, f64::NAN, 0.0, -0.0, f64::MIN_POSITIVE / 2.0]);
+/// assert_eq!(report.nan, 1);
+/// assert_eq!(report.zero, 2);
+/// assert_eq!(report.subnormal, 1);
+/// assert!(!report.is_finite());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FloatReport {
+    /// Number of values.
+    pub count: usize,
+    /// Number of NaN values.
+    pub nan: usize,
+    /// Number of infinite values, of either sign.
+    pub infinite: usize,
+    /// Number of subnormal values.
+    pub subnormal: usize,
+    /// Number of zeros, of either sign.
+    pub zero: usize,
+}
+
+impl FloatReport {
+    /// Create a report of no value at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether every value is finite, neither NaN nor infinite.
+    pub fn is_finite(&self) -> bool {
+        self.nan == 0 && self.infinite == 0
+    }
+}
+
+impl Reducer for FloatReport {
+    fn update(&mut self, values: &[f64]) {
+        self.count += values.len();
+        for x in values {
+            match x.classify() {
+                FpCategory::Nan => self.nan += 1,
+                FpCategory::Infinite => self.infinite += 1,
+                FpCategory::Subnormal => self.subnormal += 1,
+                FpCategory::Zero => self.zero += 1,
+                FpCategory::Normal => {}
+            }
+        }
+    }
+}
+
+/// [`Stats`] of consecutive windows of a fixed number of values.
+///
+/// The last window holds the remaining values, and may be shorter.
+///
+/// [`Stats`]: struct.Stats.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{Reducer, WindowedStats};
+///
+/// let mut windows = WindowedStats::new(2);
+/// windows.update(&[1.0, 3.0, 5.0]);
+/// windows.update(&[7.0, 9.0]);
+/// let means: Vec<_> = windows.windows.iter().map(|w| w.mean()).collect();
+/// assert_eq!(means, [2.0, 6.0, 9.0]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowedStats {
+    /// Number of values per window.
+    pub size: usize,
+    /// Statistics of every window, in order.
+    pub windows: Vec<Stats>,
+}
+
+impl WindowedStats {
+    /// Create statistics of windows of `size` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "a window holds at least one value");
+        WindowedStats {
+            size,
+            windows: Vec::new(),
+        }
+    }
+}
+
+impl Reducer for WindowedStats {
+    fn update(&mut self, mut values: &[f64]) {
+        while !values.is_empty() {
+            let window = match self.windows.last_mut() {
+                Some(window) if window.count < self.size => window,
+                _ => {
+                    self.windows.push(Stats::new());
+                    self.windows.last_mut().unwrap()
+                }
+            };
+            let n = (self.size - window.count).min(values.len());
+            window.update(&values[..n]);
+            values = &values[n..];
+        }
+    }
+}
+
+/// Read a *High Tension Message* from the `stream`, feeding its values to
+/// `reducer` as they arrive.
+///
+/// This function is blocking, and allocates like [`hiread`].
+///
+/// Values are reduced right after being received, while they are still in
+/// cache, which spares a second pass over the array for common quality
+/// checks.
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiread_with_reduce, Stats};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let mut stats = Stats::new();
+/// let data = hiread_with_reduce(&mut stream, &mut stats)?;
+/// println!("mean {}, range {}..{}", stats.mean(), stats.min, stats.max);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_with_reduce<S, R>(stream: &mut S, reducer: &mut R) -> Result<Vec<f64>>
+where
+    S: Read + Write,
+    R: Reducer + ?Sized,
+{
+    let mut buf = vec![0.0; DEFAULT_SIZE];
+    read_into_with(stream, &mut buf, 0, &DELIMITER_NAN, usize::MAX, |values| {
+        reducer.update(values)
+    })?;
+    Ok(buf)
+}
@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+
+use crate::{acknowledge, hidelimiter, hiwrite, read_chunks_unacked, refuse, Error, Result};
+
+/// Forward a *High Tension Message* from `src` to `dst`, without decoding it.
+///
+/// This function is blocking.
+///
+/// The message is forwarded chunk by chunk as it arrives, so only a small
+/// buffer is allocated whatever its size. It is acknowledged to `src` only
+/// once `dst` acknowledged it, so the original sender keeps the end-to-end
+/// guarantee of reception. Returns the number of floats forwarded.
+///
+/// # Errors
+///
+/// Fails with [`Error::RemoteError`] if `dst` refused the message, after
+/// refusing it to `src` with the same reason, so that both connections stay
+/// usable.
+///
+/// [`Error::RemoteError`]: enum.Error.html#variant.RemoteError
+///
+/// # Examples
+///
+/// A gateway bridging two networks:
+///
+/// ```no_run
+/// use hi_tension::hirelay;
+/// use std::net::{TcpListener, TcpStream};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let listener = TcpListener::bind("10.0.0.1:34567")?;
+/// let (mut src, _) = listener.accept()?;
+/// let mut dst = TcpStream::connect("192.168.0.2:34567")?;
+///
+/// loop {
+///     hirelay(&mut src, &mut dst)?;
+/// }
+/// # }
+/// ```
+pub fn hirelay<S, D>(src: &mut S, dst: &mut D) -> Result<usize>
+where
+    S: Read + Write,
+    D: Read + Write,
+{
+    let len = read_chunks_unacked(src, |chunk| hiwrite(dst, chunk))?;
+    match hidelimiter(dst) {
+        Err(Error::RemoteError(reason)) => {
+            refuse(src, &reason)?;
+            return Err(Error::RemoteError(reason));
+        }
+        result => result?,
+    }
+    acknowledge(src)?;
+    Ok(len)
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::panic;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::{Error, HiStream, Result};
+
+struct State {
+    results: BTreeMap<u64, Vec<f64>>,
+    next: u64,
+    running: usize,
+    duplicates: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A receiver collecting results from a set of worker connections, and
+/// delivering them in the order of their sequence numbers.
+///
+/// Each connection is read by a thread of its own, as fast as its worker
+/// sends. Every result must carry its sequence number, in decimal, as its
+/// user header, like the messages sent by a [`LoadBalancer`] or a
+/// [`JournaledSender`]: both ends need [`HiConfig::user_headers`]. Results
+/// arriving ahead of their turn are held until the missing ones come, and
+/// results received twice are dropped.
+///
+/// Once every connection ended, a sequence number which never arrived is
+/// skipped, so that the results held are still delivered.
+///
+/// [`LoadBalancer`]: struct.LoadBalancer.html
+/// [`JournaledSender`]: struct.JournaledSender.html
+/// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, Balance, HiConfig, HiStream, LoadBalancer, ReorderPool};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let config = HiConfig::new().user_headers();
+/// let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+/// for worker in 0..2 {
+///     let (input, work) = pipe();
+///     let (done, output) = pipe();
+///     let worker_config = config.clone();
+///     thread::spawn(move || -> hi_tension::Result<()> {
+///         let mut work = HiStream::server(work, worker_config.clone())?;
+///         let mut done = HiStream::client(done, worker_config)?;
+///         while let Ok(data) = work.read() {
+///             if worker == 0 {
+///                 thread::sleep(Duration::from_millis(5));
+///             }
+///             let sequence = work.last_header().unwrap_or_default().to_vec();
+///             done.set_header(&sequence);
+///             done.send(&[data[0] * 2.0])?;
+///         }
+///         done.close()?;
+///         Ok(())
+///     });
+///     inputs.push(HiStream::client(input, config.clone())?);
+///     outputs.push(HiStream::server(output, config.clone())?);
+/// }
+///
+/// let mut results = ReorderPool::new(outputs);
+/// let mut balancer = LoadBalancer::new(inputs, Balance::LeastInFlight);
+/// for frame in 0..20 {
+///     balancer.send(vec![f64::from(frame)])?;
+/// }
+/// for stream in balancer.finish() {
+///     stream?.close()?;
+/// }
+///
+/// for frame in 0..20 {
+///     assert_eq!(results.recv(), Some((frame, vec![frame as f64 * 2.0])));
+/// }
+/// assert_eq!(results.recv(), None);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReorderPool<S> {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<Result<HiStream<S>>>>,
+}
+
+impl<S> ReorderPool<S>
+where
+    S: Read + Write + Send + 'static,
+{
+    /// Collect the results sent over `workers`, starting from the sequence
+    /// number 0.
+    pub fn new(workers: Vec<HiStream<S>>) -> Self {
+        Self::starting_at(workers, 0)
+    }
+
+    /// Collect the results sent over `workers`, starting from the sequence
+    /// number `next`: earlier ones are dropped as duplicates.
+    pub fn starting_at(workers: Vec<HiStream<S>>, next: u64) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                results: BTreeMap::new(),
+                next,
+                running: workers.len(),
+                duplicates: 0,
+            }),
+            changed: Condvar::new(),
+        });
+        let threads = workers
+            .into_iter()
+            .map(|stream| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let result = collect(&shared, stream);
+                    shared.lock().running -= 1;
+                    shared.changed.notify_all();
+                    result
+                })
+            })
+            .collect();
+        ReorderPool { shared, threads }
+    }
+
+    /// Get the next result in order, with its sequence number, or `None`
+    /// once every connection ended and every result was delivered.
+    ///
+    /// This function is blocking, while the next result is missing and a
+    /// connection may still bring it.
+    pub fn recv(&mut self) -> Option<(u64, Vec<f64>)> {
+        let mut state = self.shared.lock();
+        loop {
+            let next = state.next;
+            if let Some(data) = state.results.remove(&next) {
+                state.next += 1;
+                return Some((next, data));
+            }
+            if state.running == 0 {
+                // The missing result will never come
+                let (&first, _) = state.results.iter().next()?;
+                state.next = first;
+                continue;
+            }
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Number of results received ahead of their turn, waiting for the
+    /// missing ones.
+    pub fn pending(&self) -> usize {
+        self.shared.lock().results.len()
+    }
+
+    /// Number of results dropped because they were received twice.
+    pub fn duplicates(&self) -> u64 {
+        self.shared.lock().duplicates
+    }
+
+    /// Wait for every connection to end, and give back the stream of each
+    /// worker, in order, or the error which ended it.
+    ///
+    /// This function is blocking. A connection ends when its worker closes
+    /// it with [`HiStream::close`]. Results not delivered yet are dropped.
+    ///
+    /// [`HiStream::close`]: struct.HiStream.html#method.close
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of a reading thread, if any.
+    pub fn finish(self) -> Vec<Result<HiStream<S>>> {
+        self.threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    }
+}
+
+impl<S> fmt::Debug for ReorderPool<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("ReorderPool")
+            .field("next", &state.next)
+            .field("pending", &state.results.len())
+            .field("running", &state.running)
+            .field("duplicates", &state.duplicates)
+            .finish()
+    }
+}
+
+/// Read the results sent over `stream` into the pool, until the worker
+/// closes the connection.
+fn collect<S: Read + Write>(shared: &Shared, mut stream: HiStream<S>) -> Result<HiStream<S>> {
+    loop {
+        let data = match stream.read() {
+            Ok(data) => data,
+            Err(Error::Closed) => return Ok(stream),
+            Err(e) => return Err(e),
+        };
+        let sequence = stream
+            .last_header()
+            .and_then(|header| std::str::from_utf8(header).ok())
+            .and_then(|header| header.parse().ok())
+            .ok_or_else(|| Error::Framing("result without a sequence number".into()))?;
+
+        let mut state = shared.lock();
+        if sequence < state.next || state.results.contains_key(&sequence) {
+            state.duplicates += 1;
+        } else {
+            state.results.insert(sequence, data);
+            shared.changed.notify_all();
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Read, Write};
+
+use crate::{HiStream, Message, Result};
+
+/// Prefix of the text messages carrying a request, before its ID.
+const REQUEST_PREFIX: char = '?';
+
+/// Prefix of the text messages carrying a response, before the ID of its
+/// request.
+const RESPONSE_PREFIX: char = '=';
+
+/// Split a text message made of `prefix`, an ID, a space and a body.
+fn split_tagged(text: &str, prefix: char) -> Option<(u64, &str)> {
+    let (id, body) = text.strip_prefix(prefix)?.split_once(' ')?;
+    Some((id.parse().ok()?, body))
+}
+
+/// Text requests sent over a [`HiStream`], matched with their responses by
+/// correlation ID.
+///
+/// Many requests can be outstanding at once: the peer, which gets each of
+/// them as a [`Request`], can answer them in any order. The messages received
+/// while waiting for a response, such as *High Tension Messages* or requests
+/// from the peer, are kept for [`recv`], so that control traffic flows
+/// alongside bulk transfers.
+///
+/// Requests and responses are *Simple Text Messages* tagged with the ID, so
+/// both sides need [`HiConfig::typed_messages`].
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`Request`]: struct.Request.html
+/// [`recv`]: #method.recv
+/// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, HiConfig, HiStream, Message, Request, Requests};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let instrument = thread::spawn(move || -> hi_tension::Result<()> {
+///     let mut stream = HiStream::server(server, HiConfig::new().typed_messages())?;
+///     let mut requests = Vec::new();
+///     while requests.len() < 2 {
+///         if let Message::Text(text) = stream.recv()? {
+///             requests.extend(Request::parse(&text));
+///         }
+///     }
+///     // Answer the slow request last
+///     requests[1].respond(&mut stream, "1.5")?;
+///     stream.send(&[0.0; 1024])?;
+///     requests[0].respond(&mut stream, "done")?;
+///     Ok(())
+/// });
+///
+/// let mut stream = HiStream::client(client, HiConfig::new().typed_messages())?;
+/// let mut requests = Requests::new();
+/// let calibrate = requests.send(&mut stream, "calibrate")?;
+/// let gain = requests.send(&mut stream, "get gain")?;
+///
+/// assert_eq!(requests.wait(&mut stream, gain)?, "1.5");
+/// assert_eq!(requests.wait(&mut stream, calibrate)?, "done");
+/// assert_eq!(requests.recv(&mut stream)?, Message::Array(vec![0.0; 1024]));
+/// instrument.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Requests {
+    next_id: u64,
+    pending: HashSet<u64>,
+    responses: HashMap<u64, String>,
+    messages: VecDeque<Message>,
+}
+
+impl Requests {
+    /// Create a queue without any outstanding request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `text` as a request, returning its ID.
+    ///
+    /// This function is blocking, until the request is written.
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`HiStream::send_text`].
+    ///
+    /// [`HiStream::send_text`]: struct.HiStream.html#method.send_text
+    pub fn send<S: Read + Write>(&mut self, stream: &mut HiStream<S>, text: &str) -> Result<u64> {
+        let id = self.next_id;
+        stream.send_text(&format!("{}{} {}", REQUEST_PREFIX, id, text))?;
+        self.next_id += 1;
+        self.pending.insert(id);
+        Ok(id)
+    }
+
+    /// Wait for the response to the request `id`.
+    ///
+    /// This function is blocking. Other messages received in the meantime
+    /// are kept for [`recv`], and the responses to other requests for their
+    /// own `wait`.
+    ///
+    /// [`recv`]: #method.recv
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not a request of this queue waiting for its
+    /// response.
+    pub fn wait<S: Read + Write>(&mut self, stream: &mut HiStream<S>, id: u64) -> Result<String> {
+        assert!(
+            self.pending.contains(&id) || self.responses.contains_key(&id),
+            "no request {} waiting for its response",
+            id
+        );
+        loop {
+            if let Some(response) = self.responses.remove(&id) {
+                return Ok(response);
+            }
+            if let Some(message) = self.receive(stream)? {
+                self.messages.push_back(message);
+            }
+        }
+    }
+
+    /// Take the response to the request `id`, if it was received already.
+    pub fn try_wait(&mut self, id: u64) -> Option<String> {
+        self.responses.remove(&id)
+    }
+
+    /// Get the next message which is not a response, like
+    /// [`HiStream::recv`].
+    ///
+    /// This function is blocking. The messages received while waiting for a
+    /// response are returned first, in order.
+    ///
+    /// [`HiStream::recv`]: struct.HiStream.html#method.recv
+    pub fn recv<S: Read + Write>(&mut self, stream: &mut HiStream<S>) -> Result<Message> {
+        if let Some(message) = self.messages.pop_front() {
+            return Ok(message);
+        }
+        loop {
+            if let Some(message) = self.receive(stream)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Number of requests whose response was not received yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Read a message, keeping it if it is the response to a pending request.
+    fn receive<S: Read + Write>(&mut self, stream: &mut HiStream<S>) -> Result<Option<Message>> {
+        let message = stream.recv()?;
+        if let Message::Text(text) = &message {
+            if let Some((id, body)) = split_tagged(text, RESPONSE_PREFIX) {
+                if self.pending.remove(&id) {
+                    self.responses.insert(id, body.to_owned());
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(message))
+    }
+}
+
+/// A request received from the [`Requests`] of the peer.
+///
+/// [`Requests`]: struct.Requests.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Request {
+    id: u64,
+    text: String,
+}
+
+impl Request {
+    /// Parse a *Simple Text Message* as a request, if it is one.
+    pub fn parse(text: &str) -> Option<Request> {
+        let (id, text) = split_tagged(text, REQUEST_PREFIX)?;
+        Some(Request {
+            id,
+            text: text.to_owned(),
+        })
+    }
+
+    /// Correlation ID of the request.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Text of the request.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Send `text` as the response to this request.
+    ///
+    /// This function is blocking, until the response is written.
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`HiStream::send_text`].
+    ///
+    /// [`HiStream::send_text`]: struct.HiStream.html#method.send_text
+    pub fn respond<S: Read + Write>(&self, stream: &mut HiStream<S>, text: &str) -> Result<()> {
+        stream.send_text(&format!("{}{} {}", RESPONSE_PREFIX, self.id, text))
+    }
+}
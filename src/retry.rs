@@ -0,0 +1,149 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+/// How a [`HiStream`] retries the reads and writes of its underlying stream
+/// failing with a transient error, set with [`HiConfig::retry`].
+///
+/// An operation is attempted at most `attempts` times. Between attempts, the
+/// stream sleeps for a delay doubling from the initial [`backoff`] up to its
+/// maximum, except after `Interrupted` errors which are retried right away.
+/// By default, `Interrupted`, `WouldBlock` and `TimedOut` errors are retried,
+/// so that sockets with a read timeout or in nonblocking mode can be used
+/// with blocking semantics.
+///
+/// A connection reset cannot be retried at this level: reconnecting is up to
+/// the application, with [`HiStream::resume`] to carry on with its session.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`HiConfig::retry`]: struct.HiConfig.html#method.retry
+/// [`backoff`]: #method.backoff
+/// [`HiStream::resume`]: struct.HiStream.html#method.resume
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{HiConfig, RetryPolicy};
+/// use std::io::ErrorKind;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(10)
+///     .backoff(Duration::from_millis(10), Duration::from_secs(1))
+///     .retry_on(ErrorKind::ConnectionAborted);
+/// let config = HiConfig::new().retry(policy);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    kinds: Vec<ErrorKind>,
+}
+
+impl RetryPolicy {
+    /// Create a policy attempting every operation at most `attempts` times,
+    /// waiting from 1 ms up to 1 s between attempts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attempts` is zero.
+    pub fn new(attempts: u32) -> Self {
+        assert!(attempts > 0, "operations are attempted at least once");
+        RetryPolicy {
+            attempts,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_secs(1),
+            kinds: vec![
+                ErrorKind::Interrupted,
+                ErrorKind::WouldBlock,
+                ErrorKind::TimedOut,
+            ],
+        }
+    }
+
+    /// Wait `initial` after the first failed attempt, doubling up to `max`
+    /// after the next ones.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_delay = initial;
+        self.max_delay = max;
+        self
+    }
+
+    /// Retry errors of kind `kind` too.
+    pub fn retry_on(mut self, kind: ErrorKind) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    /// Maximum number of attempts of an operation.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Kinds of the errors retried.
+    pub fn kinds(&self) -> &[ErrorKind] {
+        &self.kinds
+    }
+
+    /// Delay after the failed attempt number `attempt`, starting from 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+/// A stream retrying its operations according to a policy, if any.
+#[derive(Debug)]
+pub(crate) struct Retrying<S> {
+    pub(crate) inner: S,
+    policy: Option<RetryPolicy>,
+}
+
+impl<S> Retrying<S> {
+    pub(crate) fn new(inner: S, policy: Option<RetryPolicy>) -> Self {
+        Retrying { inner, policy }
+    }
+
+    fn retry<T, F>(&mut self, mut op: F) -> io::Result<T>
+    where
+        F: FnMut(&mut S) -> io::Result<T>,
+    {
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => return op(&mut self.inner),
+        };
+        let mut attempt = 1;
+        loop {
+            match op(&mut self.inner) {
+                Err(e) if attempt < policy.attempts && policy.kinds.contains(&e.kind()) => {
+                    if e.kind() != ErrorKind::Interrupted {
+                        thread::sleep(policy.delay(attempt));
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<S: Read> Read for Retrying<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.retry(|inner| inner.read(buf))
+    }
+}
+
+impl<S: Write> Write for Retrying<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.retry(|inner| inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.retry(|inner| inner.flush())
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// The description of a named type of *High Tension Messages*.
+///
+/// Schemas are registered with [`HiConfig::schema`], and announced to the peer
+/// during the handshake. A schema known to both sides under the same name must
+/// have the same definition, or the connection is refused: producer and
+/// consumer drifting apart is caught at connect time rather than at analysis
+/// time.
+///
+/// Messages then refer to their schema with [`HiStream::set_schema`], and the
+/// receiver finds it with [`HiStream::last_schema`].
+///
+/// A shape pattern lists the dimensions of the messages, row-major, where
+/// `None` stands for any number of elements.
+///
+/// [`HiConfig::schema`]: struct.HiConfig.html#method.schema
+/// [`HiStream::set_schema`]: struct.HiStream.html#method.set_schema
+/// [`HiStream::last_schema`]: struct.HiStream.html#method.last_schema
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::Schema;
+///
+/// // Any number of 512 by 512 frames
+/// let frames = Schema::new("frames", "f64")
+///     .shape(&[None, Some(512), Some(512)])
+///     .units("counts");
+/// assert!(frames.accepts(3 * 512 * 512));
+/// assert!(!frames.accepts(1000));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Schema {
+    name: String,
+    dtype: String,
+    shape: Vec<Option<usize>>,
+    units: String,
+}
+
+impl Schema {
+    /// Create the schema `name`, for messages of values of type `dtype`, with
+    /// any length and no units.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` or `dtype` is empty or contains whitespace.
+    pub fn new(name: impl Into<String>, dtype: impl Into<String>) -> Self {
+        Schema {
+            name: word(name.into()),
+            dtype: word(dtype.into()),
+            shape: vec![None],
+            units: String::new(),
+        }
+    }
+
+    /// Set the shape pattern of the messages.
+    pub fn shape(mut self, shape: &[Option<usize>]) -> Self {
+        self.shape = shape.to_vec();
+        self
+    }
+
+    /// Set the units of the values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `units` is empty or contains whitespace.
+    pub fn units(mut self, units: impl Into<String>) -> Self {
+        self.units = word(units.into());
+        self
+    }
+
+    /// Name of the schema.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type of the values.
+    pub fn dtype(&self) -> &str {
+        &self.dtype
+    }
+
+    /// Shape pattern of the messages.
+    pub fn shape_pattern(&self) -> &[Option<usize>] {
+        &self.shape
+    }
+
+    /// Units of the values, empty if there are none.
+    pub fn unit(&self) -> &str {
+        &self.units
+    }
+
+    /// Whether a message of `len` values fits the shape pattern.
+    pub fn accepts(&self, len: usize) -> bool {
+        let fixed = self
+            .shape
+            .iter()
+            .flatten()
+            .try_fold(1usize, |n, &d| n.checked_mul(d));
+        match fixed {
+            None => false,
+            Some(0) => len == 0,
+            Some(fixed) if self.shape.contains(&None) => len.is_multiple_of(fixed),
+            Some(fixed) => len == fixed,
+        }
+    }
+
+    /// Encode as the value of a `schema` handshake field.
+    pub(crate) fn to_field(&self) -> String {
+        let units = if self.units.is_empty() {
+            "-"
+        } else {
+            &self.units
+        };
+        format!(
+            "{} {} {} {}",
+            self.name,
+            self.dtype,
+            ShapePattern(&self.shape),
+            units
+        )
+    }
+
+    pub(crate) fn from_field(field: &str) -> Result<Self> {
+        let invalid = || Error::Handshake(format!("invalid schema {:?}", field));
+        let parts: Vec<_> = field.split(' ').collect();
+        let (name, dtype, shape, units) = match parts[..] {
+            [name, dtype, shape, units] if !name.is_empty() && !dtype.is_empty() => {
+                (name, dtype, shape, units)
+            }
+            _ => return Err(invalid()),
+        };
+        let shape = shape
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(invalid)?;
+        let shape = match shape {
+            "" => Vec::new(),
+            _ => shape
+                .split(',')
+                .map(|d| match d {
+                    "*" => Ok(None),
+                    _ => d.parse().map(Some).map_err(|_| invalid()),
+                })
+                .collect::<Result<_>>()?,
+        };
+        Ok(Schema {
+            name: name.into(),
+            dtype: dtype.into(),
+            shape,
+            units: if units == "-" { "" } else { units }.into(),
+        })
+    }
+}
+
+struct ShapePattern<'a>(&'a [Option<usize>]);
+
+impl fmt::Display for ShapePattern<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, d) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match d {
+                Some(d) => write!(f, "{}", d)?,
+                None => f.write_str("*")?,
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+fn word(s: String) -> String {
+    assert!(
+        !s.is_empty() && !s.contains(char::is_whitespace),
+        "schema fields must be non-empty and without whitespace: {:?}",
+        s
+    );
+    s
+}
+
+/// Find a schema known to both sides under the same name, but defined
+/// differently.
+pub(crate) fn drift<'a>(local: &'a [Schema], peer: &[Schema]) -> Option<&'a str> {
+    local
+        .iter()
+        .find(|l| peer.iter().any(|p| p.name == l.name && p != *l))
+        .map(|l| l.name.as_str())
+}
@@ -0,0 +1,685 @@
+use std::io;
+#[cfg(unix)]
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::raw::{c_int, c_short};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::handshake::{self, MAX_MESSAGE};
+use crate::{Error, HiConfig, HiStream, Result, Router};
+
+/// How often idle connections and the listener check for a shutdown.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a client may stall during its handshake or a message, unless
+/// configured otherwise.
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most bytes read from a connection at once by `serve_nonblocking`.
+#[cfg(unix)]
+const READ_SIZE: usize = 64 << 10;
+
+#[cfg(unix)]
+const POLLIN: c_short = 1;
+
+#[cfg(target_os = "linux")]
+type Nfds = std::os::raw::c_ulong;
+#[cfg(all(unix, not(target_os = "linux")))]
+type Nfds = std::os::raw::c_uint;
+
+/// `struct pollfd`, from `poll.h`.
+#[cfg(unix)]
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn poll(fds: *mut PollFd, nfds: Nfds, timeout: c_int) -> c_int;
+}
+
+/// A connection served by `serve_nonblocking`, with the bytes it received.
+#[cfg(unix)]
+#[derive(Debug)]
+enum Connection {
+    /// Waiting for the end of the handshake request of the client, started
+    /// at `since`.
+    Handshake {
+        tcp: TcpStream,
+        request: Vec<u8>,
+        since: Instant,
+    },
+    /// Open, with the message being received started at `since`, if any.
+    Open {
+        stream: Box<HiStream<TcpStream>>,
+        since: Option<Instant>,
+    },
+}
+
+#[cfg(unix)]
+impl Connection {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Connection::Handshake { tcp, .. } => tcp,
+            Connection::Open { stream, .. } => stream.get_ref(),
+        }
+    }
+
+    /// Whether a message was received whole and is waiting to be handled.
+    fn has_message(&self) -> bool {
+        match self {
+            Connection::Handshake { .. } => false,
+            Connection::Open { stream, .. } => stream.is_message_fed(),
+        }
+    }
+}
+
+/// A listening socket accepting `hi-tension` connections.
+///
+/// Every connection is opened with [`HiStream::server`] using the same
+/// configuration. Connections are either accepted one by one with [`accept`],
+/// or served by a thread each with [`serve_threaded`], or by a thread each
+/// with a handler picked by their identity with [`serve_routed`], or all by
+/// a single thread with [`serve_nonblocking`], until [`shutdown`] is called.
+///
+/// [`HiStream::server`]: struct.HiStream.html#method.server
+/// [`accept`]: #method.accept
+/// [`serve_threaded`]: #method.serve_threaded
+/// [`serve_routed`]: #method.serve_routed
+/// [`serve_nonblocking`]: #method.serve_nonblocking
+/// [`shutdown`]: #method.shutdown
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiServer};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let server = Arc::new(HiServer::bind("0.0.0.0:34567", HiConfig::new())?);
+///
+/// let signal = server.clone();
+/// thread::spawn(move || {
+///     // Wait for whatever tells the server to stop
+///     signal.shutdown();
+/// });
+///
+/// server.serve_threaded(
+///     |stream, data| {
+///         println!("received {} floats", data.len());
+///         stream.send(&[data.iter().sum()])
+///     },
+///     64,
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HiServer {
+    listener: TcpListener,
+    config: HiConfig,
+    stall_timeout: Duration,
+    shutdown: AtomicBool,
+}
+
+impl HiServer {
+    /// Listen on `addr`, opening connections with `config`.
+    pub fn bind(addr: impl ToSocketAddrs, config: HiConfig) -> Result<Self> {
+        Ok(HiServer {
+            listener: TcpListener::bind(addr)?,
+            config,
+            stall_timeout: STALL_TIMEOUT,
+            shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Drop the served clients which stall for `timeout` during their
+    /// handshake or in the middle of a message, 30 s by default, so that a
+    /// silent client can neither hold a connection forever nor delay a
+    /// [`shutdown`]. Reads and writes of the served connections time out
+    /// after `timeout`, except while waiting for the next message.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    pub fn stall_timeout(mut self, timeout: Duration) -> Self {
+        assert!(timeout > Duration::ZERO, "a stall timeout cannot be zero");
+        self.stall_timeout = timeout;
+        self
+    }
+
+    /// The local address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept a single connection, and perform the server side of its
+    /// handshake.
+    ///
+    /// This function is blocking.
+    pub fn accept(&self) -> Result<HiStream<TcpStream>> {
+        let (tcp, _) = self.listener.accept()?;
+        HiStream::server(tcp, self.config.clone())
+    }
+
+    /// Serve every client on a thread of its own, until [`shutdown`] is
+    /// called.
+    ///
+    /// This function is blocking.
+    ///
+    /// `handler` is called for every message received, along with the stream
+    /// it came from to answer on. A connection ends when the client
+    /// disconnects, stalls for the [`stall_timeout`], or when its handshake or
+    /// `handler` fails. At most `max_conns` clients are served at once:
+    /// further ones wait to be accepted.
+    ///
+    /// Once [`shutdown`] is called, no connection is accepted anymore. Each
+    /// connection ends after the message in flight, if any, has been received
+    /// and handled, or has stalled, and this function returns when they are
+    /// all over.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    /// [`stall_timeout`]: #method.stall_timeout
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_conns` is zero.
+    pub fn serve_threaded<F>(&self, handler: F, max_conns: usize) -> Result<()>
+    where
+        F: Fn(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()> + Sync,
+    {
+        self.serve_each(
+            |tcp| {
+                // The connection ends the same way whatever the reason
+                let _ = self.serve_connection(tcp, &handler);
+            },
+            max_conns,
+        )
+    }
+
+    /// Serve every client on a thread of its own, with the handler `router`
+    /// picks for its [`Identity`], until [`shutdown`] is called.
+    ///
+    /// This function is blocking, and serves the connections like
+    /// [`serve_threaded`] does. A client handed over to a channel by the
+    /// router no longer counts towards `max_conns`, nor ends on
+    /// [`shutdown`]: its consumer serves it from then on, without the
+    /// timeouts of the server.
+    ///
+    /// [`Identity`]: struct.Identity.html
+    /// [`shutdown`]: #method.shutdown
+    /// [`serve_threaded`]: #method.serve_threaded
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_conns` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiServer, Router};
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let server = HiServer::bind("0.0.0.0:34567", HiConfig::new())?;
+    ///
+    /// let (dashboards, connected) = mpsc::sync_channel(1);
+    /// let router = Router::new()
+    ///     .on("instrument", |stream, data| {
+    ///         println!("{:?} measured {} floats", stream.peer_identity(), data.len());
+    ///         Ok(())
+    ///     })
+    ///     .on("archiver", |stream, _| stream.send(&[0.0]))
+    ///     .forward("dashboard", dashboards);
+    ///
+    /// // Dashboards get a thread of their own, to push updates at their pace
+    /// thread::spawn(move || {
+    ///     for mut dashboard in connected {
+    ///         let _ = dashboard.send_text("hello");
+    ///     }
+    /// });
+    /// server.serve_routed(&router, 64)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn serve_routed(&self, router: &Router, max_conns: usize) -> Result<()> {
+        self.serve_each(
+            |tcp| {
+                let _ = self.serve_routed_connection(tcp, router);
+            },
+            max_conns,
+        )
+    }
+
+    /// Accept every client, and call `serve` on a thread of its own with its
+    /// connection, until [`shutdown`] is called.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    fn serve_each<F>(&self, serve: F, max_conns: usize) -> Result<()>
+    where
+        F: Fn(TcpStream) + Sync,
+    {
+        assert!(
+            max_conns > 0,
+            "a server needs room for at least one connection"
+        );
+        self.listener.set_nonblocking(true)?;
+        let open = Mutex::new(0);
+        let closed = Condvar::new();
+
+        let result = thread::scope(|scope| {
+            while !self.is_shutdown() {
+                {
+                    let mut open = open.lock().unwrap_or_else(|e| e.into_inner());
+                    while *open >= max_conns && !self.is_shutdown() {
+                        open = closed
+                            .wait_timeout(open, POLL_INTERVAL)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0;
+                    }
+                    if self.is_shutdown() {
+                        break;
+                    }
+                }
+
+                let tcp = match self.listener.accept() {
+                    Ok((tcp, _)) => tcp,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                };
+
+                *open.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+                let (serve, open, closed) = (&serve, &open, &closed);
+                scope.spawn(move || {
+                    serve(tcp);
+                    *open.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+                    closed.notify_one();
+                });
+            }
+            Ok(())
+        });
+
+        self.listener.set_nonblocking(false)?;
+        result
+    }
+
+    fn serve_connection<F>(&self, tcp: TcpStream, handler: &F) -> Result<()>
+    where
+        F: Fn(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()>,
+    {
+        self.set_timeouts(&tcp)?;
+        let stream = HiStream::server(tcp, self.config.clone())?;
+        self.serve_messages(stream, handler)
+    }
+
+    fn serve_routed_connection(&self, tcp: TcpStream, router: &Router) -> Result<()> {
+        self.set_timeouts(&tcp)?;
+        let stream = HiStream::server(tcp, self.config.clone())?;
+        // Forwarded streams are up to their consumer
+        set_timeouts(stream.get_ref(), None)?;
+        match router.dispatch(stream) {
+            Some((stream, handler)) => {
+                self.set_timeouts(stream.get_ref())?;
+                self.serve_messages(stream, handler)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Make `tcp` blocking, with the stall timeout for its reads and writes.
+    fn set_timeouts(&self, tcp: &TcpStream) -> Result<()> {
+        tcp.set_nonblocking(false)?;
+        set_timeouts(tcp, Some(self.stall_timeout))
+    }
+
+    /// Call `handler` for every message of `stream`, until the connection
+    /// ends or the server shuts down.
+    fn serve_messages<F>(&self, mut stream: HiStream<TcpStream>, handler: &F) -> Result<()>
+    where
+        F: Fn(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()> + ?Sized,
+    {
+        while self.wait_message(&stream)? {
+            let data = match stream.read() {
+                Ok(data) => data,
+                Err(Error::Closed) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            handler(&mut stream, data)?;
+        }
+        Ok(())
+    }
+
+    /// Serve every client from the calling thread, waiting for messages on all
+    /// the connections at once, until [`shutdown`] is called.
+    ///
+    /// This function is blocking.
+    ///
+    /// `handler` is called for every message received, along with the stream
+    /// it came from to answer on, like with [`serve_threaded`]. A single
+    /// thread waits with `poll` for the connections with bytes to read, and
+    /// reads what they received so far: this suits many clients sending
+    /// small messages now and then, such as instruments reporting to a
+    /// telemetry aggregator, without a thread per client. Handshakes and
+    /// messages are received a bit at a time, so that slow or stalled
+    /// clients do not hold the other connections up, and the clients
+    /// stalling for the [`stall_timeout`] are dropped. Only the answers of
+    /// `handler` are sent blocking. At most `max_conns` clients are served at
+    /// once: further ones wait to be accepted.
+    ///
+    /// Text messages are not expected: a client sending one is dropped, like
+    /// [`HiStream::read`] fails.
+    ///
+    /// Once [`shutdown`] is called, no connection is accepted anymore, and
+    /// this function returns after the message being handled, if any, closing
+    /// every connection.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    /// [`serve_threaded`]: #method.serve_threaded
+    /// [`stall_timeout`]: #method.stall_timeout
+    /// [`HiStream::read`]: struct.HiStream.html#method.read
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_conns` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiServer};
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let server = HiServer::bind("0.0.0.0:34567", HiConfig::new())?;
+    /// let mut readings = Vec::new();
+    ///
+    /// server.serve_nonblocking(
+    ///     |stream, data| {
+    ///         readings.push((stream.get_ref().peer_addr()?, data));
+    ///         Ok(())
+    ///     },
+    ///     1024,
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn serve_nonblocking<F>(&self, mut handler: F, max_conns: usize) -> Result<()>
+    where
+        F: FnMut(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()>,
+    {
+        assert!(
+            max_conns > 0,
+            "a server needs room for at least one connection"
+        );
+        self.listener.set_nonblocking(true)?;
+        let mut connections = Vec::new();
+        let mut fds = Vec::new();
+        let mut buf = vec![0; READ_SIZE];
+
+        let result = loop {
+            if self.is_shutdown() {
+                break Ok(());
+            }
+            let accepting = connections.len() < max_conns;
+            fds.clear();
+            if accepting {
+                fds.push(PollFd {
+                    fd: self.listener.as_raw_fd(),
+                    events: POLLIN,
+                    revents: 0,
+                });
+            }
+            fds.extend(connections.iter().map(|connection: &Connection| PollFd {
+                fd: connection.tcp().as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            }));
+            // Messages already received, such as batched ones, are served
+            // without waiting
+            let timeout = if connections.iter().any(Connection::has_message) {
+                Duration::ZERO
+            } else {
+                POLL_INTERVAL
+            };
+            if let Err(e) = wait_readable(&mut fds, timeout) {
+                break Err(e);
+            }
+
+            let (listener, ready) = if accepting {
+                (fds[0].revents != 0, &fds[1..])
+            } else {
+                (false, &fds[..])
+            };
+            connections = connections
+                .into_iter()
+                .zip(ready)
+                .filter_map(|(connection, fd)| {
+                    // The connection ends the same way whatever the reason
+                    self.advance(connection, fd.revents != 0, &mut buf, &mut handler)
+                        .ok()
+                })
+                .collect();
+            if listener {
+                if let Err(e) = self.accept_ready(&mut connections, max_conns) {
+                    break Err(e);
+                }
+            }
+        };
+
+        self.listener.set_nonblocking(false)?;
+        result
+    }
+
+    /// Read what `connection` received if it is `readable`, and carry on with
+    /// its handshake, or handle the next message it received whole, if any.
+    /// Fails if the connection should end.
+    #[cfg(unix)]
+    fn advance<F>(
+        &self,
+        connection: Connection,
+        readable: bool,
+        buf: &mut [u8],
+        handler: &mut F,
+    ) -> Result<Connection>
+    where
+        F: FnMut(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()>,
+    {
+        match connection {
+            Connection::Handshake {
+                mut tcp,
+                mut request,
+                since,
+            } => {
+                // Only the bytes just read may complete the request
+                let scanned = request.len().saturating_sub(1);
+                if readable {
+                    let n = read_ready(&mut tcp, buf)?;
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let end = match request[scanned..].windows(2).position(|w| w == b"\n\n") {
+                    Some(i) => scanned + i + 2,
+                    None if request.len() > MAX_MESSAGE => {
+                        return Err(Error::Handshake("request too long".into()))
+                    }
+                    None => {
+                        self.check_stall(since)?;
+                        return Ok(Connection::Handshake {
+                            tcp,
+                            request,
+                            since,
+                        });
+                    }
+                };
+                let fields = handshake::read_request(&mut &request[..end])?;
+                let stream = HiStream::server_with_request(tcp, fields, self.config.clone())?;
+                let mut stream = Box::new(stream);
+                stream.feed(&request[end..]);
+                self.advance_open(stream, None, handler)
+            }
+            Connection::Open { mut stream, since } => {
+                if readable {
+                    let n = read_ready(stream.get_mut(), buf)?;
+                    stream.feed(&buf[..n]);
+                }
+                self.advance_open(stream, since, handler)
+            }
+        }
+    }
+
+    /// Handle the next message `stream` received whole, if any, keeping track
+    /// of `since` when the message being received started.
+    #[cfg(unix)]
+    fn advance_open<F>(
+        &self,
+        mut stream: Box<HiStream<TcpStream>>,
+        since: Option<Instant>,
+        handler: &mut F,
+    ) -> Result<Connection>
+    where
+        F: FnMut(&mut HiStream<TcpStream>, Vec<f64>) -> Result<()>,
+    {
+        if stream.is_message_fed() {
+            match stream.read_fed() {
+                Ok(data) => handler(&mut stream, data)?,
+                // Only a ping or a probe, or part of a frame, was received
+                Err(Error::WouldBlock) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let since = match since {
+            _ if stream.fed().is_empty() => None,
+            Some(since) => Some(self.check_stall(since)?),
+            None => Some(Instant::now()),
+        };
+        Ok(Connection::Open { stream, since })
+    }
+
+    /// Fail if what a client started sending at `since` stalled for too long.
+    #[cfg(unix)]
+    fn check_stall(&self, since: Instant) -> Result<Instant> {
+        if since.elapsed() > self.stall_timeout {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "client stalled").into());
+        }
+        Ok(since)
+    }
+
+    /// Accept the connections waiting on the listener, while there is room
+    /// for them.
+    #[cfg(unix)]
+    fn accept_ready(&self, connections: &mut Vec<Connection>, max_conns: usize) -> Result<()> {
+        while connections.len() < max_conns {
+            let tcp = match self.listener.accept() {
+                Ok((tcp, _)) => tcp,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            // The handshake is received a bit at a time, and answered blocking
+            if self.set_timeouts(&tcp).is_ok() {
+                connections.push(Connection::Handshake {
+                    tcp,
+                    request: Vec::new(),
+                    since: Instant::now(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for the next message to start arriving, checking for a shutdown
+    /// meanwhile. Returns `false` if the connection should end.
+    fn wait_message(&self, stream: &HiStream<TcpStream>) -> Result<bool> {
+        if stream.has_unbatched() {
+            return Ok(true);
+        }
+        let tcp = stream.get_ref();
+        tcp.set_read_timeout(Some(POLL_INTERVAL))?;
+        let result = loop {
+            if self.is_shutdown() {
+                break Ok(false);
+            }
+            match tcp.peek(&mut [0]) {
+                Ok(n) => break Ok(n > 0),
+                Err(e) if is_retryable(&e) => {}
+                Err(e) => break Err(e.into()),
+            }
+        };
+        tcp.set_read_timeout(Some(self.stall_timeout))?;
+        result
+    }
+
+    /// Stop accepting connections, and end the ones being served by
+    /// [`serve_threaded`], [`serve_routed`] or [`serve_nonblocking`] once
+    /// their message in flight is handled. Clients stalling during their
+    /// handshake or a message hold the shutdown up for the [`stall_timeout`]
+    /// at most.
+    ///
+    /// [`serve_threaded`]: #method.serve_threaded
+    /// [`serve_routed`]: #method.serve_routed
+    /// [`serve_nonblocking`]: #method.serve_nonblocking
+    /// [`stall_timeout`]: #method.stall_timeout
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`shutdown`] was called.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+}
+
+/// Wait for data to arrive on any of `fds`, for at most `timeout`.
+#[cfg(unix)]
+fn wait_readable(fds: &mut [PollFd], timeout: Duration) -> Result<()> {
+    let timeout = timeout.as_millis() as c_int;
+    if unsafe { poll(fds.as_mut_ptr(), fds.len() as Nfds, timeout) } < 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Read what `tcp` received so far into `buf`, which a single read does not
+/// block for once `poll` found it readable. Fails at the end of the stream.
+#[cfg(unix)]
+fn read_ready(tcp: &mut TcpStream, buf: &mut [u8]) -> Result<usize> {
+    loop {
+        match tcp.read(buf) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => return Ok(n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn set_timeouts(tcp: &TcpStream, timeout: Option<Duration>) -> Result<()> {
+    tcp.set_read_timeout(timeout)?;
+    tcp.set_write_timeout(timeout)?;
+    Ok(())
+}
+
+fn is_retryable(e: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(e.kind(), WouldBlock | TimedOut | Interrupted)
+}
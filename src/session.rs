@@ -0,0 +1,292 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct State {
+    sent: u64,
+    received: u64,
+    pending: VecDeque<Vec<f64>>,
+    last_used: Instant,
+}
+
+/// A session outliving the connections attached to it.
+///
+/// A session is identified by a connection ID assigned by the server at
+/// handshake. It counts the *High Tension Messages* sent and received, and
+/// holds a queue of messages waiting to be sent. When a client reconnects with
+/// [`HiStream::resume`], it reattaches to the same session on the server side
+/// and both ends carry on from where they stopped.
+///
+/// `Session` is a cheap handle: clones refer to the same session.
+///
+/// [`HiStream::resume`]: struct.HiStream.html#method.resume
+#[derive(Clone)]
+pub struct Session {
+    id: u64,
+    state: Arc<Mutex<State>>,
+}
+
+impl Session {
+    pub(crate) fn new(id: u64) -> Self {
+        Session {
+            id,
+            state: Arc::new(Mutex::new(State {
+                sent: 0,
+                received: 0,
+                pending: VecDeque::new(),
+                last_used: Instant::now(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The connection ID of this session.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Number of messages sent within this session.
+    pub fn sent(&self) -> u64 {
+        self.lock().sent
+    }
+
+    /// Number of messages received within this session.
+    pub fn received(&self) -> u64 {
+        self.lock().received
+    }
+
+    /// Queue `data` to be sent by [`HiStream::send_pending`], possibly over a
+    /// later connection.
+    ///
+    /// [`HiStream::send_pending`]: struct.HiStream.html#method.send_pending
+    pub fn queue(&self, data: Vec<f64>) {
+        self.lock().pending.push_back(data);
+    }
+
+    /// Number of queued messages.
+    pub fn pending(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    pub(crate) fn count_sent(&self) {
+        let mut state = self.lock();
+        state.sent += 1;
+        state.last_used = Instant::now();
+    }
+
+    pub(crate) fn count_received(&self) {
+        let mut state = self.lock();
+        state.received += 1;
+        state.last_used = Instant::now();
+    }
+
+    fn touch(&self) {
+        self.lock().last_used = Instant::now();
+    }
+
+    fn last_used(&self) -> Instant {
+        self.lock().last_used
+    }
+
+    /// Whether a connection, or the application, holds this session besides
+    /// the store.
+    fn is_attached(&self) -> bool {
+        Arc::strong_count(&self.state) > 1
+    }
+
+    pub(crate) fn front_pending(&self) -> Option<Vec<f64>> {
+        self.lock().pending.front().cloned()
+    }
+
+    pub(crate) fn pop_pending(&self) {
+        self.lock().pending.pop_front();
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Idle time counts from when connections let go of the session
+        self.touch();
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Session")
+            .field("id", &self.id)
+            .field("sent", &state.sent)
+            .field("received", &state.received)
+            .field("pending", &state.pending.len())
+            .finish()
+    }
+}
+
+/// The server side registry of [`Session`]s.
+///
+/// A server enables sessions by setting a store with [`HiConfig::sessions`].
+/// Every connection accepted with that configuration is then assigned a
+/// session, or reattached to its previous one.
+///
+/// `SessionStore` is a cheap handle: clones refer to the same registry, so it
+/// can be shared by several listening threads.
+///
+/// Sessions are kept until [`remove`]d, so that clients can resume them at
+/// any time. On a long-running server, where clients may disconnect and
+/// never come back, [`idle_timeout`] and [`max_sessions`] bound the sessions
+/// kept, and the messages queued in them. Sessions attached to a connection,
+/// or otherwise held by the application, are never dropped.
+///
+/// [`Session`]: struct.Session.html
+/// [`HiConfig::sessions`]: struct.HiConfig.html#method.sessions
+/// [`remove`]: #method.remove
+/// [`idle_timeout`]: #method.idle_timeout
+/// [`max_sessions`]: #method.max_sessions
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{HiConfig, SessionStore};
+/// use std::time::Duration;
+///
+/// let store = SessionStore::new()
+///     .idle_timeout(Duration::from_secs(3600))
+///     .max_sessions(10_000);
+/// let config = HiConfig::new().sessions(store);
+/// ```
+#[derive(Clone, Default)]
+pub struct SessionStore {
+    registry: Arc<Mutex<Registry>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    sessions: HashMap<u64, Session>,
+    idle_timeout: Option<Duration>,
+    max_sessions: Option<usize>,
+}
+
+impl Registry {
+    /// Drop the sessions which have been idle for too long, then the least
+    /// recently used ones until there is room for `room` more.
+    fn evict(&mut self, room: usize) {
+        if let Some(timeout) = self.idle_timeout {
+            self.sessions.retain(|_, session| {
+                session.is_attached() || session.last_used().elapsed() < timeout
+            });
+        }
+        if let Some(max) = self.max_sessions {
+            while self.sessions.len() + room > max {
+                let oldest = self
+                    .sessions
+                    .iter()
+                    .filter(|(_, session)| !session.is_attached())
+                    .min_by_key(|(_, session)| session.last_used())
+                    .map(|(&id, _)| id);
+                match oldest {
+                    Some(id) => self.sessions.remove(&id),
+                    None => break,
+                };
+            }
+        }
+    }
+}
+
+impl SessionStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop the sessions no connection used for `timeout`, when new ones are
+    /// opened.
+    pub fn idle_timeout(self, timeout: Duration) -> Self {
+        self.lock().idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep at most `max` sessions, dropping the least recently used ones
+    /// to make room for new ones.
+    ///
+    /// The store still grows past `max` while every session is attached to
+    /// a connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn max_sessions(self, max: usize) -> Self {
+        assert!(max > 0, "stores hold at least one session");
+        self.lock().max_sessions = Some(max);
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the session with the given connection `id`.
+    pub fn get(&self, id: u64) -> Option<Session> {
+        self.lock().sessions.get(&id).cloned()
+    }
+
+    /// Forget the session with the given connection `id`, returning it.
+    pub fn remove(&self, id: u64) -> Option<Session> {
+        self.lock().sessions.remove(&id)
+    }
+
+    /// Number of sessions in the store.
+    pub fn len(&self) -> usize {
+        self.lock().sessions.len()
+    }
+
+    /// Whether the store has no session.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the session `id` if it is known, or open a new one.
+    pub(crate) fn attach(&self, id: Option<u64>) -> Session {
+        let mut registry = self.lock();
+        if let Some(session) = id.and_then(|id| registry.sessions.get(&id)) {
+            session.touch();
+            return session.clone();
+        }
+
+        registry.evict(1);
+        let mut id = new_id();
+        while registry.sessions.contains_key(&id) {
+            id = new_id();
+        }
+        let session = Session::new(id);
+        registry.sessions.insert(id, session.clone());
+        session
+    }
+}
+
+impl fmt::Debug for SessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registry = self.lock();
+        f.debug_struct("SessionStore")
+            .field("len", &registry.sessions.len())
+            .field("idle_timeout", &registry.idle_timeout)
+            .field("max_sessions", &registry.max_sessions)
+            .finish()
+    }
+}
+
+/// Draw an unpredictable connection ID, using the random keys of the standard
+/// library hasher.
+fn new_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
@@ -0,0 +1,116 @@
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{HiStream, Result};
+
+/// A [`HiStream`] shared by several threads, each sending and receiving
+/// complete messages.
+///
+/// The connection is locked for the whole duration of a message, acknowledge
+/// included, so messages of different threads are interleaved but never mixed
+/// up. Messages sent in several parts are written through [`lock`].
+///
+/// `SyncHiStream` is a cheap handle: clones refer to the same connection.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`lock`]: #method.lock
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiStream, SyncHiStream};
+/// use std::net::TcpStream;
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+/// let stream = SyncHiStream::new(HiStream::client(tcp, HiConfig::new())?);
+///
+/// let producers: Vec<_> = (0..4)
+///     .map(|i| {
+///         let stream = stream.clone();
+///         thread::spawn(move || stream.send(&vec![i as f64; 1_000_000]))
+///     })
+///     .collect();
+/// for producer in producers {
+///     producer.join().unwrap()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SyncHiStream<S> {
+    stream: Arc<Mutex<HiStream<S>>>,
+}
+
+impl<S> Clone for SyncHiStream<S> {
+    fn clone(&self) -> Self {
+        SyncHiStream {
+            stream: self.stream.clone(),
+        }
+    }
+}
+
+impl<S: Read + Write> SyncHiStream<S> {
+    /// Share `stream` between threads.
+    pub fn new(stream: HiStream<S>) -> Self {
+        SyncHiStream {
+            stream: Arc::new(Mutex::new(stream)),
+        }
+    }
+
+    /// Get exclusive access to the connection, until the guard is dropped.
+    ///
+    /// This function is blocking: it waits for the message in progress, if
+    /// any, to be over.
+    ///
+    /// A message started through the guard must be finished before dropping
+    /// it, or the peer will see it merged with the next one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream, SyncHiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let stream = SyncHiStream::new(HiStream::client(tcp, HiConfig::new())?);
+    ///
+    /// let mut guard = stream.lock();
+    /// for part in vec![0.0; 1_000_000].chunks(1000) {
+    ///     guard.write(part)?;
+    /// }
+    /// guard.finish()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lock(&self) -> MutexGuard<'_, HiStream<S>> {
+        self.stream.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send `data` as a complete *High Tension Message*, like
+    /// [`HiStream::send`].
+    ///
+    /// [`HiStream::send`]: struct.HiStream.html#method.send
+    pub fn send(&self, data: &[f64]) -> Result<()> {
+        self.lock().send(data)
+    }
+
+    /// Read a *High Tension Message*, like [`HiStream::read`].
+    ///
+    /// [`HiStream::read`]: struct.HiStream.html#method.read
+    pub fn read(&self) -> Result<Vec<f64>> {
+        self.lock().read()
+    }
+
+    /// Get the `HiStream` back, if this is the last handle to it.
+    ///
+    /// Otherwise, the handle is returned unchanged.
+    pub fn into_inner(self) -> std::result::Result<HiStream<S>, Self> {
+        match Arc::try_unwrap(self.stream) {
+            Ok(stream) => Ok(stream.into_inner().unwrap_or_else(|e| e.into_inner())),
+            Err(stream) => Err(SyncHiStream { stream }),
+        }
+    }
+}
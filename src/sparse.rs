@@ -0,0 +1,240 @@
+use std::io::{Read, Write};
+
+use crate::{hidelimiter, hiread, hiwrite, read_chunks, try_filled, Error, HiStream, Result};
+
+const PAIRS_PER_WRITE: usize = 4096;
+
+/// A mostly zero array, stored as its non-zero values and their indices.
+///
+/// Sparse arrays travel as *High Tension Messages* holding the logical length
+/// of the array, followed by `(index, value)` pairs. They are sent with
+/// [`hiwrite_sparse`], and received with [`hiread_sparse`] or, to get the
+/// dense array back directly, [`hiread_sparse_dense`].
+///
+/// [`hiwrite_sparse`]: fn.hiwrite_sparse.html
+/// [`hiread_sparse`]: fn.hiread_sparse.html
+/// [`hiread_sparse_dense`]: fn.hiread_sparse_dense.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::SparseArray;
+///
+/// let sparse = SparseArray::from_dense(&[0.0, 0.0, 3.0, 0.0]);
+/// assert_eq!(sparse.len, 4);
+/// assert_eq!(sparse.indices, [2]);
+/// assert_eq!(sparse.values, [3.0]);
+/// assert_eq!(sparse.to_dense(), [0.0, 0.0, 3.0, 0.0]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SparseArray {
+    /// Logical length of the array.
+    pub len: usize,
+    /// Indices of the non-zero values.
+    pub indices: Vec<u64>,
+    /// Non-zero values, in the same order as their indices.
+    pub values: Vec<f64>,
+}
+
+impl SparseArray {
+    /// Keep the non-zero values of `dense`.
+    pub fn from_dense(dense: &[f64]) -> Self {
+        let (indices, values) = dense
+            .iter()
+            .enumerate()
+            .filter(|(_, &x)| x != 0.0)
+            .map(|(i, &x)| (i as u64, x))
+            .unzip();
+        SparseArray {
+            len: dense.len(),
+            indices,
+            values,
+        }
+    }
+
+    /// Expand into a dense array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is out of bounds.
+    pub fn to_dense(&self) -> Vec<f64> {
+        let mut dense = vec![0.0; self.len];
+        for (&i, &x) in self.indices.iter().zip(&self.values) {
+            dense[i as usize] = x;
+        }
+        dense
+    }
+
+    /// Write the message words into `f`, a few pairs at a time.
+    fn encode<F: FnMut(&[f64]) -> Result<()>>(&self, mut f: F) -> Result<()> {
+        assert_eq!(self.indices.len(), self.values.len());
+        f(&[f64::from_bits(self.len as u64)])?;
+
+        let mut buf = Vec::with_capacity(2 * PAIRS_PER_WRITE);
+        for (indices, values) in self
+            .indices
+            .chunks(PAIRS_PER_WRITE)
+            .zip(self.values.chunks(PAIRS_PER_WRITE))
+        {
+            buf.clear();
+            for (&i, &x) in indices.iter().zip(values) {
+                buf.push(f64::from_bits(i));
+                buf.push(x);
+            }
+            f(&buf)?;
+        }
+        Ok(())
+    }
+
+    /// Decode a message, checking its logical length with `check_len`.
+    fn decode<F: FnOnce(usize) -> Result<()>>(words: &[f64], check_len: F) -> Result<Self> {
+        if words.is_empty() || words.len() % 2 != 1 {
+            return Err(malformed());
+        }
+        let len = words[0].to_bits() as usize;
+        // Longer arrays could not be expanded with `to_dense`
+        if len > isize::MAX as usize / 8 {
+            return Err(malformed());
+        }
+        check_len(len)?;
+        let pairs = words[1..].chunks_exact(2);
+        let mut sparse = SparseArray {
+            len,
+            indices: Vec::with_capacity(pairs.len()),
+            values: Vec::with_capacity(pairs.len()),
+        };
+        for pair in pairs {
+            let i = pair[0].to_bits();
+            if i >= len as u64 {
+                return Err(malformed());
+            }
+            sparse.indices.push(i);
+            sparse.values.push(pair[1]);
+        }
+        Ok(sparse)
+    }
+}
+
+fn malformed() -> Error {
+    Error::Framing("malformed sparse message".into())
+}
+
+/// Send `sparse` as a sparse *High Tension Message*.
+///
+/// This function is blocking. Unlike [`hiwrite`], the message is complete: no
+/// call to [`hidelimiter`] is needed.
+///
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+///
+/// # Panics
+///
+/// Panics if `sparse` does not have as many indices as values.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiwrite_sparse, SparseArray};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let mut field = vec![0.0; 1_000_000];
+/// field[1234] = 1.0;
+///
+/// hiwrite_sparse(&mut stream, &SparseArray::from_dense(&field))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_sparse<S: Read + Write>(stream: &mut S, sparse: &SparseArray) -> Result<()> {
+    sparse.encode(|words| hiwrite(stream, words))?;
+    hidelimiter(stream)
+}
+
+/// Read a sparse *High Tension Message* sent by [`hiwrite_sparse`].
+///
+/// This function is blocking.
+///
+/// [`hiwrite_sparse`]: fn.hiwrite_sparse.html
+pub fn hiread_sparse<S: Read + Write>(stream: &mut S) -> Result<SparseArray> {
+    SparseArray::decode(&hiread(stream)?, |_| Ok(()))
+}
+
+/// Read a sparse *High Tension Message* sent by [`hiwrite_sparse`], expanded
+/// into a dense array.
+///
+/// This function is blocking.
+///
+/// The pairs are scattered into the dense array as they arrive, so they are
+/// never held in memory as a whole.
+///
+/// [`hiwrite_sparse`]: fn.hiwrite_sparse.html
+pub fn hiread_sparse_dense<S: Read + Write>(stream: &mut S) -> Result<Vec<f64>> {
+    let mut dense = None;
+    let mut index = None;
+    // Keep reading malformed messages to the end, to stay in sync with the peer
+    let mut valid = true;
+    read_chunks(stream, |mut words| {
+        let dense = match &mut dense {
+            Some(dense) => dense,
+            None => match words.split_first() {
+                Some((len, rest)) => {
+                    words = rest;
+                    // Without room for the array, every pair is out of bounds
+                    let len = len.to_bits() as usize;
+                    dense.insert(try_filled(len, 0.0, "sparse message").unwrap_or_else(|_| {
+                        valid = false;
+                        Vec::new()
+                    }))
+                }
+                None => return Ok(()),
+            },
+        };
+        for &word in words {
+            match index.take() {
+                None => index = Some(word.to_bits()),
+                Some(i) => match dense.get_mut(i as usize) {
+                    Some(x) => *x = word,
+                    None => valid = false,
+                },
+            }
+        }
+        Ok(())
+    })?;
+
+    match (dense, index) {
+        (Some(dense), None) if valid => Ok(dense),
+        _ => Err(malformed()),
+    }
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Send `sparse` as a sparse *High Tension Message*, like
+    /// [`hiwrite_sparse`].
+    ///
+    /// [`hiwrite_sparse`]: fn.hiwrite_sparse.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sparse` does not have as many indices as values.
+    pub fn send_sparse(&mut self, sparse: &SparseArray) -> Result<()> {
+        sparse.encode(|words| self.write(words))?;
+        self.finish()
+    }
+
+    /// Read a sparse *High Tension Message* sent by [`send_sparse`].
+    ///
+    /// The logical length of the array is checked against
+    /// [`HiConfig::max_message`], like the message itself, so that it can be
+    /// expanded with [`SparseArray::to_dense`].
+    ///
+    /// [`send_sparse`]: #method.send_sparse
+    /// [`HiConfig::max_message`]: struct.HiConfig.html#method.max_message
+    /// [`SparseArray::to_dense`]: struct.SparseArray.html#method.to_dense
+    pub fn read_sparse(&mut self) -> Result<SparseArray> {
+        let words = self.read()?;
+        SparseArray::decode(&words, |len| self.check_len(len))
+    }
+}
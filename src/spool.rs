@@ -0,0 +1,161 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::pod::{bytes_of, bytes_of_mut};
+use crate::{hidelimiter, hiwrite, Error, Result};
+
+const EXTENSION: &str = "f64";
+
+/// A store-and-forward queue of outgoing *High Tension Messages*, persisted on
+/// disk.
+///
+/// Messages which could not be delivered are kept as files in the spool
+/// directory, one per message, and survive a restart of the application. They
+/// are sent again, oldest first, once the peer is reachable.
+///
+/// Delivery is *at least once*: a message whose acknowledgement got lost is
+/// sent again after reconnection.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::Spool;
+/// use std::net::TcpStream;
+///
+/// # fn acquire() -> Vec<f64> { Vec::new() }
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut spool = Spool::open("/var/spool/acquisition")?;
+/// let mut stream = TcpStream::connect("127.0.0.1:34567").ok();
+///
+/// loop {
+///     let data = acquire();
+///     if stream.is_none() {
+///         stream = TcpStream::connect("127.0.0.1:34567").ok();
+///     }
+///     match &mut stream {
+///         Some(s) => {
+///             if spool.send(s, &data).is_err() {
+///                 stream = None;
+///             }
+///         }
+///         None => spool.push(&data)?,
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Spool {
+    dir: PathBuf,
+    head: u64,
+    tail: u64,
+}
+
+impl Spool {
+    /// Open the spool stored in the directory `dir`, creating it if needed.
+    ///
+    /// Messages left over by a previous run are kept, in order.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(&dir)?;
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                ids.push(id);
+            }
+        }
+
+        let head = ids.iter().copied().min().unwrap_or(0);
+        let tail = ids.iter().copied().max().map_or(0, |id| id + 1);
+        Ok(Spool { dir, head, tail })
+    }
+
+    /// Number of messages waiting in the spool.
+    pub fn len(&self) -> usize {
+        (self.tail - self.head) as usize
+    }
+
+    /// Whether the spool has no waiting message.
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    /// Persist `data` at the end of the spool.
+    ///
+    /// The message is synced to disk before this function returns.
+    pub fn push(&mut self, data: &[f64]) -> Result<()> {
+        let tmp = self.dir.join(format!("{:020}.tmp", self.tail));
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes_of(data))?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.path(self.tail))?;
+        self.tail += 1;
+        Ok(())
+    }
+
+    /// Send all the waiting messages to the `stream`, oldest first.
+    ///
+    /// This function is blocking.
+    ///
+    /// Each message is removed from the spool once acknowledged by the peer.
+    /// Returns the number of messages sent.
+    pub fn drain<S: Read + Write>(&mut self, stream: &mut S) -> Result<usize> {
+        let mut sent = 0;
+        while !self.is_empty() {
+            let path = self.path(self.head);
+            let data = match load(&path) {
+                // Removed behind our back, nothing to send
+                Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                    self.head += 1;
+                    continue;
+                }
+                result => result?,
+            };
+            hiwrite(stream, &data)?;
+            hidelimiter(stream)?;
+            fs::remove_file(&path)?;
+            self.head += 1;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// Send `data` to the `stream` after the waiting messages, spooling it if
+    /// it could not be delivered.
+    ///
+    /// This function is blocking.
+    ///
+    /// On error, `data` is safe in the spool: the caller only needs to
+    /// reconnect, and the next call to [`drain`] or `send` delivers it.
+    ///
+    /// [`drain`]: #method.drain
+    pub fn send<S: Read + Write>(&mut self, stream: &mut S, data: &[f64]) -> Result<()> {
+        if !self.is_empty() {
+            self.push(data)?;
+            return self.drain(stream).map(drop);
+        }
+
+        let result = hiwrite(stream, data).and_then(|_| hidelimiter(stream));
+        if result.is_err() {
+            self.push(data)?;
+        }
+        result
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.{}", id, EXTENSION))
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<f64>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+    let mut data = vec![0.0; len / 8];
+    file.read_exact(bytes_of_mut(&mut data))?;
+    Ok(data)
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::{HiConfig, HiStream, Result};
+
+/// A connection forwarded by the `ssh` client, from its standard input and
+/// output.
+///
+/// Open it with [`SshStream::connect`], or [`HiStream::connect_ssh`]. The
+/// `ssh` process runs as long as the stream, and is killed when it is
+/// dropped.
+///
+/// [`SshStream::connect`]: #method.connect
+/// [`HiStream::connect_ssh`]: struct.HiStream.html#method.connect_ssh
+pub struct SshStream {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+impl SshStream {
+    /// Log into `destination`, as `[user@]host`, and forward the connection
+    /// to `target`, as `host:port` seen from there.
+    ///
+    /// This runs `ssh -W target destination`, with the keys, agent and
+    /// configuration of the current user. Passwords cannot be prompted for:
+    /// authentication must succeed without interaction.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `ssh` cannot be run. Failures to log in or to reach `target`
+    /// are only reported by `ssh` on its standard error, and close the
+    /// stream.
+    pub fn connect(destination: &str, target: &str) -> io::Result<Self> {
+        let mut child = Command::new("ssh")
+            .args(["-o", "BatchMode=yes", "-W", target, "--", destination])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped standard input");
+        let stdout = child.stdout.take().expect("piped standard output");
+        Ok(SshStream {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl Read for SshStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl Write for SshStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
+impl Drop for SshStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl fmt::Debug for SshStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SshStream")
+            .field("pid", &self.child.id())
+            .finish()
+    }
+}
+
+impl HiStream<SshStream> {
+    /// Connect to the server at `target`, through an SSH tunnel to
+    /// `destination` opened with [`SshStream::connect`], and perform the
+    /// client side of the handshake.
+    ///
+    /// This function is blocking.
+    ///
+    /// [`SshStream::connect`]: struct.SshStream.html#method.connect
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let mut stream =
+    ///     HiStream::connect_ssh("alice@login.cluster.org", "node17:34567", HiConfig::new())?;
+    /// stream.send(&[1.0, 2.0, 3.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_ssh(destination: &str, target: &str, config: HiConfig) -> Result<Self> {
+        HiStream::client(SshStream::connect(destination, target)?, config)
+    }
+}
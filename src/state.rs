@@ -0,0 +1,184 @@
+use std::any;
+use std::fmt;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use crate::{hidelimiter, hiread, hiwrite, read_ack, Result, DELIMITER_NAN};
+
+/// State of a [`Connection`] between messages, free to send or receive.
+///
+/// [`Connection`]: struct.Connection.html
+#[derive(Debug)]
+pub enum Idle {}
+
+/// State of a [`Connection`] writing a *High Tension Message*.
+///
+/// [`Connection`]: struct.Connection.html
+#[derive(Debug)]
+pub enum Sending {}
+
+/// State of a [`Connection`] whose message is written, waiting for the
+/// receiver to acknowledge it.
+///
+/// [`Connection`]: struct.Connection.html
+#[derive(Debug)]
+pub enum AwaitingAck {}
+
+/// A stream speaking the plain protocol, like the free functions, whose
+/// state is tracked by its type.
+///
+/// Each step of a message consumes the connection and returns it in its
+/// next state, so that misuses, such as reading while a message is half
+/// written, or starting a message before the previous one is acknowledged,
+/// do not compile: only an [`Idle`] connection reads, and only a
+/// [`Sending`] one writes.
+///
+/// [`Idle`]: enum.Idle.html
+/// [`Sending`]: enum.Sending.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread, hiwrite, hidelimiter, pipe, Connection};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, mut server) = pipe();
+/// let echo = thread::spawn(move || -> hi_tension::Result<()> {
+///     let data = hiread(&mut server)?;
+///     hiwrite(&mut server, &data)?;
+///     hidelimiter(&mut server)
+/// });
+///
+/// let mut sending = Connection::new(client).start();
+/// sending.write(&[1.0, 2.0])?;
+/// sending.write(&[3.0])?;
+/// let mut idle = sending.end()?.wait_ack()?;
+///
+/// assert_eq!(idle.read()?, [1.0, 2.0, 3.0]);
+/// echo.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Reading in the middle of a message is a compile-time error:
+///
+/// ```compile_fail
+/// use hi_tension::{pipe, Connection};
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, _server) = pipe();
+/// let mut sending = Connection::new(client).start();
+/// sending.write(&[1.0, 2.0])?;
+/// sending.read()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Connection<S, State = Idle> {
+    stream: S,
+    state: PhantomData<State>,
+}
+
+impl<S: Read + Write> Connection<S, Idle> {
+    /// Wrap `stream`, between two messages.
+    pub fn new(stream: S) -> Self {
+        Connection {
+            stream,
+            state: PhantomData,
+        }
+    }
+
+    /// Read a *High Tension Message*, like [`hiread`].
+    ///
+    /// This function is blocking.
+    ///
+    /// [`hiread`]: fn.hiread.html
+    pub fn read(&mut self) -> Result<Vec<f64>> {
+        hiread(&mut self.stream)
+    }
+
+    /// Send `data` as a complete *High Tension Message*, and wait for its
+    /// acknowledgement.
+    ///
+    /// This function is blocking.
+    pub fn send(&mut self, data: &[f64]) -> Result<()> {
+        hiwrite(&mut self.stream, data)?;
+        hidelimiter(&mut self.stream)
+    }
+
+    /// Start a *High Tension Message*, to be written in parts.
+    pub fn start(self) -> Connection<S, Sending> {
+        self.into_state()
+    }
+}
+
+impl<S: Read + Write> Connection<S, Sending> {
+    /// Write `data` as part of the message, like [`hiwrite`].
+    ///
+    /// This function is blocking.
+    ///
+    /// [`hiwrite`]: fn.hiwrite.html
+    pub fn write(&mut self, data: &[f64]) -> Result<()> {
+        hiwrite(&mut self.stream, data)
+    }
+
+    /// End the message with its delimiter, without waiting for its
+    /// acknowledgement yet.
+    ///
+    /// This function is blocking, until the delimiter is written.
+    pub fn end(mut self) -> Result<Connection<S, AwaitingAck>> {
+        self.stream.write_all(&DELIMITER_NAN)?;
+        self.stream.flush()?;
+        Ok(self.into_state())
+    }
+}
+
+impl<S: Read + Write> Connection<S, AwaitingAck> {
+    /// Wait for the receiver to acknowledge the message.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::RemoteError`] if the receiver refused the message.
+    ///
+    /// [`Error::RemoteError`]: enum.Error.html#variant.RemoteError
+    pub fn wait_ack(mut self) -> Result<Connection<S, Idle>> {
+        read_ack(&mut self.stream)?;
+        Ok(self.into_state())
+    }
+}
+
+impl<S, State> Connection<S, State> {
+    fn into_state<Next>(self) -> Connection<S, Next> {
+        Connection {
+            stream: self.stream,
+            state: PhantomData,
+        }
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Unwrap this `Connection`, returning the underlying stream, whatever
+    /// its state.
+    ///
+    /// The peer is left in the middle of the message being sent, if any.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: fmt::Debug, State> fmt::Debug for Connection<S, State> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("stream", &self.stream)
+            .field(
+                "state",
+                &any::type_name::<State>().rsplit("::").next().unwrap_or(""),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,2391 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::ack::Checksum;
+use crate::adaptive::ChunkTuner;
+use crate::calibrate::{self, is_probe};
+use crate::fed::Fed;
+use crate::handshake::{self, Fields};
+use crate::hmac::{self, HmacSha256, Sha256};
+use crate::memory::prefault;
+use crate::message::{self, ARRAY_PREFIX, TEXT_PREFIX};
+use crate::pause::{SendPause, PAUSED_TEXT, RESUMED_TEXT};
+use crate::pod::bytes_of;
+use crate::quota::Usage;
+use crate::retry::Retrying;
+use crate::schema;
+#[cfg(feature = "metrics")]
+use crate::ConnectionMetrics;
+use crate::{
+    hiwrite, hiwrite_chunked, pack_bytes, read_ack, read_chunks_until, read_frame, refuse,
+};
+use crate::{is_end, Journal, Result, Session, Timestamp, DELIMITER_NAN, END_NAN};
+use crate::{write_delimiter, CHUNK_SIZE, DEFAULT_SIZE};
+use crate::{AckStatus, Direction, FlowWindow, RecvBuffer, Reused};
+use crate::{ArrayRef, Error, FloatReport, HiConfig, Identity, Message, MessageRef};
+use crate::{Reducer, Schema};
+
+/// The single word of the message closing a connection: a NaN spelling
+/// `close`, like the delimiter is one.
+const CLOSE_NAN: [u8; 8] = *b"close\x00\xf8\x7f";
+
+/// The single word of the message checking that the peer is alive, skipped
+/// by receivers.
+const PING_NAN: [u8; 8] = *b"ping\x00\x00\xf8\x7f";
+
+const DEFAULT_DELIMITER: u64 = u64::from_le_bytes(DELIMITER_NAN);
+
+/// Handshake value of the `layout` field, asking for column-major matrices.
+const COLUMN_MAJOR: &str = "column-major";
+
+/// Room left for the trailer and the delimiter of a message, on top of its
+/// values, in buffers sized for them.
+const TRAILER_ROOM: usize = 64;
+
+/// Schema ID of the messages sent without schema.
+const NO_SCHEMA: u64 = u64::MAX;
+
+/// A connection speaking the `hi-tension` protocol with a given [`HiConfig`].
+///
+/// The free functions [`hiread`], [`hiwrite`] and [`hidelimiter`] implement the
+/// plain protocol. `HiStream` wraps any `Read + Write` stream and applies the
+/// options of its configuration on top of them.
+///
+/// [`HiConfig`]: struct.HiConfig.html
+/// [`hiread`]: fn.hiread.html
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+///
+/// A `HiStream` is either opened with a handshake, through [`client`] and
+/// [`server`], or created directly over an already established stream with
+/// [`new`].
+///
+/// [`client`]: #method.client
+/// [`server`]: #method.server
+/// [`new`]: #method.new
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{HiConfig, HiStream};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let config = HiConfig::new().hmac_key("shared secret");
+/// let mut stream = HiStream::client(TcpStream::connect("127.0.0.1:34567")?, config)?;
+///
+/// stream.send(&vec![0.0; 1_000_000])?;
+/// let data = stream.read()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HiStream<S> {
+    stream: Fed<Retrying<S>>,
+    config: HiConfig,
+    mac: Option<HmacSha256>,
+    session: Option<Session>,
+    journal: Option<Journal>,
+    stamp: Option<Timestamp>,
+    last_timestamp: Option<Timestamp>,
+    header: Vec<u8>,
+    last_header: Option<Vec<u8>>,
+    writing: bool,
+    in_frame: bool,
+    broken: bool,
+    at_boundary: bool,
+    batch: Vec<f64>,
+    batch_lens: Vec<f64>,
+    batch_start: usize,
+    unbatched: VecDeque<Vec<f64>>,
+    reused: Reused,
+    peer_identity: Option<Identity>,
+    peer_column_major: bool,
+    peer_delimiter: [u8; 8],
+    schema: Option<usize>,
+    last_schema: Option<usize>,
+    last_report: Option<FloatReport>,
+    sent_usage: Option<Usage>,
+    pause: SendPause,
+    window: FlowWindow,
+    received_usage: Option<Usage>,
+    peer_schemas: Vec<Schema>,
+    chunk_size: Option<usize>,
+    tuner: Option<ChunkTuner>,
+    peer_max_message: Option<usize>,
+    frame_len: usize,
+    coalesce: bool,
+    coalesced: Vec<u8>,
+    ack_status: bool,
+    sent_checksum: Checksum,
+    last_ack: Option<AckStatus>,
+    queue_depth: u64,
+    ack_timeout: Option<(TcpStream, Duration)>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<ConnectionMetrics>,
+}
+
+impl<S: Read + Write> HiStream<S> {
+    /// Wrap `stream` into a `HiStream` using `config`, without any handshake.
+    pub fn new(stream: S, config: HiConfig) -> Self {
+        let delimiter = config.delimiter.to_le_bytes();
+        let schemas = config.schemas.clone();
+        let chunk_size = config.chunk_size;
+        let tuner = config.adaptive_chunks.then(|| ChunkTuner::new(chunk_size));
+        let max_message = config.max_message;
+        let sent_usage = config.send_quota.map(Usage::new);
+        let received_usage = config.receive_quota.map(Usage::new);
+        let ack_status = config.ack_status;
+        HiStream {
+            stream: Fed::new(Retrying::new(stream, config.retry.clone())),
+            config,
+            mac: None,
+            session: None,
+            journal: None,
+            stamp: None,
+            last_timestamp: None,
+            header: Vec::new(),
+            last_header: None,
+            writing: false,
+            in_frame: false,
+            broken: false,
+            at_boundary: false,
+            batch: Vec::new(),
+            batch_lens: Vec::new(),
+            batch_start: 0,
+            unbatched: VecDeque::new(),
+            reused: Reused::default(),
+            peer_identity: None,
+            peer_column_major: false,
+            peer_delimiter: delimiter,
+            schema: None,
+            last_schema: None,
+            last_report: None,
+            sent_usage,
+            pause: SendPause::new(),
+            window: FlowWindow::new(),
+            received_usage,
+            peer_schemas: schemas,
+            chunk_size,
+            tuner,
+            peer_max_message: max_message,
+            frame_len: 0,
+            coalesce: false,
+            coalesced: Vec::new(),
+            ack_status,
+            sent_checksum: Checksum::default(),
+            last_ack: None,
+            queue_depth: 0,
+            ack_timeout: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Open a `HiStream` over `stream` by performing the client side of the
+    /// handshake.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::AuthFailed`] if the server refused the access token
+    /// of `config`, and with [`Error::Handshake`] if the server is not
+    /// speaking the same protocol, or defines a [`Schema`] differently.
+    ///
+    /// [`Error::AuthFailed`]: enum.Error.html#variant.AuthFailed
+    /// [`Error::Handshake`]: enum.Error.html#variant.Handshake
+    /// [`Schema`]: struct.Schema.html
+    pub fn client(stream: S, config: HiConfig) -> Result<Self> {
+        Self::open_client(stream, config, None)
+    }
+
+    /// Open a `HiStream` over `stream` like [`client`], asking the server to
+    /// reattach to `session`.
+    ///
+    /// This function is blocking.
+    ///
+    /// If the server does not know this session anymore, it opens a new one:
+    /// check the [`Session::id`] of [`session`] to find out.
+    ///
+    /// [`client`]: #method.client
+    /// [`session`]: #method.session
+    /// [`Session::id`]: struct.Session.html#method.id
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let stream = HiStream::client(tcp, HiConfig::new())?;
+    /// let session = stream.session().cloned().expect("server without sessions");
+    /// drop(stream);
+    ///
+    /// // Later on, after the connection was lost
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::resume(tcp, HiConfig::new(), &session)?;
+    /// stream.send_pending()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume(stream: S, config: HiConfig, session: &Session) -> Result<Self> {
+        Self::open_client(stream, config, Some(session))
+    }
+
+    fn open_client(mut stream: S, config: HiConfig, resume: Option<&Session>) -> Result<Self> {
+        let mut request = Fields::new();
+        if let Some(token) = &config.token {
+            request.push("token", token.as_str());
+        }
+        if let Some(identity) = &config.identity {
+            identity.to_fields(&mut request);
+        }
+        if let Some(session) = resume {
+            request.push("session", session.id().to_string());
+        }
+        if config.column_major {
+            request.push("layout", COLUMN_MAJOR);
+        }
+        if config.delimiter != DEFAULT_DELIMITER {
+            request.push("delimiter", format!("{:016x}", config.delimiter));
+        }
+        for schema in &config.schemas {
+            request.push("schema", schema.to_field());
+        }
+        if let Some(len) = config.max_message {
+            request.push("max-message", len.to_string());
+        }
+        if config.ack_status {
+            request.push("ack-status", "on");
+        }
+
+        let reply = handshake::client(&mut stream, &request)?;
+        let peer_schemas = parse_schemas(&reply)?;
+        let peer_max_message = parse_max_message(&reply)?;
+        if let Some(name) = schema::drift(&config.schemas, &peer_schemas) {
+            return Err(schema_mismatch(name));
+        }
+
+        let mut hi = Self::new(stream, config);
+        hi.peer_identity = Identity::from_fields(&reply);
+        hi.peer_column_major = reply.get("layout") == Some(COLUMN_MAJOR);
+        hi.peer_delimiter = parse_delimiter(&reply)?;
+        hi.peer_schemas = peer_schemas;
+        hi.peer_max_message = peer_max_message;
+        hi.ack_status &= reply.get("ack-status") == Some("on");
+        if let Some(id) = reply.get("session") {
+            let id = id
+                .parse()
+                .map_err(|_| Error::Handshake(format!("invalid session ID {:?}", id)))?;
+            hi.session = match resume {
+                Some(session) if session.id() == id => Some(session.clone()),
+                _ => Some(Session::new(id)),
+            };
+        }
+        Ok(hi)
+    }
+
+    /// Open a `HiStream` over `stream` by performing the server side of the
+    /// handshake.
+    ///
+    /// This function is blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::AuthFailed`] if `config` has an access token and
+    /// the client did not present the same one, and with [`Error::Handshake`]
+    /// if the client is not speaking the same protocol, or defines a
+    /// [`Schema`] differently.
+    ///
+    /// [`Error::AuthFailed`]: enum.Error.html#variant.AuthFailed
+    /// [`Error::Handshake`]: enum.Error.html#variant.Handshake
+    /// [`Schema`]: struct.Schema.html
+    pub fn server(mut stream: S, config: HiConfig) -> Result<Self> {
+        let request = handshake::read_request(&mut stream)?;
+        Self::server_with_request(stream, request, config)
+    }
+
+    /// Perform the server side of the handshake, once the `request` of the
+    /// client has been read from `stream`.
+    pub(crate) fn server_with_request(
+        mut stream: S,
+        request: Fields,
+        config: HiConfig,
+    ) -> Result<Self> {
+        let peer_delimiter = match parse_delimiter(&request) {
+            Ok(delimiter) => delimiter,
+            Err(e) => {
+                handshake::refuse(&mut stream, "invalid delimiter")?;
+                return Err(e);
+            }
+        };
+
+        if let Some(token) = &config.token {
+            let presented = request.get("token").unwrap_or_default();
+            if !token_eq(token, presented) {
+                handshake::refuse(&mut stream, handshake::UNAUTHORIZED)?;
+                return Err(Error::AuthFailed);
+            }
+        }
+
+        let peer_schemas = match parse_schemas(&request) {
+            Ok(schemas) => schemas,
+            Err(e) => {
+                handshake::refuse(&mut stream, "invalid schema")?;
+                return Err(e);
+            }
+        };
+        if let Some(name) = schema::drift(&config.schemas, &peer_schemas) {
+            handshake::refuse(&mut stream, &format!("schema mismatch {}", name))?;
+            return Err(schema_mismatch(name));
+        }
+        let peer_max_message = match parse_max_message(&request) {
+            Ok(len) => len,
+            Err(e) => {
+                handshake::refuse(&mut stream, "invalid max-message")?;
+                return Err(e);
+            }
+        };
+
+        let mut reply = Fields::new();
+        let session = config.sessions.as_ref().map(|store| {
+            let id = request.get("session").and_then(|id| id.parse().ok());
+            store.attach(id)
+        });
+        if let Some(session) = &session {
+            reply.push("session", session.id().to_string());
+        }
+        if let Some(identity) = &config.identity {
+            identity.to_fields(&mut reply);
+        }
+        if config.column_major {
+            reply.push("layout", COLUMN_MAJOR);
+        }
+        if config.delimiter != DEFAULT_DELIMITER {
+            reply.push("delimiter", format!("{:016x}", config.delimiter));
+        }
+        for schema in &config.schemas {
+            reply.push("schema", schema.to_field());
+        }
+        if let Some(len) = config.max_message {
+            reply.push("max-message", len.to_string());
+        }
+        if config.ack_status {
+            reply.push("ack-status", "on");
+        }
+
+        handshake::accept(&mut stream, &reply)?;
+        let mut hi = Self::new(stream, config);
+        hi.session = session;
+        hi.peer_identity = Identity::from_fields(&request);
+        hi.peer_column_major = request.get("layout") == Some(COLUMN_MAJOR);
+        hi.peer_delimiter = peer_delimiter;
+        hi.peer_schemas = peer_schemas;
+        hi.peer_max_message = peer_max_message;
+        hi.ack_status &= request.get("ack-status") == Some("on");
+        Ok(hi)
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream.inner.inner
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    ///
+    /// Writing to or reading from it directly may corrupt the framing of
+    /// messages.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream.inner.inner
+    }
+
+    /// Unwrap this `HiStream`, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream.inner.inner
+    }
+
+    /// Get the configuration of this `HiStream`.
+    pub fn config(&self) -> &HiConfig {
+        &self.config
+    }
+
+    /// Get the session this `HiStream` is attached to, if the server assigned
+    /// one during the handshake.
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// Archive every message sent or received from now on into `journal`.
+    ///
+    /// Returns the journal previously set, if any.
+    pub fn set_journal(&mut self, journal: Journal) -> Option<Journal> {
+        self.journal.replace(journal)
+    }
+
+    /// Get the journal of this `HiStream`, if any.
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Stop journaling, returning the journal.
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
+    }
+
+    /// Count the messages, bytes, errors and send latencies of this
+    /// `HiStream` into `metrics`, from now on.
+    ///
+    /// This requires the `metrics` feature. Returns the metrics previously
+    /// set, if any.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics(&mut self, metrics: ConnectionMetrics) -> Option<ConnectionMetrics> {
+        self.metrics.replace(metrics)
+    }
+
+    /// Stop counting into metrics, returning them.
+    ///
+    /// This requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn take_metrics(&mut self) -> Option<ConnectionMetrics> {
+        self.metrics.take()
+    }
+
+    /// Send `data` as part of the current *High Tension Message*.
+    ///
+    /// This function is blocking.
+    ///
+    /// Like [`hiwrite`], it may be called more than one time. The message shall
+    /// be ended by calling [`finish`].
+    ///
+    /// [`hiwrite`]: fn.hiwrite.html
+    /// [`finish`]: #method.finish
+    pub fn write(&mut self, data: &[f64]) -> Result<()> {
+        if !self.writing && self.pause.is_paused() {
+            self.stall()?;
+        }
+        if let Some(usage) = &mut self.sent_usage {
+            let bytes = data.len() as u64 * 8;
+            // Refusing the rest of a message would leave it halfway
+            if self.writing {
+                usage.add(bytes);
+            } else {
+                usage.reserve(bytes)?;
+            }
+        }
+        self.writing = true;
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &mut self.metrics {
+            metrics.write(data.len());
+        }
+        if self.config.batching.is_some() {
+            if let Some(journal) = &mut self.journal {
+                journal.write(data)?;
+            }
+            self.batch.extend_from_slice(data);
+            return Ok(());
+        }
+        self.write_frame(data)
+    }
+
+    /// Wait for sending to resume, notifying the peer with typed messages.
+    fn stall(&mut self) -> Result<()> {
+        if self.config.typed_messages {
+            self.send_text(PAUSED_TEXT)?;
+        }
+        self.pause.wait();
+        if self.config.typed_messages {
+            self.send_text(RESUMED_TEXT)?;
+        }
+        Ok(())
+    }
+
+    /// Pause the *High Tension Messages* sent from now on, until
+    /// [`resume_sending`].
+    ///
+    /// The next message stalls before its first value is written, see
+    /// [`SendPause`]. Sending can only be resumed from another thread, with
+    /// the handle of [`pause_handle`].
+    ///
+    /// [`resume_sending`]: #method.resume_sending
+    /// [`SendPause`]: struct.SendPause.html
+    /// [`pause_handle`]: #method.pause_handle
+    pub fn pause_sending(&self) {
+        self.pause.pause();
+    }
+
+    /// Resume sending, after [`pause_sending`].
+    ///
+    /// [`pause_sending`]: #method.pause_sending
+    pub fn resume_sending(&self) {
+        self.pause.resume();
+    }
+
+    /// Get a handle pausing and resuming the sending of this `HiStream`
+    /// from any thread.
+    pub fn pause_handle(&self) -> SendPause {
+        self.pause.clone()
+    }
+
+    /// Get the bytes of the current wire frame written but not acknowledged
+    /// by the peer yet.
+    ///
+    /// Messages waiting in a batch are not written yet, and do not count. See
+    /// [`FlowWindow`] to watch this amount from another thread.
+    ///
+    /// [`FlowWindow`]: struct.FlowWindow.html
+    pub fn in_flight_bytes(&self) -> u64 {
+        self.window.in_flight_bytes()
+    }
+
+    /// Get a handle watching the bytes in flight of this `HiStream` from any
+    /// thread.
+    pub fn window_handle(&self) -> FlowWindow {
+        self.window.clone()
+    }
+
+    /// Largest number of values the current wire frame can hold for the
+    /// peer to accept it, if it announced a limit: its limit, or less when
+    /// the trailer does not fit in the room the peer leaves for it.
+    fn frame_room(&self) -> Option<usize> {
+        self.peer_max_message.map(|limit| {
+            let frame = limit.saturating_add(TRAILER_ROOM);
+            limit.min(frame.saturating_sub(self.trailer_len()))
+        })
+    }
+
+    /// Number of floats sent after the values of the current wire frame,
+    /// delimiter included.
+    fn trailer_len(&self) -> usize {
+        let mut len = 1;
+        if !self.config.schemas.is_empty() {
+            len += 1;
+        }
+        if self.config.user_headers {
+            len += self.header.len().div_ceil(8) + 1;
+        }
+        if self.config.timestamps {
+            len += 2;
+        }
+        if self.config.hmac_key.is_some() {
+            len += hmac::TAG_SIZE / 8;
+        }
+        len
+    }
+
+    /// Fail with `Error::TooLarge` if the current wire frame, grown by `len`
+    /// values, would exceed what the peer accepts.
+    fn check_room(&self, len: usize) -> Result<()> {
+        match self.frame_room() {
+            Some(room) if self.frame_len.saturating_add(len) > room => Err(Error::TooLarge(room)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Send `data` as part of the current wire frame.
+    fn write_frame(&mut self, data: &[f64]) -> Result<()> {
+        self.check_room(data.len())?;
+        self.frame_len += data.len();
+        if self.config.prefault {
+            prefault(data);
+        }
+        if self.config.timestamps && self.stamp.is_none() {
+            self.stamp = Some(Timestamp::now());
+        }
+        self.authenticate(data);
+        let result = self.write_inner(data);
+        if result.is_err() {
+            self.abort_message();
+        }
+        result
+    }
+
+    fn write_inner(&mut self, data: &[f64]) -> Result<()> {
+        self.start_frame()?;
+        if let Some(journal) = &mut self.journal {
+            journal.write(data)?;
+        }
+        self.window.add(data.len() * 8);
+        if self.coalesce {
+            self.coalesced.extend_from_slice(bytes_of(data));
+            return Ok(());
+        }
+        match (&mut self.tuner, self.chunk_size) {
+            (Some(tuner), _) => tuner.write(&mut self.stream, data),
+            (None, Some(chunk_size)) => hiwrite_chunked(&mut self.stream, data, chunk_size),
+            (None, None) => hiwrite(&mut self.stream, data),
+        }
+    }
+
+    /// Write protocol bytes of the current wire frame.
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        self.window.add(bytes.len());
+        if self.coalesce {
+            self.coalesced.extend_from_slice(bytes);
+        } else {
+            self.stream.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// End the current *High Tension Message*, and wait for the other side to
+    /// acknowledge it.
+    ///
+    /// This function is blocking. If [`HiConfig::batching`] is set, the
+    /// message joins the current batch instead, which is only sent when full.
+    ///
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    pub fn finish(&mut self) -> Result<()> {
+        let max = match self.config.batching {
+            Some(max) => max,
+            None => return self.finish_frame(),
+        };
+        self.writing = false;
+        let len = self.batch.len() - self.batch_start;
+        self.batch_lens.push(f64::from_bits(len as u64));
+        self.batch_start = self.batch.len();
+        if let Some(journal) = &mut self.journal {
+            journal.commit(Direction::Sent)?;
+        }
+        if let Some(session) = &self.session {
+            session.count_sent();
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &mut self.metrics {
+            metrics.sent();
+        }
+
+        if self.batch_lens.len() >= max {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Send the messages of the current batch right away, as one wire frame,
+    /// if [`HiConfig::batching`] is set.
+    ///
+    /// This function is blocking.
+    ///
+    /// A message being written is not part of the batch yet, and stays out of
+    /// the frame.
+    ///
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    pub fn flush(&mut self) -> Result<()> {
+        if self.batch_lens.is_empty() {
+            return Ok(());
+        }
+        let count = self.batch_lens.len();
+        let rest = self.batch.split_off(self.batch_start);
+        let mut frame = std::mem::replace(&mut self.batch, rest);
+        self.batch_start = 0;
+        frame.append(&mut self.batch_lens);
+        frame.push(f64::from_bits(count as u64));
+        self.send_unrecorded(&frame)
+    }
+
+    /// Send the type prefix of the wire frame, if it was not sent yet.
+    fn start_frame(&mut self) -> Result<()> {
+        if self.config.typed_messages && !self.in_frame {
+            self.put(&[ARRAY_PREFIX])?;
+        }
+        self.in_frame = true;
+        Ok(())
+    }
+
+    /// End the current wire frame, and wait for the other side to acknowledge
+    /// it.
+    fn finish_frame(&mut self) -> Result<()> {
+        let result = self.finish_inner();
+        if result.is_err() {
+            self.abort_message();
+        }
+        if let Err(Error::RemoteError(_)) = result {
+            // The peer skipped the whole message, and waits for the next one
+            self.broken = false;
+        }
+        self.writing = false;
+        self.in_frame = false;
+        self.frame_len = 0;
+        self.window.clear();
+        result
+    }
+
+    fn finish_inner(&mut self) -> Result<()> {
+        // The user header may have grown since the values were written
+        self.check_room(0)?;
+        self.start_frame()?;
+        if !self.config.schemas.is_empty() {
+            let id = self.schema.map_or(NO_SCHEMA, |id| id as u64);
+            let words = [f64::from_bits(id)];
+            self.authenticate(&words);
+            self.put(bytes_of(&words))?;
+        }
+        if self.config.user_headers {
+            let header = std::mem::take(&mut self.header);
+            let mut words = pack_bytes(&header);
+            words.push(f64::from_bits(header.len() as u64));
+            self.authenticate(&words);
+            self.put(bytes_of(&words))?;
+        }
+        if self.config.timestamps {
+            let stamp = self.stamp.take().unwrap_or_else(Timestamp::now);
+            let words = stamp.to_words();
+            self.authenticate(&words);
+            self.put(bytes_of(&words))?;
+        }
+        if let Some(key) = &self.config.hmac_key {
+            let mac = self.mac.take().unwrap_or_else(|| HmacSha256::new(key));
+            let tag = mac.finalize();
+            if self.ack_status {
+                self.sent_checksum.update(&tag);
+            }
+            self.put(&tag)?;
+        }
+        self.end_frame()?;
+
+        if let Some(journal) = &mut self.journal {
+            journal.commit(Direction::Sent)?;
+        }
+        if let Some(session) = &self.session {
+            session.count_sent();
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &mut self.metrics {
+            metrics.sent();
+        }
+        Ok(())
+    }
+
+    /// Send the delimiter ending the frame, and wait for its acknowledgement,
+    /// at most for the ack timeout if any.
+    fn end_frame(&mut self) -> Result<()> {
+        if let Some((socket, timeout)) = &self.ack_timeout {
+            socket.set_read_timeout(Some(*timeout))?;
+        }
+        let result = self.end_frame_inner();
+        match (&self.ack_timeout, result) {
+            (Some((socket, _)), Err(Error::WouldBlock)) => {
+                // The peer may be stuck in the middle of the frame
+                let _ = socket.shutdown(Shutdown::Both);
+                Err(Error::Io(io::ErrorKind::TimedOut.into()))
+            }
+            (Some((socket, _)), result) => {
+                socket.set_read_timeout(None)?;
+                result
+            }
+            (None, result) => result,
+        }
+    }
+
+    fn end_frame_inner(&mut self) -> Result<()> {
+        if self.coalesce {
+            // The whole frame goes out in a single write
+            self.coalesced.extend_from_slice(&self.peer_delimiter);
+            self.stream.write_all(&self.coalesced)?;
+            self.coalesced.clear();
+            self.stream.flush()?;
+            read_ack(&mut self.stream)?;
+        } else {
+            write_delimiter(&mut self.stream, &self.peer_delimiter)?;
+        }
+        if self.ack_status {
+            self.check_ack()?;
+        }
+        Ok(())
+    }
+
+    /// Feed `words` to the HMAC and the checksum of the message being sent,
+    /// if enabled.
+    fn authenticate(&mut self, words: &[f64]) {
+        if let Some(key) = &self.config.hmac_key {
+            self.mac
+                .get_or_insert_with(|| HmacSha256::new(key))
+                .update(bytes_of(words));
+        }
+        if self.ack_status {
+            self.sent_checksum.update(bytes_of(words));
+        }
+    }
+
+    /// Read the status following the acknowledgement of the frame just
+    /// sent, and check it against what was sent.
+    fn check_ack(&mut self) -> Result<()> {
+        let mut bytes = [0; AckStatus::SIZE];
+        self.stream.read_exact(&mut bytes)?;
+        let status = AckStatus::from_bytes(&bytes);
+        let sent = std::mem::take(&mut self.sent_checksum);
+        self.last_ack = Some(status);
+        if status.bytes != sent.bytes() || status.checksum != sent.value() {
+            return Err(Error::Invalid("peer received a corrupted message".into()));
+        }
+        Ok(())
+    }
+
+    /// Forget the state of a message which failed to be sent.
+    fn abort_message(&mut self) {
+        // The peer is left in the middle of the message
+        self.broken = true;
+        self.in_frame = false;
+        self.frame_len = 0;
+        self.coalesce = false;
+        self.coalesced.clear();
+        self.mac = None;
+        self.sent_checksum = Checksum::default();
+        self.window.clear();
+        self.stamp = None;
+        self.header.clear();
+        if let Some(journal) = &mut self.journal {
+            journal.discard();
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &mut self.metrics {
+            metrics.error();
+        }
+    }
+
+    /// Whether the peer asked for matrices in column-major order during the
+    /// handshake.
+    pub(crate) fn peer_column_major(&self) -> bool {
+        self.peer_column_major
+    }
+
+    /// Whether messages of a batch were received but not read yet.
+    pub(crate) fn has_unbatched(&self) -> bool {
+        !self.unbatched.is_empty()
+    }
+
+    /// Whether this `HiStream` is between messages and can still carry new
+    /// ones: no message is being written, and no transfer failed halfway.
+    pub(crate) fn is_reusable(&self) -> bool {
+        !self.writing && !self.broken
+    }
+
+    /// Set the user header of the message being sent, if
+    /// [`HiConfig::user_headers`] is set.
+    ///
+    /// The header can be set at any time before [`finish`], and applies to
+    /// that message only. It counts towards the limit announced by the peer
+    /// with [`HiConfig::max_message`], along with the rest of the trailer: a
+    /// message whose header does not fit fails with [`Error::TooLarge`],
+    /// before its values are sent if the header was set first.
+    ///
+    /// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+    /// [`finish`]: #method.finish
+    /// [`HiConfig::max_message`]: struct.HiConfig.html#method.max_message
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, HiConfig::new().user_headers())?;
+    ///
+    /// for frame in 0u64.. {
+    ///     stream.set_header(&frame.to_le_bytes());
+    ///     stream.send(&[0.0; 1024])?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_header(&mut self, header: &[u8]) {
+        self.header.clear();
+        self.header.extend_from_slice(header);
+    }
+
+    /// Tag the messages sent from now on with the schema `name`, registered
+    /// with [`HiConfig::schema`], or with no schema at all.
+    ///
+    /// With [`HiConfig::batching`], the schema applies to whole frames, so
+    /// every message of a batch must fit it.
+    ///
+    /// [`HiConfig::schema`]: struct.HiConfig.html#method.schema
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    ///
+    /// # Panics
+    ///
+    /// Panics if no schema named `name` is registered.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream, Schema};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let config = HiConfig::new().schema(Schema::new("spectrum", "f64").shape(&[Some(4096)]));
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, config)?;
+    ///
+    /// stream.set_schema(Some("spectrum"));
+    /// stream.send(&[0.0; 4096])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_schema(&mut self, name: Option<&str>) {
+        self.schema = name.map(|name| {
+            self.config
+                .schemas
+                .iter()
+                .position(|s| s.name() == name)
+                .unwrap_or_else(|| panic!("unknown schema {}", name))
+        });
+    }
+
+    /// Send `data` as a complete *High Tension Message*.
+    ///
+    /// This is a shorthand for [`write`] followed by [`finish`]. With
+    /// [`HiConfig::small_messages`], small messages are sent with a single
+    /// write.
+    ///
+    /// [`HiConfig::small_messages`]: struct.HiConfig.html#method.small_messages
+    /// [`write`]: #method.write
+    /// [`finish`]: #method.finish
+    pub fn send(&mut self, data: &[f64]) -> Result<()> {
+        let small = self
+            .config
+            .small_messages
+            .is_some_and(|max| data.len() <= max);
+        if small && !self.writing && self.config.batching.is_none() {
+            self.coalesce = true;
+            let result = self.write(data).and_then(|_| self.finish());
+            self.coalesce = false;
+            return result;
+        }
+        self.write(data)?;
+        self.finish()
+    }
+
+    /// Send each of the fixed-size `frames` as a *High Tension Message* of its
+    /// own, like [`hiwrite_frames`], and return how many were sent.
+    ///
+    /// This function is blocking. With [`HiConfig::batching`], the frames are
+    /// acknowledged by batches, and the last batch is flushed before
+    /// returning.
+    ///
+    /// [`hiwrite_frames`]: fn.hiwrite_frames.html
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, HiConfig, HiStream};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<Vec<f64>>> {
+    ///     let mut stream = HiStream::server(server, HiConfig::new().batching(32))?;
+    ///     (0..100).map(|_| stream.read()).collect()
+    /// });
+    ///
+    /// let mut stream = HiStream::client(client, HiConfig::new().batching(32))?;
+    /// stream.send_frames((0..100).map(|i| [f64::from(i); 4]))?;
+    /// assert_eq!(consumer.join().unwrap()?[42], [42.0; 4]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_frames<I, const N: usize>(&mut self, frames: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = [f64; N]>,
+    {
+        let mut sent = 0;
+        for frame in frames {
+            self.send(&frame)?;
+            sent += 1;
+        }
+        self.flush()?;
+        Ok(sent)
+    }
+
+    /// Send `data` as a message of any length, split into as many *High
+    /// Tension Messages* as the limit announced by the peer requires, to be
+    /// reassembled with [`read_split`].
+    ///
+    /// This function is blocking. A first message holds the length of
+    /// `data`, and each of the next ones a segment of at most
+    /// [`peer_max_message`] floats. With [`HiConfig::batching`], each of
+    /// them goes in its own frame, two floats smaller to leave room for the
+    /// batch lengths. Segments are smaller still when the trailer of the
+    /// messages, such as a long user header, does not fit in the room the
+    /// peer leaves for it.
+    ///
+    /// [`read_split`]: #method.read_split
+    /// [`peer_max_message`]: #method.peer_max_message
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, HiConfig, HiStream};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+    ///     let mut stream = HiStream::server(server, HiConfig::new().max_message(1000))?;
+    ///     stream.read_split()
+    /// });
+    ///
+    /// let mut stream = HiStream::client(client, HiConfig::new())?;
+    /// assert_eq!(stream.peer_max_message(), Some(1000));
+    /// let data: Vec<_> = (0..2500).map(f64::from).collect();
+    /// stream.send_split(&data)?;
+    /// assert_eq!(consumer.join().unwrap()?, data);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_split(&mut self, data: &[f64]) -> Result<()> {
+        let batched = self.config.batching.is_some();
+        let room = match self.frame_room() {
+            Some(max) if batched => max.saturating_sub(2).max(1),
+            Some(max) => max.max(1),
+            None => usize::MAX,
+        };
+        let announcement = [f64::from_bits(data.len() as u64)];
+        for message in std::iter::once(&announcement[..]).chain(data.chunks(room)) {
+            self.send(message)?;
+            if batched {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send `text` as a *Simple Text Message*.
+    ///
+    /// This function is blocking. The batch being sent, if any, is flushed
+    /// first so that messages keep their order.
+    ///
+    /// The receiver can only tell text messages apart from *High Tension
+    /// Messages* if [`HiConfig::typed_messages`] is set, and then gets them
+    /// with [`recv`]. Text messages are not acknowledged, and none of the
+    /// other options of the configuration apply to them.
+    ///
+    /// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+    /// [`recv`]: #method.recv
+    ///
+    /// # Panics
+    ///
+    /// Panics if `text` contains a newline, or if a *High Tension Message* is
+    /// being written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, HiConfig::new().typed_messages())?;
+    ///
+    /// stream.send_text("calibration run 12")?;
+    /// stream.send(&vec![0.0; 1_000_000])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_text(&mut self, text: &str) -> Result<()> {
+        assert!(
+            !text.contains('\n'),
+            "text messages cannot contain newlines"
+        );
+        assert!(
+            !self.writing,
+            "text message sent in the middle of a High Tension Message"
+        );
+        self.flush()?;
+        let mut bytes = Vec::with_capacity(text.len() + 2);
+        if self.config.typed_messages {
+            bytes.push(TEXT_PREFIX);
+        }
+        bytes.extend_from_slice(text.as_bytes());
+        bytes.push(b'\n');
+        self.stream
+            .write_all(&bytes)
+            .and_then(|_| self.stream.flush())
+            .inspect_err(|_| self.broken = true)?;
+        Ok(())
+    }
+
+    /// Send the messages queued in the session of this `HiStream`, oldest
+    /// first.
+    ///
+    /// This function is blocking.
+    ///
+    /// A message leaves the queue only once acknowledged, so messages which
+    /// could not be sent on this connection wait for the next one.
+    pub fn send_pending(&mut self) -> Result<()> {
+        let session = match &self.session {
+            Some(session) => session.clone(),
+            None => return Ok(()),
+        };
+        while let Some(data) = session.front_pending() {
+            self.send(&data)?;
+            session.pop_pending();
+        }
+        Ok(())
+    }
+
+    /// Send again the journaled messages at positions within `range`, whether
+    /// they were originally sent or received.
+    ///
+    /// This function is blocking.
+    ///
+    /// Replayed messages are not journaled a second time. Positions past the
+    /// end of the journal are ignored, and nothing is sent if this `HiStream`
+    /// has no journal.
+    pub fn replay(&mut self, range: Range<usize>) -> Result<()> {
+        let journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+        let result = range
+            .map_while(|i| journal.read(i).transpose())
+            .try_for_each(|data| self.send(&data?));
+        self.journal = Some(journal);
+        result
+    }
+
+    /// Send again the journaled messages at positions within `range` like
+    /// [`replay`], at the pace they were recorded, `speed` times faster.
+    ///
+    /// This function is blocking. Each message is sent once the time elapsed
+    /// since the first one was recorded, divided by `speed`, has passed: a
+    /// `speed` of 1 replays a recorded run in real time, and an infinite one
+    /// as fast as [`replay`]. Messages late because the peer or the network
+    /// is slower than the recording are sent right away, and the next ones
+    /// keep the original schedule.
+    ///
+    /// [`replay`]: #method.replay
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` is not strictly positive.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream, Journal};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, HiConfig::new())?;
+    /// let journal = Journal::open("run-42")?;
+    /// let len = journal.len();
+    /// stream.set_journal(journal);
+    ///
+    /// // Replay the whole recorded run, twice as fast
+    /// stream.replay_paced(0..len, 2.0)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn replay_paced(&mut self, range: Range<usize>, speed: f64) -> Result<()> {
+        assert!(speed > 0.0, "replay speed must be strictly positive");
+        let journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+        let start = Instant::now();
+        let origin = journal.entries().get(range.start).map(|e| e.timestamp);
+        let result = range
+            .map_while(|i| Some((i, journal.entries().get(i)?)))
+            .try_for_each(|(i, entry)| {
+                let offset = origin
+                    .and_then(|origin| entry.timestamp.duration_since(origin).ok())
+                    .unwrap_or_default();
+                let due = Duration::try_from_secs_f64(offset.as_secs_f64() / speed)
+                    .ok()
+                    .and_then(|offset| start.checked_add(offset));
+                std::thread::sleep(due.map_or(Duration::MAX, |due| {
+                    due.saturating_duration_since(Instant::now())
+                }));
+                match journal.read(i)? {
+                    Some(data) => self.send(&data),
+                    None => Ok(()),
+                }
+            });
+        self.journal = Some(journal);
+        result
+    }
+
+    /// Measure which chunk size makes writes the fastest on this connection,
+    /// like [`hicalibrate`], and use it from now on.
+    ///
+    /// This function is blocking. The peer discards the probe messages. Returns
+    /// the chunk size chosen, in bytes.
+    ///
+    /// [`hicalibrate`]: fn.hicalibrate.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpStream;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::client(tcp, HiConfig::new())?;
+    ///
+    /// let chunk_size = stream.calibrate()?;
+    /// println!("writing {} bytes at a time", chunk_size);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn calibrate(&mut self) -> Result<usize> {
+        self.flush()?;
+        let previous = self.chunk_size;
+        let tuner = self.tuner.take();
+        let result = calibrate::calibrate_with(|probe, chunk_size| {
+            self.chunk_size = Some(chunk_size);
+            self.send_unrecorded(probe)
+        });
+        self.chunk_size = previous;
+        self.tuner = tuner;
+        let chunk_size = result?;
+        self.chunk_size = Some(chunk_size);
+        if let Some(tuner) = &mut self.tuner {
+            *tuner = ChunkTuner::new(Some(chunk_size));
+        }
+        Ok(chunk_size)
+    }
+
+    /// Get the size of the writes of this `HiStream`, in bytes, if they are
+    /// chunked, as currently tuned with [`HiConfig::adaptive_chunks`].
+    ///
+    /// [`HiConfig::adaptive_chunks`]: struct.HiConfig.html#method.adaptive_chunks
+    pub fn chunk_size(&self) -> Option<usize> {
+        self.tuner
+            .as_ref()
+            .map(ChunkTuner::size)
+            .or(self.chunk_size)
+    }
+
+    /// Get the timestamp of the last message received by [`read`], if
+    /// [`HiConfig::timestamps`] is set.
+    ///
+    /// [`read`]: #method.read
+    /// [`HiConfig::timestamps`]: struct.HiConfig.html#method.timestamps
+    pub fn last_timestamp(&self) -> Option<Timestamp> {
+        self.last_timestamp
+    }
+
+    /// Get the user header of the last message received by [`read`], if
+    /// [`HiConfig::user_headers`] is set.
+    ///
+    /// [`read`]: #method.read
+    /// [`HiConfig::user_headers`]: struct.HiConfig.html#method.user_headers
+    pub fn last_header(&self) -> Option<&[u8]> {
+        self.last_header.as_deref()
+    }
+
+    /// Get the schema of the last message received by [`read`], if the peer
+    /// tagged it with one.
+    ///
+    /// [`read`]: #method.read
+    pub fn last_schema(&self) -> Option<&Schema> {
+        self.last_schema.map(|id| &self.peer_schemas[id])
+    }
+
+    /// Get the counts of special values of the last message received by
+    /// [`read`], if [`HiConfig::float_report`] is set.
+    ///
+    /// [`read`]: #method.read
+    /// [`HiConfig::float_report`]: struct.HiConfig.html#method.float_report
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::net::TcpListener;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (tcp, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+    /// let mut stream = HiStream::server(tcp, HiConfig::new().float_report())?;
+    ///
+    /// let data = stream.read()?;
+    /// let report = stream.last_report().unwrap();
+    /// if !report.is_finite() {
+    ///     eprintln!("{} NaN and {} infinite values", report.nan, report.infinite);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last_report(&self) -> Option<FloatReport> {
+        self.last_report
+    }
+
+    /// Get the status the peer sent with its last acknowledgement, if
+    /// [`HiConfig::ack_status`] is used on this connection.
+    ///
+    /// [`HiConfig::ack_status`]: struct.HiConfig.html#method.ack_status
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, HiConfig, HiStream};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+    ///     let mut stream = HiStream::server(server, HiConfig::new().ack_status())?;
+    ///     stream.set_queue_depth(3);
+    ///     stream.read()
+    /// });
+    ///
+    /// let mut stream = HiStream::client(client, HiConfig::new().ack_status())?;
+    /// stream.send(&[1.0, 2.0, 3.0])?;
+    /// let status = stream.last_ack().unwrap();
+    /// assert_eq!(status.bytes, 24);
+    /// assert_eq!(status.queue_depth, 3);
+    /// consumer.join().unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last_ack(&self) -> Option<AckStatus> {
+        self.last_ack
+    }
+
+    /// Set the queue depth reported to the peer along with the next
+    /// acknowledgements, if [`HiConfig::ack_status`] is used on this
+    /// connection, such as the number of messages waiting to be processed.
+    ///
+    /// [`HiConfig::ack_status`]: struct.HiConfig.html#method.ack_status
+    pub fn set_queue_depth(&mut self, depth: u64) {
+        self.queue_depth = depth;
+    }
+
+    /// Get the identity the peer announced during the handshake with
+    /// [`HiConfig::identity`], if any.
+    ///
+    /// [`HiConfig::identity`]: struct.HiConfig.html#method.identity
+    pub fn peer_identity(&self) -> Option<&Identity> {
+        self.peer_identity.as_ref()
+    }
+
+    /// Get the largest message the peer accepts, in floats, if it announced
+    /// one during the handshake with [`HiConfig::max_message`].
+    ///
+    /// Larger messages are refused with [`Error::TooLarge`] before being
+    /// sent: use [`send_split`] to send them anyway.
+    ///
+    /// [`HiConfig::max_message`]: struct.HiConfig.html#method.max_message
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    /// [`send_split`]: #method.send_split
+    pub fn peer_max_message(&self) -> Option<usize> {
+        self.peer_max_message
+    }
+
+    /// Get the schemas announced by the peer during the handshake.
+    ///
+    /// A `HiStream` created with [`new`] assumes the peer has the same schemas
+    /// as its own configuration.
+    ///
+    /// [`new`]: #method.new
+    pub fn peer_schemas(&self) -> &[Schema] {
+        &self.peer_schemas
+    }
+
+    /// Read a *High Tension Message*.
+    ///
+    /// This function is blocking, and allocates like [`hiread`].
+    ///
+    /// [`hiread`]: fn.hiread.html
+    ///
+    /// # Errors
+    ///
+    /// If the peer called [`close`], the closing is confirmed and
+    /// [`Error::Closed`] is returned. A peer which disconnects without closing
+    /// results in an IO error instead.
+    ///
+    /// A message which does not fit the shape of its schema is rejected with
+    /// [`Error::Invalid`]. With [`HiConfig::typed_messages`], a text message
+    /// is rejected with [`Error::Framing`]; use [`recv`] to get both kinds.
+    ///
+    /// [`close`]: #method.close
+    /// [`Error::Closed`]: enum.Error.html#variant.Closed
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    /// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+    /// [`recv`]: #method.recv
+    pub fn read(&mut self) -> Result<Vec<f64>> {
+        match self.recv()? {
+            Message::Array(data) => Ok(data),
+            Message::Empty => Ok(Vec::new()),
+            Message::Text(_) => Err(unexpected_text()),
+        }
+    }
+
+    /// Read a message sent with [`send_split`], reassembling its segments.
+    ///
+    /// This function is blocking, and allocates like [`read`] for each
+    /// segment.
+    ///
+    /// [`send_split`]: #method.send_split
+    /// [`read`]: #method.read
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`read`], and with [`Error::Framing`] if the messages do not
+    /// make a split message.
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    pub fn read_split(&mut self) -> Result<Vec<f64>> {
+        let len = match self.read()?[..] {
+            [len] => usize::try_from(len.to_bits()).ok(),
+            _ => None,
+        };
+        let len = len.ok_or_else(|| Error::Framing("invalid split message length".into()))?;
+        let mut data = Vec::new();
+        while data.len() < len {
+            let segment = self.read()?;
+            if segment.is_empty() || segment.len() > len - data.len() {
+                return Err(Error::Framing(
+                    "segment past the end of a split message".into(),
+                ));
+            }
+            data.extend_from_slice(&segment);
+        }
+        Ok(data)
+    }
+
+    /// Read a message of either kind, if [`HiConfig::typed_messages`] is set.
+    ///
+    /// This function is blocking. Without typed messages, every message is a
+    /// [`Message::Array`], like [`read`] returns, except for empty ones which
+    /// are a [`Message::Empty`].
+    ///
+    /// [`HiConfig::typed_messages`]: struct.HiConfig.html#method.typed_messages
+    /// [`Message::Array`]: enum.Message.html#variant.Array
+    /// [`Message::Empty`]: enum.Message.html#variant.Empty
+    /// [`read`]: #method.read
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`read`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream, Message};
+    /// use std::net::TcpListener;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (tcp, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+    /// let mut stream = HiStream::server(tcp, HiConfig::new().typed_messages())?;
+    ///
+    /// loop {
+    ///     match stream.recv()? {
+    ///         Message::Text(text) => println!("note: {}", text),
+    ///         Message::Array(data) => println!("{} floats", data.len()),
+    ///         Message::Empty => println!("barrier"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn recv(&mut self) -> Result<Message> {
+        let result = self.recv_inner();
+        self.counted(result)
+    }
+
+    fn recv_inner(&mut self) -> Result<Message> {
+        self.check_receive_quota()?;
+        let message = match self.config.batching {
+            Some(_) => self.read_batched()?,
+            None => self.read_frame()?,
+        };
+        if let Message::Array(data) = &message {
+            self.check_schema(data)?;
+            self.record_received(data)?;
+            if data.is_empty() {
+                return Ok(Message::Empty);
+            }
+        }
+        Ok(message)
+    }
+
+    /// Read a message of either kind like [`recv`], into a buffer reused
+    /// across messages.
+    ///
+    /// This function is blocking. The buffer is allocated like [`hiread`]
+    /// does for the first message only, so hot loops receive every next
+    /// message without allocating. The array is borrowed until the next
+    /// message is received: copy it with `to_vec` to keep it longer. With
+    /// [`HiConfig::batching`], messages are still copied out of their frame.
+    ///
+    /// [`recv`]: #method.recv
+    /// [`hiread`]: fn.hiread.html
+    /// [`HiConfig::batching`]: struct.HiConfig.html#method.batching
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`read`].
+    ///
+    /// [`read`]: #method.read
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream, MessageRef};
+    /// use std::net::TcpListener;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (tcp, _) = TcpListener::bind("0.0.0.0:34567")?.accept()?;
+    /// let mut stream = HiStream::server(tcp, HiConfig::new())?;
+    ///
+    /// let mut total = 0.0;
+    /// loop {
+    ///     if let MessageRef::Array(data) = stream.recv_ref()? {
+    ///         total += data.iter().sum::<f64>();
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn recv_ref(&mut self) -> Result<MessageRef<'_>> {
+        let result = self.recv_reused();
+        if let Some(text) = self.counted(result)? {
+            return Ok(MessageRef::Text(text));
+        }
+        match self.reused.as_slice() {
+            [] => Ok(MessageRef::Empty),
+            data => Ok(MessageRef::Array(ArrayRef::new(data))),
+        }
+    }
+
+    /// Same as `recv`, receiving arrays into the reused buffer. Returns the
+    /// text of text messages.
+    fn recv_reused(&mut self) -> Result<Option<String>> {
+        self.check_receive_quota()?;
+        if self.config.batching.is_some() {
+            match self.read_batched()? {
+                Message::Text(text) => return Ok(Some(text)),
+                Message::Array(data) => self.reused.set(data),
+                Message::Empty => self.reused.set(Vec::new()),
+            }
+        } else if let Some(text) = self.read_frame_reused()? {
+            return Ok(Some(text));
+        }
+
+        let reused = std::mem::take(&mut self.reused);
+        let result = self
+            .check_schema(reused.as_slice())
+            .and_then(|_| self.record_received(reused.as_slice()));
+        self.reused = reused;
+        result.map(|_| None)
+    }
+
+    /// Same as `read_frame`, receiving arrays into the reused buffer. Returns
+    /// the text of text messages.
+    fn read_frame_reused(&mut self) -> Result<Option<String>> {
+        loop {
+            if let Some(text) = self.receive_text()? {
+                return Ok(Some(text));
+            }
+            let mut reused = std::mem::take(&mut self.reused);
+            let result = self.receive_into(&mut reused);
+            self.reused = reused;
+            result?;
+            if is_close(self.reused.as_slice()) {
+                self.send_close()?;
+                return Err(Error::Closed);
+            }
+            if !is_probe(self.reused.as_slice()) && !is_ping(self.reused.as_slice()) {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Fail if the receive quota is exhausted, before reading anything.
+    fn check_receive_quota(&mut self) -> Result<()> {
+        match &mut self.received_usage {
+            Some(usage) => usage.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that a message received fits the shape of its schema.
+    fn check_schema(&self, data: &[f64]) -> Result<()> {
+        // Empty messages are barriers, whatever the schema
+        if let Some(schema) = self.last_schema().filter(|_| !data.is_empty()) {
+            if !schema.accepts(data.len()) {
+                return Err(Error::Invalid(format!(
+                    "{} values do not fit the shape of schema {}",
+                    data.len(),
+                    schema.name()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Journal, count and report on a message received.
+    fn record_received(&mut self, data: &[f64]) -> Result<()> {
+        if let Some(usage) = &mut self.received_usage {
+            usage.add(data.len() as u64 * 8);
+        }
+        if self.config.float_report {
+            let mut report = FloatReport::new();
+            report.update(data);
+            self.last_report = Some(report);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.write(data)?;
+            journal.commit(Direction::Received)?;
+        }
+        if let Some(session) = &self.session {
+            session.count_received();
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.received(data.len());
+        }
+        Ok(())
+    }
+
+    /// Count a failure in the metrics, unless the peer closed the connection.
+    fn counted<T>(&mut self, result: Result<T>) -> Result<T> {
+        #[cfg(feature = "metrics")]
+        if let (Err(e), Some(metrics)) = (&result, &mut self.metrics) {
+            let starved = matches!(e, Error::WouldBlock) && self.stream.is_feeding();
+            if !matches!(e, Error::Closed) && !starved {
+                metrics.error();
+            }
+        }
+        result
+    }
+
+    /// Read a wire frame, confirming the closing of the connection if that is
+    /// what the peer asked for.
+    ///
+    /// While feeding, the stream fails with `Error::WouldBlock` once the bytes
+    /// fed are all read rather than skipping a ping or a probe, for the caller
+    /// to feed it the next message. The bytes of a frame fed partly are read
+    /// again with the next attempt, the stream left as if they were not.
+    fn read_frame(&mut self) -> Result<Message> {
+        loop {
+            let start = self.stream.consumed();
+            let (broken, at_boundary) = (self.broken, self.at_boundary);
+            let message = match self.receive_message() {
+                Err(Error::WouldBlock) if self.stream.is_feeding() => {
+                    self.stream.rewind(start);
+                    self.broken = broken;
+                    self.at_boundary = at_boundary;
+                    return Err(Error::WouldBlock);
+                }
+                result => result?,
+            };
+            match message {
+                Message::Array(data) if is_close(&data) => {
+                    self.send_close()?;
+                    return Err(Error::Closed);
+                }
+                Message::Array(data) if is_probe(&data) || is_ping(&data) => {
+                    if self.stream.is_drained() {
+                        return Err(Error::WouldBlock);
+                    }
+                }
+                message => return Ok(message),
+            }
+        }
+    }
+
+    /// Read the next message of the current batch, receiving a new one if
+    /// needed. The batch being sent is flushed first, in case the peer waits
+    /// for it to answer.
+    fn read_batched(&mut self) -> Result<Message> {
+        self.flush()?;
+        loop {
+            if let Some(data) = self.unbatched.pop_front() {
+                return Ok(Message::Array(data));
+            }
+            let frame = match self.read_frame()? {
+                Message::Array(frame) => frame,
+                other => return Ok(other),
+            };
+            if is_end(&frame) {
+                return Ok(Message::Array(frame));
+            }
+            self.unbatched = unbatch(&frame)?;
+        }
+    }
+
+    /// Check that the peer is alive, returning the round-trip time.
+    ///
+    /// This function is blocking. The batch being sent, if any, is flushed
+    /// first. The ping is a message of its own, acknowledged like any other,
+    /// but the receiving `HiStream` skips it: it is neither handed over to
+    /// the application, nor journaled, nor counted in the session.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, HiConfig, HiStream};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+    ///     let mut stream = HiStream::server(server, HiConfig::new())?;
+    ///     stream.read()
+    /// });
+    ///
+    /// let mut stream = HiStream::client(client, HiConfig::new())?;
+    /// let rtt = stream.ping()?;
+    /// println!("round trip in {:?}", rtt);
+    /// stream.send(&[1.0])?;
+    /// assert_eq!(consumer.join().unwrap()?, [1.0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ping(&mut self) -> Result<Duration> {
+        self.flush()?;
+        let start = Instant::now();
+        self.send_unrecorded(&[f64::from_le_bytes(PING_NAN)])?;
+        Ok(start.elapsed())
+    }
+
+    /// Refuse the next *High Tension Message* of the peer, telling it the
+    /// `reason` why, such as a full disk.
+    ///
+    /// This function is blocking. The message is read and skipped as it
+    /// arrives, and the peer gets an [`Error::RemoteError`] holding `reason`
+    /// instead of its acknowledgement, rather than a broken connection. Text
+    /// messages arriving first are skipped too. The `reason` is cut to 4096
+    /// bytes, and its newlines replaced by spaces.
+    ///
+    /// [`Error::RemoteError`]: enum.Error.html#variant.RemoteError
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, Error, HiConfig, HiStream};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+    ///     let mut stream = HiStream::server(server, HiConfig::new())?;
+    ///     stream.reject("disk full, aborting")?;
+    ///     stream.read()
+    /// });
+    ///
+    /// let mut stream = HiStream::client(client, HiConfig::new())?;
+    /// match stream.send(&vec![0.0; 100_000]) {
+    ///     Err(Error::RemoteError(reason)) => assert_eq!(reason, "disk full, aborting"),
+    ///     result => panic!("message not refused: {:?}", result),
+    /// }
+    /// stream.send(&[1.0])?;
+    /// assert_eq!(consumer.join().unwrap()?, [1.0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reject(&mut self, reason: &str) -> Result<()> {
+        self.skip_frame()
+            .and_then(|()| refuse(&mut self.stream, reason))
+            .inspect_err(|_| self.broken = true)
+    }
+
+    /// Skip the bytes received up to the end of the next wire frame, after
+    /// a reception failed halfway, such as with [`Error::Framing`] or
+    /// [`Error::TooLarge`], and get ready to receive the next message.
+    ///
+    /// This function is blocking, and returns the number of bytes skipped.
+    ///
+    /// The delimiter is looked for at any offset, in case the peer did not
+    /// send whole words, until a read ends with it: a well-behaved peer then
+    /// waits for the acknowledgement. Every frame skipped is refused, so the
+    /// peer gets an [`Error::RemoteError`] for each of them and carries on.
+    /// Since bytes up to a delimiter are dropped, the message following the
+    /// broken one may be lost too. [`HiConfig::auto_resync`] calls this
+    /// function after every such failure.
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    /// [`Error::TooLarge`]: enum.Error.html#variant.TooLarge
+    /// [`Error::RemoteError`]: enum.Error.html#variant.RemoteError
+    /// [`HiConfig::auto_resync`]: struct.HiConfig.html#method.auto_resync
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hi_tension::{pipe, Error, HiConfig, HiStream};
+    /// use std::io::Write;
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (client, server) = pipe();
+    /// let producer = thread::spawn(move || -> hi_tension::Result<()> {
+    ///     let mut stream = HiStream::client(client, HiConfig::new())?;
+    ///     // Bytes which are not whole words throw the framing off
+    ///     stream.get_mut().write_all(b"oops")?;
+    ///     assert!(matches!(stream.send(&[0.0; 1000]), Err(Error::RemoteError(_))));
+    ///     stream.send(&[1.0, 2.0])
+    /// });
+    ///
+    /// let mut stream = HiStream::server(server, HiConfig::new().strict_framing())?;
+    /// assert!(matches!(stream.read(), Err(Error::Framing(_))));
+    /// stream.resync()?;
+    /// assert_eq!(stream.read()?, [1.0, 2.0]);
+    /// producer.join().unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resync(&mut self) -> Result<usize> {
+        if std::mem::take(&mut self.at_boundary) {
+            // The frames were already refused, up to the last one
+            self.broken = false;
+            return Ok(0);
+        }
+        let delimiter = u64::from_le_bytes(self.config.delimiter.to_le_bytes());
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut window = 0u64;
+        let mut skipped = 0;
+        let mut frames = 0;
+        loop {
+            let n = match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let mut last = false;
+            for &byte in &buf[..n] {
+                // The last 8 bytes received, in little endian order
+                window = (window >> 8) | u64::from(byte) << 56;
+                skipped += 1;
+                last = skipped >= 8 && window == delimiter;
+                frames += usize::from(last);
+            }
+            if last {
+                break;
+            }
+        }
+        for _ in 0..frames {
+            refuse(&mut self.stream, "message discarded to resynchronize")?;
+        }
+        self.broken = false;
+        self.unbatched.clear();
+        Ok(skipped)
+    }
+
+    /// Read the next wire frame up to its delimiter, discarding it.
+    fn skip_frame(&mut self) -> Result<()> {
+        if self.config.typed_messages {
+            while message::read_prefix(&mut self.stream)? == TEXT_PREFIX {
+                message::read_text(&mut self.stream)?;
+            }
+        }
+        let delimiter = self.config.delimiter.to_le_bytes();
+        read_chunks_until(&mut self.stream, &delimiter, |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Signal the end of a dataset to the peer, like [`hiend`].
+    ///
+    /// This function is blocking. The message ending the dataset is neither
+    /// journaled nor counted in the session.
+    ///
+    /// [`hiend`]: fn.hiend.html
+    pub fn send_end(&mut self) -> Result<()> {
+        self.flush()?;
+        self.send_unrecorded(&[f64::from_le_bytes(END_NAN)])
+    }
+
+    /// Read messages until the end of the dataset, signaled by the peer with
+    /// [`send_end`].
+    ///
+    /// This function is blocking.
+    ///
+    /// [`send_end`]: #method.send_end
+    pub fn read_all(&mut self) -> Result<Vec<Vec<f64>>> {
+        let result = self.read_all_inner();
+        self.counted(result)
+    }
+
+    fn read_all_inner(&mut self) -> Result<Vec<Vec<f64>>> {
+        let mut arrays = Vec::new();
+        loop {
+            self.check_receive_quota()?;
+            let message = match self.config.batching {
+                Some(_) => self.read_batched()?,
+                None => self.read_frame()?,
+            };
+            let data = match message {
+                Message::Array(data) => data,
+                Message::Empty => Vec::new(),
+                Message::Text(_) => return Err(unexpected_text()),
+            };
+            if is_end(&data) {
+                return Ok(arrays);
+            }
+            self.check_schema(&data)?;
+            self.record_received(&data)?;
+            arrays.push(data);
+        }
+    }
+
+    /// Close the connection in an orderly way, and get the underlying stream
+    /// back.
+    ///
+    /// This function is blocking.
+    ///
+    /// The messages queued in the session are sent first, then a closing
+    /// message. The peer confirms it from [`read`], which returns
+    /// [`Error::Closed`] so that deliberate disconnects can be told apart from
+    /// crashes. Messages still arriving before the confirmation are discarded.
+    ///
+    /// This waits for the confirmation indefinitely; see [`close_timeout`] to
+    /// bound the wait on a `TcpStream`.
+    ///
+    /// [`read`]: #method.read
+    /// [`Error::Closed`]: enum.Error.html#variant.Closed
+    /// [`close_timeout`]: #method.close_timeout
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{Error, HiConfig, HiStream};
+    /// use std::net::TcpListener;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let listener = TcpListener::bind("127.0.0.1:34567")?;
+    /// let mut stream = HiStream::server(listener.accept()?.0, HiConfig::new())?;
+    ///
+    /// loop {
+    ///     match stream.read() {
+    ///         Ok(data) => println!("received {} floats", data.len()),
+    ///         Err(Error::Closed) => break println!("client is done"),
+    ///         Err(e) => break println!("client crashed: {}", e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close(mut self) -> Result<S> {
+        self.send_pending()?;
+        self.flush()?;
+        self.send_close()?;
+        loop {
+            if let Message::Array(data) = self.receive_message()? {
+                if is_close(&data) {
+                    break;
+                }
+            }
+        }
+        Ok(self.stream.inner.inner)
+    }
+
+    /// Send the closing message.
+    fn send_close(&mut self) -> Result<()> {
+        self.send_unrecorded(&[f64::from_le_bytes(CLOSE_NAN)])
+    }
+
+    /// Send `data` as a wire frame of its own, which is neither journaled nor
+    /// counted in the session or the metrics, except for its failure.
+    fn send_unrecorded(&mut self, data: &[f64]) -> Result<()> {
+        let journal = self.journal.take();
+        let session = self.session.take();
+        let schema = self.schema.take();
+        #[cfg(feature = "metrics")]
+        let metrics = self.metrics.take();
+        let result = self.write_frame(data).and_then(|_| self.finish_frame());
+        self.journal = journal;
+        self.session = session;
+        self.schema = schema;
+        #[cfg(feature = "metrics")]
+        {
+            self.metrics = metrics;
+        }
+        self.counted(result)
+    }
+
+    /// Read the next message, of either kind with typed messages.
+    fn receive_message(&mut self) -> Result<Message> {
+        match self.receive_text()? {
+            Some(text) => Ok(Message::Text(text)),
+            None => self.receive().map(Message::Array),
+        }
+    }
+
+    /// Read the type prefix of the next message with typed messages, and the
+    /// message itself if it is a text one.
+    fn receive_text(&mut self) -> Result<Option<String>> {
+        if !self.config.typed_messages {
+            return Ok(None);
+        }
+        let prefix = message::read_prefix(&mut self.stream).inspect_err(|_| self.broken = true)?;
+        if prefix != TEXT_PREFIX {
+            return Ok(None);
+        }
+        let limit = self
+            .config
+            .max_message
+            .map_or(usize::MAX, |len| len.saturating_mul(8));
+        let text = message::read_text_limited(&mut self.stream, limit)
+            .inspect_err(|_| self.broken = true)?;
+        Ok(Some(text))
+    }
+
+    /// Read a *High Tension Message* and strip its trailer.
+    fn receive(&mut self) -> Result<Vec<f64>> {
+        let size = match self.config.small_messages {
+            Some(max) => max + TRAILER_ROOM,
+            None => DEFAULT_SIZE,
+        };
+        let mut data = vec![0.0; size.min(self.receive_limit())];
+        self.read_limited(&mut data)?;
+        let len = self.strip_trailer(&data)?;
+        self.check_len(len)?;
+        data.truncate(len);
+        Ok(data)
+    }
+
+    /// Same as `receive`, into a buffer whose memory is reused across
+    /// messages.
+    fn receive_into(&mut self, reused: &mut Reused) -> Result<()> {
+        reused.prepare();
+        self.read_limited(reused)?;
+        let len = self.strip_trailer(reused.as_slice())?;
+        self.check_len(len)?;
+        reused.truncate(len);
+        Ok(())
+    }
+
+    /// Read a wire frame into `buf`, within the limit of the configuration.
+    fn read_limited<B: RecvBuffer>(&mut self, buf: &mut B) -> Result<()> {
+        let delimiter = self.config.delimiter.to_le_bytes();
+        let limit = self.receive_limit();
+        let mut checksum = Checksum::default();
+        let ack_status = self.ack_status;
+        let mut synced = false;
+        let strict = if self.config.strict_framing {
+            Some(&mut synced)
+        } else {
+            None
+        };
+        let result = read_frame(
+            &mut self.stream,
+            buf,
+            0,
+            &delimiter,
+            limit,
+            strict,
+            |words| {
+                if ack_status {
+                    checksum.update(bytes_of(words));
+                }
+            },
+        )
+        .and_then(|()| {
+            if !ack_status {
+                return Ok(());
+            }
+            let status = AckStatus {
+                bytes: checksum.bytes(),
+                checksum: checksum.value(),
+                queue_depth: self.queue_depth,
+            };
+            self.stream.write_all(&status.to_bytes())?;
+            self.stream.flush()?;
+            Ok(())
+        });
+
+        let e = match result {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        self.broken = true;
+        self.at_boundary = synced;
+        let e = match (e, self.config.max_message) {
+            (Error::TooLarge(_), Some(max)) => Error::TooLarge(max),
+            (e, _) => e,
+        };
+        if self.config.auto_resync && matches!(e, Error::Framing(_) | Error::TooLarge(_)) {
+            // This message is lost, but the next ones are not
+            let _ = self.resync();
+        }
+        Err(e)
+    }
+
+    /// Append `bytes` received from the underlying stream to the ones
+    /// [`read_fed`] reads messages from.
+    ///
+    /// [`read_fed`]: #method.read_fed
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        self.stream.feed(bytes);
+    }
+
+    /// The bytes fed and not read yet.
+    pub(crate) fn fed(&self) -> &[u8] {
+        self.stream.fed()
+    }
+
+    /// Read a message like [`read`], from the bytes fed only, failing with
+    /// `Error::WouldBlock` if they run out, which leaves the stream usable:
+    /// they only held a ping or a probe, or part of a frame, kept to be read
+    /// again.
+    ///
+    /// [`read`]: #method.read
+    pub(crate) fn read_fed(&mut self) -> Result<Vec<f64>> {
+        self.stream.set_feeding(true);
+        let result = self.read();
+        self.stream.set_feeding(false);
+        result
+    }
+
+    /// Whether the bytes fed hold a whole message to read, or more than a
+    /// message may, for reading it to fail, or a batch holds one already.
+    ///
+    /// Peers wait for the acknowledgement of a message before sending the
+    /// next one, so a message ends with the last bytes fed, on a word
+    /// boundary. A frame which turns out to be partial is read again once
+    /// more bytes are fed.
+    pub(crate) fn is_message_fed(&self) -> bool {
+        if self.has_unbatched() {
+            return true;
+        }
+        let mut fed = self.stream.fed();
+        if self.stream.is_rewound() {
+            return false;
+        }
+        if self.config.typed_messages {
+            match fed.split_first() {
+                Some((&TEXT_PREFIX, _)) => return fed.contains(&b'\n'),
+                Some((_, words)) => fed = words,
+                None => return false,
+            }
+        }
+        let limit = self.receive_limit().saturating_mul(8);
+        let delimiter = self.config.delimiter.to_le_bytes();
+        fed.len() > limit || fed.len().is_multiple_of(8) && fed.ends_with(&delimiter)
+    }
+
+    /// Largest wire frame received, in floats, trailer and delimiter
+    /// included.
+    fn receive_limit(&self) -> usize {
+        self.config
+            .max_message
+            .map_or(usize::MAX, |max| max.saturating_add(TRAILER_ROOM))
+    }
+
+    /// Check the number of values of a received message against the limit
+    /// of the configuration.
+    pub(crate) fn check_len(&self, len: usize) -> Result<()> {
+        match self.config.max_message {
+            Some(max) if len > max => Err(Error::TooLarge(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check and strip the trailer of a received message, returning the
+    /// length of its user data.
+    fn strip_trailer(&mut self, mut data: &[f64]) -> Result<usize> {
+        if let Some(key) = &self.config.hmac_key {
+            let words = hmac::TAG_SIZE / 8;
+            if data.len() < words {
+                return Err(Error::AuthFailed);
+            }
+            let tag = split_trailer(&mut data, words)?;
+
+            let mut mac = HmacSha256::new(key);
+            mac.update(bytes_of(data));
+            if !hmac::ct_eq(&mac.finalize(), bytes_of(tag)) {
+                return Err(Error::AuthFailed);
+            }
+        }
+
+        self.last_timestamp = None;
+        if self.config.timestamps {
+            let words = split_trailer(&mut data, 2)?;
+            self.last_timestamp = Some(Timestamp::from_words(words));
+        }
+
+        self.last_header = None;
+        if self.config.user_headers {
+            let len = split_trailer(&mut data, 1)?[0].to_bits() as usize;
+            let words = len
+                .checked_add(7)
+                .ok_or_else(|| Error::Framing("invalid user header length".into()))?
+                / 8;
+            let words = split_trailer(&mut data, words)?;
+            self.last_header = Some(bytes_of(words)[..len].to_vec());
+        }
+
+        self.last_schema = None;
+        if !self.peer_schemas.is_empty() {
+            let id = split_trailer(&mut data, 1)?[0].to_bits();
+            if id != NO_SCHEMA {
+                if id >= self.peer_schemas.len() as u64 {
+                    return Err(Error::Framing(format!("unknown schema ID {}", id)));
+                }
+                self.last_schema = Some(id as usize);
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+impl HiStream<TcpStream> {
+    /// Wait at most `timeout` for the peer to acknowledge each message sent
+    /// from now on, or as long as needed with `None`.
+    ///
+    /// A receiver stalled for longer would otherwise wedge the sender. The
+    /// message whose acknowledgement is late fails to send with an IO error
+    /// of kind `TimedOut`, and the connection is shut down: the peer may
+    /// still get the message, or be stuck in its middle. Reconnecting and
+    /// sending it again, up to a number of times, is what
+    /// [`FailoverPolicy::ack_timeout`] does.
+    ///
+    /// The socket has no read timeout outside of these waits.
+    ///
+    /// [`FailoverPolicy::ack_timeout`]: struct.FailoverPolicy.html#method.ack_timeout
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hi_tension::{HiConfig, HiStream};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let mut stream = HiStream::connect("127.0.0.1:34567", HiConfig::new())?;
+    /// stream.set_ack_timeout(Some(Duration::from_secs(2)))?;
+    /// stream.send(&[1.0, 2.0, 3.0])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_ack_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.ack_timeout = match timeout {
+            Some(timeout) => {
+                assert!(!timeout.is_zero(), "acknowledgements need time to arrive");
+                Some((self.get_ref().try_clone()?, timeout))
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Close the connection like [`close`], waiting at most `timeout` for the
+    /// peer to confirm.
+    ///
+    /// [`close`]: #method.close
+    ///
+    /// # Errors
+    ///
+    /// An IO error of kind `TimedOut` is returned if the peer did not confirm
+    /// in time.
+    pub fn close_timeout(self, timeout: Duration) -> Result<TcpStream> {
+        let previous = self.get_ref().read_timeout()?;
+        self.get_ref().set_read_timeout(Some(timeout))?;
+        let stream = self.close().map_err(|e| match e {
+            Error::WouldBlock => Error::Io(io::ErrorKind::TimedOut.into()),
+            e => e,
+        })?;
+        stream.set_read_timeout(previous)?;
+        Ok(stream)
+    }
+}
+
+/// Split a wire frame holding a batch into its messages.
+///
+/// The messages are followed by their lengths, and then by their count.
+fn unbatch(frame: &[f64]) -> Result<VecDeque<Vec<f64>>> {
+    let malformed = || Error::Framing("malformed batch of messages".into());
+    let (count, rest) = frame.split_last().ok_or_else(malformed)?;
+    let count = count.to_bits() as usize;
+    let split = rest.len().checked_sub(count).ok_or_else(malformed)?;
+    let (mut data, lens) = rest.split_at(split);
+
+    let mut messages = VecDeque::with_capacity(count);
+    for len in lens {
+        let len = len.to_bits() as usize;
+        if len > data.len() {
+            return Err(malformed());
+        }
+        let (message, tail) = data.split_at(len);
+        messages.push_back(message.to_vec());
+        data = tail;
+    }
+    if !data.is_empty() {
+        return Err(malformed());
+    }
+    Ok(messages)
+}
+
+fn unexpected_text() -> Error {
+    Error::Framing("unexpected text message".into())
+}
+
+fn is_close(data: &[f64]) -> bool {
+    matches!(data, [word] if word.to_le_bytes() == CLOSE_NAN)
+}
+
+fn is_ping(data: &[f64]) -> bool {
+    matches!(data, [word] if word.to_le_bytes() == PING_NAN)
+}
+
+/// Get the delimiter of the messages to send, as announced by the peer.
+fn parse_delimiter(fields: &Fields) -> Result<[u8; 8]> {
+    let bits = match fields.get("delimiter") {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .map_err(|_| Error::Handshake(format!("invalid delimiter {:?}", hex)))?,
+        None => DEFAULT_DELIMITER,
+    };
+    Ok(bits.to_le_bytes())
+}
+
+/// Get the largest message accepted by the peer, if it announced one.
+fn parse_max_message(fields: &Fields) -> Result<Option<usize>> {
+    fields
+        .get("max-message")
+        .map(|len| {
+            len.parse()
+                .map_err(|_| Error::Handshake(format!("invalid max-message {:?}", len)))
+        })
+        .transpose()
+}
+
+/// Get the schemas announced by the peer.
+fn parse_schemas(fields: &Fields) -> Result<Vec<Schema>> {
+    fields.get_all("schema").map(Schema::from_field).collect()
+}
+
+fn schema_mismatch(name: &str) -> Error {
+    Error::Handshake(format!("schema {} defined differently by the peer", name))
+}
+
+/// Remove the last `words` floats of a received message, which carry protocol
+/// data rather than user data.
+fn split_trailer<'a>(data: &mut &'a [f64], words: usize) -> Result<&'a [f64]> {
+    if data.len() < words {
+        return Err(Error::Framing("message too short for its trailer".into()));
+    }
+    let (rest, trailer) = data.split_at(data.len() - words);
+    *data = rest;
+    Ok(trailer)
+}
+
+/// Compare tokens in constant time. Hashing them first hides their length too.
+fn token_eq(expected: &str, presented: &str) -> bool {
+    let digest = |token: &str| {
+        let mut hash = Sha256::new();
+        hash.update(token.as_bytes());
+        hash.finalize()
+    };
+    hmac::ct_eq(&digest(expected), &digest(presented))
+}
@@ -0,0 +1,97 @@
+use std::io::{self, Read, Write};
+
+use crate::pod::bytes_of;
+use crate::{read_chunks, Result};
+
+/// Read a *High Tension Message* from the `stream`, forwarding it to every one
+/// of the `sinks` as it arrives.
+///
+/// This function is blocking.
+///
+/// The message is never held in memory as a whole: each received chunk is
+/// written to all the sinks, in order, before the next one is read. Sinks
+/// receive the raw little-endian bytes of the floats, and are flushed after
+/// every chunk, so the message is only acknowledged once all of them got it.
+/// Returns the number of floats received.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::hiread_tee;
+/// use std::fs::File;
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+/// let mut file = File::create("data.f64")?;
+/// let mut forward = TcpStream::connect("127.0.0.1:34568")?;
+///
+/// let len = hiread_tee(&mut stream, &mut [&mut file, &mut forward])?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_tee<S: Read + Write>(stream: &mut S, sinks: &mut [&mut dyn Write]) -> Result<usize> {
+    let mut tee = Tee::new(
+        sinks
+            .iter_mut()
+            .map(|sink| &mut **sink as &mut dyn Write)
+            .collect(),
+    );
+    read_chunks(stream, |chunk| {
+        tee.write_all(bytes_of(chunk))?;
+        tee.flush()?;
+        Ok(())
+    })
+}
+
+/// A writer duplicating everything written to it into several sinks.
+///
+/// This is the adapter behind [`hiread_tee`], also usable on its own, e.g.
+/// with [`std::io::copy`].
+///
+/// [`hiread_tee`]: fn.hiread_tee.html
+/// [`std::io::copy`]: https://doc.rust-lang.org/std/io/fn.copy.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::Tee;
+/// use std::io::Write;
+///
+/// let mut a = Vec::new();
+/// let mut b = Vec::new();
+/// Tee::new(vec![&mut a, &mut b]).write_all(b"hi")?;
+///
+/// assert_eq!(a, b"hi");
+/// assert_eq!(b, b"hi");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Tee<'a> {
+    sinks: Vec<&'a mut dyn Write>,
+}
+
+impl<'a> Tee<'a> {
+    /// Create a `Tee` writing into all of the `sinks`.
+    pub fn new(sinks: Vec<&'a mut dyn Write>) -> Self {
+        Tee { sinks }
+    }
+}
+
+impl Write for Tee<'_> {
+    /// Write the whole `buf` into every sink.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for sink in &mut self.sinks {
+            sink.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for sink in &mut self.sinks {
+            sink.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! Tools to test applications against an unreliable network.
+//!
+//! [`FaultyStream`] wraps any transport and injects the failures real links
+//! eventually produce, so that recovery paths are exercised before they are
+//! needed.
+//!
+//! [`FaultyStream`]: struct.FaultyStream.html
+
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use crate::pattern::Generator;
+
+/// A stream injecting faults into the traffic of the stream it wraps.
+///
+/// Faults are drawn from a pseudo-random generator, so that a failing run can
+/// be replayed with the same seed. Every fault is disabled by default:
+/// - [`short_reads`] returns fewer bytes than asked for, as sockets do,
+/// - [`delays`] sleeps before reads and writes,
+/// - [`bit_flips`] corrupts bytes read,
+/// - [`interrupts`] fails reads and writes as if interrupted by a signal,
+/// - [`would_block`] fails reads and writes like a nonblocking socket,
+/// - [`disconnect_after`] fails every read and write past a number of bytes,
+///   like a peer vanishing in the middle of a message.
+///
+/// [`short_reads`]: #method.short_reads
+/// [`delays`]: #method.delays
+/// [`bit_flips`]: #method.bit_flips
+/// [`interrupts`]: #method.interrupts
+/// [`would_block`]: #method.would_block
+/// [`disconnect_after`]: #method.disconnect_after
+///
+/// # Examples
+///
+/// ```no_run
+/// use hi_tension::testing::FaultyStream;
+/// use hi_tension::{HiConfig, HiStream};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let tcp = TcpStream::connect("127.0.0.1:34567")?;
+/// let faulty = FaultyStream::new(tcp, 42)
+///     .short_reads(100)
+///     .bit_flips(1e-6)
+///     .disconnect_after(10_000_000);
+///
+/// let mut stream = HiStream::client(faulty, HiConfig::new().hmac_key("secret"))?;
+/// loop {
+///     // Corrupted messages are rejected, and the disconnect ends the loop
+///     match stream.read() {
+///         Ok(data) => println!("received {} floats", data.len()),
+///         Err(e) => break println!("failed with {}", e),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FaultyStream<S> {
+    stream: S,
+    generator: Generator,
+    max_read: Option<usize>,
+    max_delay: Option<Duration>,
+    flip_probability: f64,
+    interrupt_probability: f64,
+    would_block_probability: f64,
+    disconnect_after: Option<u64>,
+    transferred: u64,
+}
+
+impl<S> FaultyStream<S> {
+    /// Wrap `stream`, drawing faults from a generator seeded with `seed`.
+    pub fn new(stream: S, seed: u64) -> Self {
+        FaultyStream {
+            stream,
+            generator: Generator::new(seed),
+            max_read: None,
+            max_delay: None,
+            flip_probability: 0.0,
+            interrupt_probability: 0.0,
+            would_block_probability: 0.0,
+            disconnect_after: None,
+            transferred: 0,
+        }
+    }
+
+    /// Return at most a random number of bytes between 1 and `max` from every
+    /// read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn short_reads(mut self, max: usize) -> Self {
+        assert!(max > 0, "reads return at least one byte");
+        self.max_read = Some(max);
+        self
+    }
+
+    /// Sleep for a random duration up to `max` before every read and write.
+    pub fn delays(mut self, max: Duration) -> Self {
+        self.max_delay = Some(max);
+        self
+    }
+
+    /// Flip a random bit of every byte read with `probability`.
+    pub fn bit_flips(mut self, probability: f64) -> Self {
+        self.flip_probability = probability;
+        self
+    }
+
+    /// Fail reads and writes with an IO error of kind `Interrupted` with
+    /// `probability`, before any byte is transferred.
+    ///
+    /// The protocol retries such operations, so transfers still succeed:
+    ///
+    /// ```
+    /// use hi_tension::testing::FaultyStream;
+    /// use hi_tension::{hidelimiter, hiread, hiwrite, pipe};
+    /// use std::thread;
+    ///
+    /// # fn main() -> hi_tension::Result<()> {
+    /// let (a, b) = pipe();
+    /// let receiver = thread::spawn(move || hiread(&mut FaultyStream::new(b, 1).interrupts(0.5)));
+    ///
+    /// let mut stream = FaultyStream::new(a, 2).interrupts(0.5);
+    /// hiwrite(&mut stream, &[1.0, 2.0, 3.0])?;
+    /// hidelimiter(&mut stream)?;
+    /// assert_eq!(receiver.join().unwrap()?, [1.0, 2.0, 3.0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn interrupts(mut self, probability: f64) -> Self {
+        self.interrupt_probability = probability;
+        self
+    }
+
+    /// Fail reads and writes with an IO error of kind `WouldBlock` with
+    /// `probability`, like a socket in nonblocking mode.
+    ///
+    /// The protocol rejects nonblocking streams with [`Error::WouldBlock`],
+    /// unless a [`RetryPolicy`] waits for them:
+    ///
+    /// ```
+    /// use hi_tension::testing::FaultyStream;
+    /// use hi_tension::{hiread, pipe, Error};
+    ///
+    /// let (a, _b) = pipe();
+    /// let mut stream = FaultyStream::new(a, 1).would_block(1.0);
+    /// assert!(matches!(hiread(&mut stream), Err(Error::WouldBlock)));
+    /// ```
+    ///
+    /// [`Error::WouldBlock`]: ../enum.Error.html#variant.WouldBlock
+    /// [`RetryPolicy`]: ../struct.RetryPolicy.html
+    pub fn would_block(mut self, probability: f64) -> Self {
+        self.would_block_probability = probability;
+        self
+    }
+
+    /// Fail every read and write with an IO error of kind `ConnectionReset`
+    /// once `bytes` were read or written in total.
+    pub fn disconnect_after(mut self, bytes: u64) -> Self {
+        self.disconnect_after = Some(bytes);
+        self
+    }
+
+    /// Number of bytes read and written so far.
+    pub fn transferred(&self) -> u64 {
+        self.transferred
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Unwrap this `FaultyStream`, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// A random number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.generator.next_u64() % n
+    }
+
+    /// Sleep if delays are enabled, fail if an error is drawn, then get how
+    /// many more bytes can be transferred before the disconnect, up to `len`.
+    fn before_io(&mut self, len: usize) -> io::Result<usize> {
+        if let Some(max) = self.max_delay {
+            let nanos = self.below(max.as_nanos().min(u64::MAX as u128) as u64 + 1);
+            thread::sleep(Duration::from_nanos(nanos));
+        }
+        if self.interrupt_probability > 0.0 && self.generator.next() < self.interrupt_probability {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        if self.would_block_probability > 0.0
+            && self.generator.next() < self.would_block_probability
+        {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        match self.disconnect_after {
+            Some(limit) if self.transferred >= limit => Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "injected disconnect",
+            )),
+            Some(limit) => Ok(len.min((limit - self.transferred) as usize)),
+            None => Ok(len),
+        }
+    }
+}
+
+impl<S: Read> Read for FaultyStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.stream.read(buf);
+        }
+        let mut len = self.before_io(buf.len())?;
+        if let Some(max) = self.max_read {
+            len = len.min(self.below(max as u64) as usize + 1);
+        }
+
+        let n = self.stream.read(&mut buf[..len])?;
+        if self.flip_probability > 0.0 {
+            for byte in &mut buf[..n] {
+                if self.generator.next() < self.flip_probability {
+                    *byte ^= 1 << self.below(8);
+                }
+            }
+        }
+        self.transferred += n as u64;
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for FaultyStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.stream.write(buf);
+        }
+        let len = self.before_io(buf.len())?;
+        let n = self.stream.write(&buf[..len])?;
+        self.transferred += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
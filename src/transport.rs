@@ -0,0 +1,57 @@
+use std::io::{Read, Write};
+
+/// A byte stream to run `hi-tension` over, usable as a trait object.
+///
+/// `dyn Read + Write` is not a type Rust accepts, so transports chosen at
+/// runtime, such as the ones provided by plugins, are passed around as
+/// `Box<dyn Transport>` or `&mut dyn Transport` instead. `Transport` is
+/// implemented for every type implementing both [`Read`] and [`Write`], and
+/// has no method of its own.
+///
+/// A `Box<dyn Transport>` is a stream like any other: [`HiStream`] and every
+/// function of the crate accept it, without generics spreading through the
+/// application. The basic functions, such as [`hiread`], [`hiwrite`] and
+/// [`hidelimiter`], also accept a `&mut dyn Transport` directly.
+///
+/// [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+/// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
+/// [`HiStream`]: struct.HiStream.html
+/// [`hiread`]: fn.hiread.html
+/// [`hiwrite`]: fn.hiwrite.html
+/// [`hidelimiter`]: fn.hidelimiter.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hidelimiter, hiwrite, pipe, HiConfig, HiStream, Transport};
+/// use std::thread;
+///
+/// fn publish(transport: &mut dyn Transport, data: &[f64]) -> hi_tension::Result<()> {
+///     hiwrite(transport, data)?;
+///     hidelimiter(transport)
+/// }
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let client: Box<dyn Transport + Send> = Box::new(client);
+/// let server: Box<dyn Transport + Send> = Box::new(server);
+///
+/// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<Vec<f64>>> {
+///     let mut stream = HiStream::server(server, HiConfig::new())?;
+///     let first = stream.read()?;
+///     let mut transport = stream.into_inner();
+///     Ok(vec![first, hi_tension::hiread(&mut *transport)?])
+/// });
+///
+/// let mut stream = HiStream::client(client, HiConfig::new())?;
+/// stream.send(&[1.0, 2.0])?;
+/// let mut transport = stream.into_inner();
+/// publish(&mut *transport, &[3.0])?;
+///
+/// assert_eq!(consumer.join().unwrap()?, [vec![1.0, 2.0], vec![3.0]]);
+/// # Ok(())
+/// # }
+/// ```
+pub trait Transport: Read + Write {}
+
+impl<T: Read + Write + ?Sized> Transport for T {}
@@ -0,0 +1,276 @@
+use std::any;
+use std::fmt;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::mem;
+
+use crate::pod::{bytes_of, bytes_of_mut, read_unaligned, try_cast_slice, try_cast_vec};
+use crate::{Error, HiStream, Pod, Result};
+
+/// Values copied at a time when records are not aligned like words.
+const COPY_CHUNK: usize = 65_536;
+
+/// Words per value of `T`, checked at compile time.
+struct Words<T>(PhantomData<T>);
+
+impl<T> Words<T> {
+    const N: usize = {
+        let size = mem::size_of::<T>();
+        assert!(
+            size > 0 && size.is_multiple_of(8),
+            "typed records must be made of whole 8 bytes words"
+        );
+        size / 8
+    };
+}
+
+/// The name of `T`, without module paths, so that both ends agree on it even
+/// when they define it in different crates.
+fn type_name<T>() -> String {
+    let name = any::type_name::<T>();
+    let mut pieces = name.split("::").peekable();
+    let mut short = String::with_capacity(name.len());
+    while let Some(piece) = pieces.next() {
+        if pieces.peek().is_some() {
+            short.push_str(piece.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_'));
+        } else {
+            short.push_str(piece);
+        }
+    }
+    short
+}
+
+/// The tag exchanged when opening typed endpoints: a FNV-1a hash of the
+/// name of `T`, and its size.
+fn tag<T>() -> [f64; 2] {
+    let hash = type_name::<T>()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    [
+        f64::from_bits(hash),
+        f64::from_bits(mem::size_of::<T>() as u64),
+    ]
+}
+
+fn same_words(a: &[f64], b: &[f64]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_bits() == b.to_bits())
+}
+
+/// A [`HiStream`] sending slices of records of type `T`, read by a
+/// [`TypedReceiver`].
+///
+/// Records travel as their raw bytes, each message holding a whole slice.
+/// Their size must be a multiple of 8 bytes, which is checked at compile
+/// time. When opening the endpoints, both ends check that they agree on the
+/// name of `T`, without its module path, and on its size.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`TypedReceiver`]: struct.TypedReceiver.html
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, HiConfig, HiStream, Pod, TypedReceiver, TypedSender};
+/// use std::thread;
+///
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// #[repr(C)]
+/// struct Particle {
+///     position: [f64; 3],
+///     charge: f32,
+///     id: u32,
+/// }
+///
+/// // SAFETY: `repr(C)`, 32 bytes of `Pod` fields without padding
+/// unsafe impl Pod for Particle {}
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<Particle>> {
+///     let stream = HiStream::server(server, HiConfig::new())?;
+///     TypedReceiver::<_, Particle>::new(stream)?.recv()
+/// });
+///
+/// let stream = HiStream::client(client, HiConfig::new())?;
+/// let mut sender = TypedSender::<_, Particle>::new(stream)?;
+/// let particles: Vec<_> = (0..1000)
+///     .map(|id| Particle { position: [0.0; 3], charge: -1.0, id })
+///     .collect();
+/// sender.send(&particles)?;
+/// assert_eq!(consumer.join().unwrap()?, particles);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedSender<S, T> {
+    stream: HiStream<S>,
+    records: PhantomData<fn(&[T])>,
+}
+
+impl<S: Read + Write, T: Pod> TypedSender<S, T> {
+    /// Open the sending end of a typed channel on `stream`.
+    ///
+    /// This function is blocking, until the peer opens a [`TypedReceiver`].
+    ///
+    /// [`TypedReceiver`]: struct.TypedReceiver.html
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Invalid`] if the peer expects another type.
+    ///
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    pub fn new(mut stream: HiStream<S>) -> Result<Self> {
+        let _ = Words::<T>::N;
+        stream.send(&tag::<T>())?;
+        if !same_words(&stream.read()?, &[1.0]) {
+            return Err(Error::Invalid(format!(
+                "peer does not expect records of type {}",
+                type_name::<T>()
+            )));
+        }
+        Ok(TypedSender {
+            stream,
+            records: PhantomData,
+        })
+    }
+
+    /// Send `records` as a single *High Tension Message*.
+    ///
+    /// This function is blocking. Records aligned like words are sent in
+    /// place, others are copied by chunks first.
+    pub fn send(&mut self, records: &[T]) -> Result<()> {
+        if let Some(data) = try_cast_slice(records) {
+            return self.stream.send(data);
+        }
+        let mut chunk = vec![0.0; COPY_CHUNK.min(records.len()) * Words::<T>::N];
+        for records in records.chunks(COPY_CHUNK) {
+            let bytes = bytes_of(records);
+            let chunk = &mut chunk[..bytes.len() / 8];
+            bytes_of_mut(chunk).copy_from_slice(bytes);
+            self.stream.write(chunk)?;
+        }
+        self.stream.finish()
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &HiStream<S> {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut HiStream<S> {
+        &mut self.stream
+    }
+
+    /// Unwrap this `TypedSender`, returning the underlying stream.
+    pub fn into_inner(self) -> HiStream<S> {
+        self.stream
+    }
+}
+
+impl<S: fmt::Debug, T> fmt::Debug for TypedSender<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedSender")
+            .field("stream", &self.stream)
+            .field("type", &any::type_name::<T>())
+            .finish()
+    }
+}
+
+/// A [`HiStream`] receiving slices of records of type `T`, sent by a
+/// [`TypedSender`].
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`TypedSender`]: struct.TypedSender.html
+pub struct TypedReceiver<S, T> {
+    stream: HiStream<S>,
+    records: PhantomData<fn() -> T>,
+}
+
+impl<S: Read + Write, T: Pod> TypedReceiver<S, T> {
+    /// Open the receiving end of a typed channel on `stream`.
+    ///
+    /// This function is blocking, until the peer opens a [`TypedSender`].
+    ///
+    /// [`TypedSender`]: struct.TypedSender.html
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Invalid`] if the peer sends another type.
+    ///
+    /// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+    pub fn new(mut stream: HiStream<S>) -> Result<Self> {
+        let _ = Words::<T>::N;
+        let expected = same_words(&stream.read()?, &tag::<T>());
+        stream.send(&[if expected { 1.0 } else { 0.0 }])?;
+        if !expected {
+            return Err(Error::Invalid(format!(
+                "peer does not send records of type {}",
+                type_name::<T>()
+            )));
+        }
+        Ok(TypedReceiver {
+            stream,
+            records: PhantomData,
+        })
+    }
+
+    /// Read a slice of records, sent by [`TypedSender::send`].
+    ///
+    /// This function is blocking. Records aligned like words are received in
+    /// place, others are copied from the message.
+    ///
+    /// [`TypedSender::send`]: struct.TypedSender.html#method.send
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Framing`] if the message does not hold whole
+    /// records.
+    ///
+    /// [`Error::Framing`]: enum.Error.html#variant.Framing
+    pub fn recv(&mut self) -> Result<Vec<T>> {
+        let words = Words::<T>::N;
+        let mut data = self.stream.read()?;
+        if !data.len().is_multiple_of(words) {
+            return Err(Error::Framing(format!(
+                "message of {} words, not a whole number of records",
+                data.len()
+            )));
+        }
+        data.shrink_to_fit();
+        let data = match try_cast_vec(data) {
+            Ok(records) => return Ok(records),
+            Err(data) => data,
+        };
+        let records = bytes_of(&data)
+            .chunks(words * 8)
+            .map(read_unaligned)
+            .collect();
+        Ok(records)
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &HiStream<S> {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut HiStream<S> {
+        &mut self.stream
+    }
+
+    /// Unwrap this `TypedReceiver`, returning the underlying stream.
+    pub fn into_inner(self) -> HiStream<S> {
+        self.stream
+    }
+}
+
+impl<S: fmt::Debug, T> fmt::Debug for TypedReceiver<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedReceiver")
+            .field("stream", &self.stream)
+            .field("type", &any::type_name::<T>())
+            .finish()
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{Read, Write};
+
+use crate::{read_into_with, Error, Result, DEFAULT_SIZE, DELIMITER_NAN};
+
+/// A check run on the values of a message while it is received.
+///
+/// See [`hiread_validated`].
+///
+/// [`hiread_validated`]: fn.hiread_validated.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Validator {
+    /// Reject NaN values.
+    NoNan,
+    /// Reject NaN and infinite values.
+    Finite,
+    /// Reject values outside of `min..=max`, NaN included.
+    Range {
+        /// Smallest value allowed.
+        min: f64,
+        /// Largest value allowed.
+        max: f64,
+    },
+}
+
+impl Validator {
+    /// Describe why `x` is rejected, if it is.
+    fn check(self, x: f64) -> Option<String> {
+        match self {
+            Validator::NoNan if x.is_nan() => Some("NaN value".into()),
+            Validator::Finite if !x.is_finite() => Some(format!("non-finite value {}", x)),
+            Validator::Range { min, max } if !(min..=max).contains(&x) => {
+                Some(format!("value {} out of range {}..={}", x, min, max))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read a *High Tension Message* from the `stream`, checking its values with
+/// every one of `validators` as they arrive.
+///
+/// This function is blocking, and allocates like [`hiread`].
+///
+/// [`hiread`]: fn.hiread.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Invalid`] on the first value rejected, describing it
+/// along with its index. The rest of the message is still received, without
+/// being checked, so that the stream stays usable for the next messages.
+///
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```no_run
+/// use hi_tension::{hiread_validated, Validator};
+/// use std::net::TcpStream;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let mut stream = TcpStream::connect("127.0.0.1:34567")?;
+///
+/// let validators = [
+///     Validator::Finite,
+///     Validator::Range { min: 0.0, max: 1e6 },
+/// ];
+/// let data = hiread_validated(&mut stream, &validators)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiread_validated<S: Read + Write>(
+    stream: &mut S,
+    validators: &[Validator],
+) -> Result<Vec<f64>> {
+    let mut buf = vec![0.0; DEFAULT_SIZE];
+    let mut index = 0;
+    let mut invalid = None;
+    read_into_with(stream, &mut buf, 0, &DELIMITER_NAN, usize::MAX, |values| {
+        if invalid.is_some() {
+            return;
+        }
+        invalid = values.iter().enumerate().find_map(|(i, &x)| {
+            let reason = validators.iter().find_map(|v| v.check(x))?;
+            Some(format!("{} at index {}", reason, index + i))
+        });
+        index += values.len();
+    })?;
+
+    match invalid {
+        Some(reason) => Err(Error::Invalid(reason)),
+        None => Ok(buf),
+    }
+}
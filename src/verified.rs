@@ -0,0 +1,208 @@
+use std::io::{Read, Write};
+
+use crate::pod::bytes_of;
+use crate::{hidelimiter, hiread, hiwrite, Error, Result};
+
+/// Retransmissions attempted before giving up on corrupted chunks.
+const MAX_RETRANSMISSIONS: usize = 8;
+
+/// Table of the CRC-32 (IEEE) of every byte.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE) of `data`, as a word.
+pub(crate) fn checksum(data: &[f64]) -> f64 {
+    let mut crc = !0u32;
+    for &byte in bytes_of(data) {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    f64::from_bits(!crc as u64)
+}
+
+fn word(n: usize) -> f64 {
+    f64::from_bits(n as u64)
+}
+
+/// Send a `data` slice into the `stream` in checksummed chunks of
+/// `chunk_len` values, retransmitting only the chunks received corrupted.
+///
+/// This function is blocking.
+///
+/// The chunks are sent as a first *High Tension Message*, each followed by
+/// its CRC-32. The receiver, using [`hiread_verified`], answers with the
+/// indices of the corrupted chunks, which are then sent again, until none is
+/// left. On lossy paths, a corrupted chunk thus costs its own retransmission
+/// rather than the one of a whole multi-gigabyte message.
+///
+/// [`hiread_verified`]: fn.hiread_verified.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Invalid`] if chunks are still corrupted after 8
+/// retransmissions.
+///
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+///
+/// # Panics
+///
+/// Panics if `chunk_len` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{hiread_verified, hiwrite_verified, pipe};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (mut client, mut server) = pipe();
+/// let consumer = thread::spawn(move || hiread_verified(&mut server));
+///
+/// let data: Vec<f64> = (0..10_000).map(f64::from).collect();
+/// hiwrite_verified(&mut client, &data, 1024)?;
+/// assert_eq!(consumer.join().unwrap()?, data);
+/// # Ok(())
+/// # }
+/// ```
+pub fn hiwrite_verified<S: Read + Write>(
+    stream: &mut S,
+    data: &[f64],
+    chunk_len: usize,
+) -> Result<()> {
+    assert!(chunk_len > 0, "chunks hold at least one value");
+    hiwrite(stream, &[word(data.len()), word(chunk_len)])?;
+    for chunk in data.chunks(chunk_len) {
+        hiwrite(stream, chunk)?;
+        hiwrite(stream, &[checksum(chunk)])?;
+    }
+    hidelimiter(stream)?;
+
+    let mut retransmissions = 0;
+    loop {
+        let corrupted = hiread(stream)?;
+        if corrupted.is_empty() {
+            return Ok(());
+        }
+        if retransmissions == MAX_RETRANSMISSIONS {
+            return Err(still_corrupted(corrupted.len()));
+        }
+        retransmissions += 1;
+        for index in corrupted {
+            let start = (index.to_bits() as usize).saturating_mul(chunk_len);
+            let chunk = data
+                .get(start..data.len().min(start.saturating_add(chunk_len)))
+                .filter(|chunk| !chunk.is_empty())
+                .ok_or_else(|| Error::Framing("retransmission of an unknown chunk".into()))?;
+            hiwrite(stream, &[index])?;
+            hiwrite(stream, chunk)?;
+            hiwrite(stream, &[checksum(chunk)])?;
+        }
+        hidelimiter(stream)?;
+    }
+}
+
+/// Read a *High Tension Message* sent by [`hiwrite_verified`], asking for
+/// the retransmission of its corrupted chunks.
+///
+/// This function is blocking, and allocates like [`hiread`].
+///
+/// [`hiwrite_verified`]: fn.hiwrite_verified.html
+/// [`hiread`]: fn.hiread.html
+///
+/// # Errors
+///
+/// Fails with [`Error::Framing`] if the messages do not hold checksummed
+/// chunks, and with [`Error::Invalid`] if chunks are still corrupted after 8
+/// retransmissions.
+///
+/// [`Error::Framing`]: enum.Error.html#variant.Framing
+/// [`Error::Invalid`]: enum.Error.html#variant.Invalid
+pub fn hiread_verified<S: Read + Write>(stream: &mut S) -> Result<Vec<f64>> {
+    let malformed = || Error::Framing("malformed checksummed chunks".into());
+    let mut data = hiread(stream)?;
+    if data.len() < 2 {
+        return Err(malformed());
+    }
+    let len = data[0].to_bits() as usize;
+    let chunk_len = data[1].to_bits() as usize;
+    if chunk_len == 0 {
+        return Err(malformed());
+    }
+    let chunks = len.div_ceil(chunk_len);
+    if len.checked_add(2).and_then(|n| data.len().checked_sub(n)) != Some(chunks) {
+        return Err(malformed());
+    }
+
+    // Compact the chunks in place, dropping the header and the checksums
+    let mut corrupted = Vec::new();
+    for index in 0..chunks {
+        let start = index * chunk_len;
+        let n = chunk_len.min(len - start);
+        let from = 2 + start + index;
+        if checksum(&data[from..from + n]).to_bits() != data[from + n].to_bits() {
+            corrupted.push(word(index));
+        }
+        data.copy_within(from..from + n, start);
+    }
+    data.truncate(len);
+
+    let mut retransmissions = 0;
+    loop {
+        hiwrite(stream, &corrupted)?;
+        hidelimiter(stream)?;
+        if corrupted.is_empty() {
+            return Ok(data);
+        }
+        if retransmissions == MAX_RETRANSMISSIONS {
+            return Err(still_corrupted(corrupted.len()));
+        }
+        retransmissions += 1;
+
+        let resent = hiread(stream)?;
+        let mut rest = &resent[..];
+        let mut still = Vec::new();
+        for index in corrupted {
+            let start = index.to_bits() as usize * chunk_len;
+            let n = chunk_len.min(len - start);
+            if rest.len() < n + 2 || rest[0].to_bits() != index.to_bits() {
+                return Err(malformed());
+            }
+            let chunk = &rest[1..1 + n];
+            if checksum(chunk).to_bits() == rest[1 + n].to_bits() {
+                data[start..start + n].copy_from_slice(chunk);
+            } else {
+                still.push(index);
+            }
+            rest = &rest[n + 2..];
+        }
+        if !rest.is_empty() {
+            return Err(malformed());
+        }
+        corrupted = still;
+    }
+}
+
+fn still_corrupted(chunks: usize) -> Error {
+    Error::Invalid(format!(
+        "{} chunks still corrupted after {} retransmissions",
+        chunks, MAX_RETRANSMISSIONS
+    ))
+}
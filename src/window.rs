@@ -0,0 +1,122 @@
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct State {
+    in_flight: Mutex<u64>,
+    drained: Condvar,
+}
+
+/// The amount of data sent by a [`HiStream`] and not acknowledged yet,
+/// watched from any thread.
+///
+/// Get it with [`HiStream::window_handle`]. The bytes of a wire frame are in
+/// flight from the moment they are written, until the peer acknowledges the
+/// whole frame, so that a producer sees how fast its messages actually drain
+/// and throttles its output with [`wait_below`].
+///
+/// `FlowWindow` is a cheap handle: clones refer to the same window.
+///
+/// [`HiStream`]: struct.HiStream.html
+/// [`HiStream::window_handle`]: struct.HiStream.html#method.window_handle
+/// [`wait_below`]: #method.wait_below
+///
+/// # Examples
+///
+/// ```
+/// use hi_tension::{pipe, HiConfig, HiStream};
+/// use std::thread;
+///
+/// # fn main() -> hi_tension::Result<()> {
+/// let (client, server) = pipe();
+/// let consumer = thread::spawn(move || -> hi_tension::Result<Vec<f64>> {
+///     let mut stream = HiStream::server(server, HiConfig::new())?;
+///     stream.read()
+/// });
+///
+/// let mut stream = HiStream::client(client, HiConfig::new())?;
+/// let window = stream.window_handle();
+/// let sender = thread::spawn(move || stream.send(&vec![0.0; 1_000_000]));
+///
+/// // Throttle the next step of the simulation on the drain speed
+/// window.wait_below(1 << 20);
+/// sender.join().unwrap()?;
+/// assert_eq!(window.in_flight_bytes(), 0);
+/// assert_eq!(consumer.join().unwrap()?.len(), 1_000_000);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct FlowWindow {
+    state: Arc<State>,
+}
+
+impl FlowWindow {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, u64> {
+        self.state
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Bytes written but not acknowledged yet.
+    pub fn in_flight_bytes(&self) -> u64 {
+        *self.lock()
+    }
+
+    /// Block until less than `threshold` bytes are in flight.
+    pub fn wait_below(&self, threshold: u64) {
+        let mut in_flight = self.lock();
+        while *in_flight >= threshold {
+            in_flight = self
+                .state
+                .drained
+                .wait(in_flight)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Block until less than `threshold` bytes are in flight, for at most
+    /// `timeout`. Returns whether the window went below the threshold.
+    pub fn wait_below_timeout(&self, threshold: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut in_flight = self.lock();
+        while *in_flight >= threshold {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            in_flight = self
+                .state
+                .drained
+                .wait_timeout(in_flight, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        true
+    }
+
+    /// Account for `bytes` more in flight.
+    pub(crate) fn add(&self, bytes: usize) {
+        *self.lock() += bytes as u64;
+    }
+
+    /// Forget the bytes in flight, once acknowledged or lost.
+    pub(crate) fn clear(&self) {
+        *self.lock() = 0;
+        self.state.drained.notify_all();
+    }
+}
+
+impl fmt::Debug for FlowWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FlowWindow")
+            .field("in_flight_bytes", &self.in_flight_bytes())
+            .finish()
+    }
+}